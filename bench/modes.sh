@@ -0,0 +1,11 @@
+#!/bin/bash
+
+set -e
+cd $(dirname "${BASH_SOURCE[0]}")
+wd=$(pwd)
+
+cd ..
+PJSON_BENCH_DIR=${1:-testfiles} \
+    cargo test modes_bench --release -q -- --ignored --nocapture | \
+    grep "running benchmark" | \
+    sed 's/^running benchmark: //'
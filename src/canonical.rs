@@ -0,0 +1,423 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::number::write_es_number;
+use crate::*;
+
+use std::hash::{BuildHasher, Hasher};
+
+// A container that is being canonicalized. Object members are buffered so
+// that they can be sorted, and so are Arrays inside of Objects. Arrays that
+// are not inside of an Object are written directly to the sink.
+struct Frame {
+    object: bool,
+    direct: bool,
+    empty: bool,
+    out: Vec<u8>,
+    members: Vec<(String, usize, Vec<u8>)>,
+    key: Option<(String, usize)>,
+}
+
+/// Canonicalize JSON using the JSON Canonicalization Scheme (RFC 8785).
+///
+/// The output has no whitespace, Object keys sorted by their UTF-16 code
+/// units, Strings escaped with the minimal set of escape sequences, and
+/// Numbers formatted like ECMAScript, after conversion to an f64.
+///
+/// Returns an error for invalid json, for Objects with duplicate keys, and for
+/// Numbers that overflow an f64. As RFC 8785 requires I-JSON, a String or Key
+/// with invalid UTF-8 or a lone surrogate escape is a Syntax error at its
+/// opening quote, rather than being changed into another document's bytes.
+pub fn canonicalize(json: &[u8]) -> Result<Vec<u8>, ParseError> {
+    let mut doc = Vec::new();
+    canonical_stream(json, &mut |bytes: &[u8]| doc.extend_from_slice(bytes))?;
+    Ok(doc)
+}
+
+/// Feed the canonical form of JSON, as made by canonicalize, into a hasher
+/// without building the whole canonical document. Only the members of each
+/// Object are held in memory, so that they can be sorted.
+///
+/// The canonical bytes are passed to the hasher's write function in pieces, so
+/// the result matches writing the output of canonicalize in one call for
+/// hashers that treat their input as a stream of bytes, like the hasher of
+/// std::collections::hash_map::DefaultHasher. The errors are the same as
+/// those of canonicalize, and the hasher may have been fed some data before an
+/// error is found.
+pub fn canonical_hash<H: Hasher>(json: &[u8], hasher: &mut H) -> Result<(), ParseError> {
+    canonical_stream(json, &mut |bytes: &[u8]| hasher.write(bytes))
+}
+
+/// Hash every record of newline-delimited json by its canonical form, such as
+/// for dropping duplicate records. Each line is a separate record, hashed with
+/// a new hasher from 'build' as by canonical_hash, so records that differ only
+/// in whitespace, key order, or the formatting of Numbers have the same hash.
+///
+/// The 'iter' function is called for every record with the index of its line,
+/// starting at zero, the 'start' and 'end' of the line in the json, and the
+/// hash. Returning 0 stops the hashing. Blank lines are skipped, but they are
+/// still counted in the line index.
+///
+/// Returns the number of records that were reported. The first record that
+/// canonical_hash fails for is an error, with the offset of the error from the
+/// start of the json. The records before it have already been reported.
+pub fn hash_lines<B, F>(json: &[u8], build: &B, iter: F) -> Result<usize, ParseError>
+where
+    B: BuildHasher,
+    F: FnMut(usize, usize, usize, u64) -> i64,
+{
+    let mut iter = iter;
+    let mut count = 0;
+    let mut start = 0;
+    for (index, line) in json.split(|&ch| ch == b'\n').enumerate() {
+        let end = start + line.len();
+        if !line.iter().all(|&ch| isws(ch)) {
+            let mut hasher = build.build_hasher();
+            if let Err(err) = canonical_hash(line, &mut hasher) {
+                return Err(ParseError::new(err.kind, start + err.offset));
+            }
+            count += 1;
+            if iter(index, start, end, hasher.finish()) == 0 {
+                break;
+            }
+        }
+        start = end + 1;
+    }
+    Ok(count)
+}
+
+fn canonical_stream<S>(json: &[u8], sink: &mut S) -> Result<(), ParseError>
+where
+    S: FnMut(&[u8]),
+{
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut err = None;
+    let ret = parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+        if info & (COMMA | COLON) != 0 {
+            return 1;
+        }
+        if info & STRING == STRING && !is_valid_utf8_string(json, start, end, info) {
+            err = Some(ParseError::new(ErrorKind::Syntax, start));
+            return 0;
+        }
+        if info & KEY == KEY {
+            let frame = stack.last_mut().unwrap();
+            frame.key = Some((unescape(json, start, end).into_owned(), start));
+            return 1;
+        }
+        if info & OPEN == OPEN {
+            let object = info & OBJECT == OBJECT;
+            let direct = !object
+                && match stack.last() {
+                    Some(frame) => frame.direct,
+                    None => true,
+                };
+            if direct {
+                if let Some(parent) = stack.last_mut() {
+                    if !parent.empty {
+                        sink(b",");
+                    }
+                    parent.empty = false;
+                }
+                sink(b"[");
+            }
+            stack.push(Frame {
+                object,
+                direct,
+                empty: true,
+                out: Vec::new(),
+                members: Vec::new(),
+                key: None,
+            });
+            return 1;
+        }
+        let mut value = Vec::new();
+        if info & CLOSE == CLOSE {
+            let mut frame = stack.pop().unwrap();
+            if frame.direct {
+                sink(b"]");
+                return 1;
+            }
+            if frame.object {
+                if let Err(e) = write_object(&mut frame.members, &mut value) {
+                    err = Some(e);
+                    return 0;
+                }
+            } else {
+                value.push(b'[');
+                value.extend_from_slice(&frame.out);
+                value.push(b']');
+            }
+        } else if info & STRING == STRING {
+            let text = escape(&unescape(json, start, end), 0);
+            value.extend_from_slice(text.as_bytes());
+        } else if info & NUMBER == NUMBER {
+            match number_f64(json, start, end) {
+                Some(f) if f.is_finite() => write_es_number(f, &mut value),
+                _ => {
+                    err = Some(ParseError::new(ErrorKind::NumberOutOfRange, start));
+                    return 0;
+                }
+            }
+        } else {
+            value.extend_from_slice(&json[start..end]);
+        }
+        match stack.last_mut() {
+            Some(frame) if frame.object => {
+                let (key, offset) = frame.key.take().unwrap();
+                frame.members.push((key, offset, value));
+            }
+            Some(frame) if frame.direct => {
+                if !frame.empty {
+                    sink(b",");
+                }
+                frame.empty = false;
+                sink(&value);
+            }
+            Some(frame) => {
+                if !frame.empty {
+                    frame.out.push(b',');
+                }
+                frame.empty = false;
+                frame.out.extend_from_slice(&value);
+            }
+            None => sink(&value),
+        }
+        1
+    });
+    if let Some(err) = err {
+        return Err(err);
+    }
+    if ret <= 0 {
+        return Err(ParseError::from_ret(json, ret));
+    }
+    Ok(())
+}
+
+fn write_object(
+    members: &mut [(String, usize, Vec<u8>)],
+    out: &mut Vec<u8>,
+) -> Result<(), ParseError> {
+    members.sort_by(|a, b| a.0.encode_utf16().cmp(b.0.encode_utf16()));
+    for i in 1..members.len() {
+        if members[i].0 == members[i - 1].0 {
+            let offset = members[i].1.max(members[i - 1].1);
+            return Err(ParseError::new(ErrorKind::DuplicateKey, offset));
+        }
+    }
+    out.push(b'{');
+    for (i, (key, _, value)) in members.iter().enumerate() {
+        if i > 0 {
+            out.push(b',');
+        }
+        out.extend_from_slice(escape(key, 0).as_bytes());
+        out.push(b':');
+        out.extend_from_slice(value);
+    }
+    out.push(b'}');
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canon(json: &str) -> String {
+        String::from_utf8(canonicalize(json.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn rfc8785_example() {
+        let json = r#"{
+            "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+            "string": "@u20ac$@u000F@u000aA'@u0042@u0022@u005c\\\"\/",
+            "literals": [null, true, false]
+        }"#
+        .replace('@', "\\");
+        assert_eq!(
+            canon(&json),
+            "{\"literals\":[null,true,false],\
+            \"numbers\":[333333333.3333333,1e+30,4.5,0.002,1e-27],\
+            \"string\":\"\u{20ac}$\\u000f\\nA'B\\\"\\\\\\\\\\\"/\"}"
+        );
+    }
+
+    #[test]
+    fn rfc8785_sorting() {
+        let json = r#"{
+            "@u20ac": "Euro Sign",
+            "@r": "Carriage Return",
+            "@ufb33": "Hebrew Letter Dalet With Dagesh",
+            "1": "One",
+            "@ud83d@ude00": "Emoji: Grinning Face",
+            "@u0080": "Control",
+            "@u00f6": "Latin Small Letter O With Diaeresis"
+        }"#
+        .replace('@', "\\");
+        let out = canon(&json);
+        let mut values = Vec::new();
+        parse(
+            out.as_bytes(),
+            0,
+            |start: usize, end: usize, info: usize| -> i64 {
+                if info & (STRING | VALUE) == STRING | VALUE {
+                    values.push(out[start + 1..end - 1].to_string());
+                }
+                1
+            },
+        );
+        assert_eq!(
+            values,
+            [
+                "Carriage Return",
+                "One",
+                "Control",
+                "Latin Small Letter O With Diaeresis",
+                "Euro Sign",
+                "Emoji: Grinning Face",
+                "Hebrew Letter Dalet With Dagesh"
+            ]
+        );
+        assert!(out.starts_with("{\"\\r\":"));
+        assert!(out.contains("\"\u{80}\":"));
+    }
+
+    #[test]
+    fn numbers() {
+        let nums = [
+            ("0", "0"),
+            ("-0", "0"),
+            ("-0.0e-10", "0"),
+            ("1e21", "1e+21"),
+            ("1e20", "100000000000000000000"),
+            ("9007199254740992", "9007199254740992"),
+            ("4.94065645841246544e-324", "5e-324"),
+            ("1.7976931348623157e308", "1.7976931348623157e+308"),
+            ("-1.7976931348623157e308", "-1.7976931348623157e+308"),
+            ("0.000001", "0.000001"),
+            ("1e-7", "1e-7"),
+            ("123e-10", "1.23e-8"),
+            ("295147905179352830000", "295147905179352830000"),
+            ("1.5", "1.5"),
+            ("-12.25e1", "-122.5"),
+            ("100", "100"),
+        ];
+        for (num, expect) in nums {
+            assert_eq!(canon(num), expect, "{}", num);
+        }
+    }
+
+    #[test]
+    fn structure() {
+        assert_eq!(
+            canon(r#" { "b" : [ 1 , { "z" : 1 , "y" : [ ] } ] , "a" : { } } "#),
+            r#"{"a":{},"b":[1,{"y":[],"z":1}]}"#
+        );
+        assert_eq!(canon(r#" "x" "#), r#""x""#);
+        assert_eq!(canon(r#"[]"#), r#"[]"#);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            canonicalize(br#"{"a":1,"b":2,"a":3}"#),
+            Err(ParseError::new(ErrorKind::DuplicateKey, 13))
+        );
+        assert_eq!(
+            canonicalize(br#"[1e400]"#),
+            Err(ParseError::new(ErrorKind::NumberOutOfRange, 1))
+        );
+        assert_eq!(
+            canonicalize(br#"[1,"#),
+            Err(ParseError::new(ErrorKind::UnexpectedEof, 3))
+        );
+        assert_eq!(
+            canonicalize(br#"[1,]"#),
+            Err(ParseError::new(ErrorKind::Syntax, 3))
+        );
+        // Strings and keys must be I-JSON
+        assert_eq!(
+            canonicalize(br#""\ud800""#),
+            Err(ParseError::new(ErrorKind::Syntax, 0))
+        );
+        assert_eq!(
+            canonicalize(b"[1,\"caf\xe9\"]"),
+            Err(ParseError::new(ErrorKind::Syntax, 3))
+        );
+        assert_eq!(
+            canonicalize(br#"{"\ud800":1,"\udfff":2}"#),
+            Err(ParseError::new(ErrorKind::Syntax, 1))
+        );
+        assert_eq!(
+            canonicalize(br#"{"a":1,"\udfff":2}"#),
+            Err(ParseError::new(ErrorKind::Syntax, 7))
+        );
+    }
+
+    #[test]
+    fn hash() {
+        use std::collections::hash_map::DefaultHasher;
+        let jsons = [
+            &br#" { "b" : [ 1 , { "z" : 1 , "y" : [ ] } ] , "a" : { } } "#[..],
+            br#"[[1, [2, {"b": [3, [4]], "a": 5}], []], "x", [{}]]"#,
+            br#""x""#,
+            b"[]",
+            b"1.50",
+        ];
+        for json in jsons.iter() {
+            let mut h1 = DefaultHasher::new();
+            h1.write(&canonicalize(json).unwrap());
+            let mut h2 = DefaultHasher::new();
+            canonical_hash(json, &mut h2).unwrap();
+            assert_eq!(
+                h1.finish(),
+                h2.finish(),
+                "{}",
+                String::from_utf8_lossy(json)
+            );
+        }
+        assert_eq!(
+            String::from_utf8(canonicalize(jsons[1]).unwrap()).unwrap(),
+            r#"[[1,[2,{"a":5,"b":[3,[4]]}],[]],"x",[{}]]"#
+        );
+        let mut h = DefaultHasher::new();
+        assert_eq!(
+            canonical_hash(br#"[{"a":1,"a":2}]"#, &mut h),
+            Err(ParseError::new(ErrorKind::DuplicateKey, 8))
+        );
+    }
+
+    #[test]
+    fn lines() {
+        use std::collections::hash_map::RandomState;
+        use std::collections::HashSet;
+        let json = b"{\"a\":1,\"b\":[1.0]}\n\n{ \"b\": [1], \"a\": 1 }\r\n[2]\n{\"a\":1}\n";
+        let build = RandomState::new();
+        let mut seen = HashSet::new();
+        let mut unique = Vec::new();
+        let ret = hash_lines(json, &build, |index, start, end, hash| {
+            if seen.insert(hash) {
+                unique.push((index, &json[start..end]));
+            }
+            1
+        });
+        assert_eq!(ret, Ok(4));
+        assert_eq!(
+            unique,
+            [
+                (0, &br#"{"a":1,"b":[1.0]}"#[..]),
+                (3, b"[2]"),
+                (4, br#"{"a":1}"#)
+            ]
+        );
+        assert_eq!(
+            hash_lines(json, &build, |index, _, _, _| (index < 2) as i64),
+            Ok(2)
+        );
+        assert_eq!(
+            hash_lines(b"1\n[1,]\n[", &build, |_, _, _, _| 1),
+            Err(ParseError::new(ErrorKind::Syntax, 5))
+        );
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+/// The number of tokens that parse_to_channel buffers ahead of the receiver.
+pub const CHANNEL_BOUND: usize = 1024;
+
+/// Parse JSON on a new thread, sending every element as a Token over a
+/// channel, so that the elements can be consumed on another thread.
+///
+/// The tokens are received in the same order that parse would report them.
+/// The channel holds at most CHANNEL_BOUND tokens, and the parsing thread
+/// blocks when it is full, so the parser never runs far ahead of the receiver.
+/// Dropping the receiver stops the parsing at the next element.
+///
+/// An invalid document is reported by sending an Err as the last item, after
+/// the tokens of the elements that came before the error. The channel is
+/// closed once the document is complete.
+pub fn parse_to_channel(json: Arc<[u8]>, opts: usize) -> Receiver<Result<Token, ParseError>> {
+    let (tx, rx) = sync_channel(CHANNEL_BOUND);
+    thread::spawn(move || {
+        let ret = parse(
+            &json,
+            opts,
+            |start: usize, end: usize, info: usize| -> i64 {
+                match tx.send(Ok(Token { start, end, info })) {
+                    Ok(()) => 1,
+                    Err(_) => 0,
+                }
+            },
+        );
+        if ret <= 0 {
+            let _ = tx.send(Err(ParseError::from_ret(&json, ret)));
+        }
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let json: Arc<[u8]> = Arc::from(&br#"{"a":[1,true],"b":"c"}"#[..]);
+        let rx = parse_to_channel(json.clone(), 0);
+        let tokens: Vec<Token> = rx.iter().map(|token| token.unwrap()).collect();
+        assert_eq!(tokens, parse_to_vec(&json, 0).unwrap());
+        let rx = parse_to_channel(Arc::from(&b"[1,2,}"[..]), 0);
+        let items: Vec<_> = rx.iter().collect();
+        assert_eq!(items.len(), 6);
+        assert_eq!(items[5], Err(ParseError::new(ErrorKind::Syntax, 5)));
+    }
+
+    #[test]
+    fn dropped() {
+        let mut json = vec![b'['];
+        for _ in 0..CHANNEL_BOUND * 4 {
+            json.extend_from_slice(b"1,");
+        }
+        json.extend_from_slice(b"1]");
+        let rx = parse_to_channel(Arc::from(json), 0);
+        assert_eq!(rx.recv().unwrap().unwrap().info, START | OPEN | ARRAY);
+        drop(rx);
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::fmt;
+
+/// A date and time with an offset from UTC, as read by try_datetime.
+///
+/// This is a small type of its own, rather than the OffsetDateTime of the time
+/// crate or the DateTime of chrono, so that the crate stays free of
+/// dependencies. Its getters are enough to build either of those.
+///
+/// The fields are kept as they appear in the json, so two values for the same
+/// instant in different offsets are not equal. Use unix_timestamp to compare
+/// instants.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DateTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    nanosecond: u32,
+    offset: i16,
+}
+
+impl DateTime {
+    /// the year, from 0 to 9999
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+    /// the month, from 1 to 12
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+    /// the day of the month, from 1 to 31
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+    /// the hour, from 0 to 23
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+    /// the minute, from 0 to 59
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+    /// the second, from 0 to 60, where 60 is a leap second
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+    /// the fraction of the second, in nanoseconds
+    pub fn nanosecond(&self) -> u32 {
+        self.nanosecond
+    }
+    /// the offset from UTC in minutes, which is zero for "Z"
+    pub fn offset_minutes(&self) -> i16 {
+        self.offset
+    }
+    /// The number of whole seconds since 1970-01-01T00:00:00Z, without the
+    /// fraction of the second. A leap second counts as the second after it.
+    pub fn unix_timestamp(&self) -> i64 {
+        let days = days_from_civil(self.year as i64, self.month as i64, self.day as i64);
+        let secs = self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        days * 86400 + secs - self.offset as i64 * 60
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )?;
+        if self.nanosecond != 0 {
+            let frac = format!("{:09}", self.nanosecond);
+            write!(f, ".{}", frac.trim_end_matches('0'))?;
+        }
+        if self.offset == 0 {
+            return write!(f, "Z");
+        }
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let offset = self.offset.unsigned_abs();
+        write!(f, "{}{:02}:{:02}", sign, offset / 60, offset % 60)
+    }
+}
+
+/// Read an RFC 3339 timestamp, such as "2017-05-07T13:24:43-07:00", from a
+/// String element. The 'start', 'end', and 'info' params are those of the
+/// element, as passed to the iter function of parse.
+///
+/// The 'T' may also be a 't' or a space, and the 'Z' may also be a 'z'. The
+/// fraction of the second may have any number of digits, of which only the
+/// first nine are kept. Returns None when the range is not a String, or when
+/// the String is not a valid timestamp, such as one with a day that is not in
+/// its month.
+///
+/// ```
+/// let json = br#""2017-05-07T13:24:43.5-07:00""#;
+/// let dt = pjson::try_datetime(json, 0, json.len(), pjson::STRING).unwrap();
+/// assert_eq!((dt.year(), dt.month(), dt.day()), (2017, 5, 7));
+/// assert_eq!(dt.nanosecond(), 500_000_000);
+/// assert_eq!(dt.offset_minutes(), -420);
+/// assert_eq!(dt.unix_timestamp(), 1494188683);
+/// ```
+pub fn try_datetime(json: &[u8], start: usize, end: usize, info: usize) -> Option<DateTime> {
+    if info & STRING != STRING || end - start < 2 || json[start] != b'"' {
+        return None;
+    }
+    if info & ESCAPED == ESCAPED {
+        return parse_datetime(unescape(json, start, end).as_bytes());
+    }
+    parse_datetime(&json[start + 1..end - 1])
+}
+
+fn parse_datetime(s: &[u8]) -> Option<DateTime> {
+    if s.len() < 20
+        || s[4] != b'-'
+        || s[7] != b'-'
+        || !matches!(s[10], b'T' | b't' | b' ')
+        || s[13] != b':'
+        || s[16] != b':'
+    {
+        return None;
+    }
+    let year = digits(s, 0, 4)? as u16;
+    let month = digits(s, 5, 2)? as u8;
+    let day = digits(s, 8, 2)? as u8;
+    let hour = digits(s, 11, 2)? as u8;
+    let minute = digits(s, 14, 2)? as u8;
+    let second = digits(s, 17, 2)? as u8;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let mut i = 19;
+    let mut nanosecond = 0;
+    if s[i] == b'.' {
+        i += 1;
+        let mark = i;
+        while i < s.len() && s[i].is_ascii_digit() {
+            if i - mark < 9 {
+                nanosecond = nanosecond * 10 + (s[i] - b'0') as u32;
+            }
+            i += 1;
+        }
+        if i == mark {
+            return None;
+        }
+        for _ in i - mark..9 {
+            nanosecond *= 10;
+        }
+    }
+    let offset = match s.get(i)? {
+        b'Z' | b'z' if i + 1 == s.len() => 0,
+        b'+' | b'-' if i + 6 == s.len() && s[i + 3] == b':' => {
+            let hours = digits(s, i + 1, 2)?;
+            let minutes = digits(s, i + 4, 2)?;
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = (hours * 60 + minutes) as i16;
+            if s[i] == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+    Some(DateTime {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        nanosecond,
+        offset,
+    })
+}
+
+// Read 'n' ASCII digits at s[i..].
+fn digits(s: &[u8], i: usize, n: usize) -> Option<u32> {
+    let mut r = 0;
+    for &ch in &s[i..i + n] {
+        if !ch.is_ascii_digit() {
+            return None;
+        }
+        r = r * 10 + (ch - b'0') as u32;
+    }
+    Some(r)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 => (days_from_civil(year as i64, 3, 1) - days_from_civil(year as i64, 2, 1)) as u8,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// The number of days from 1970-01-01 to a date in the proleptic Gregorian
+// calendar, from Howard Hinnant's days_from_civil.
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dt(s: &str) -> Option<DateTime> {
+        let json = format!("{:?}", s).into_bytes();
+        let mut out = None;
+        let ret = parse(&json, 0, |start: usize, end: usize, info: usize| -> i64 {
+            out = try_datetime(&json, start, end, info);
+            1
+        });
+        assert_eq!(ret, json.len() as i64);
+        out
+    }
+
+    #[test]
+    fn datetimes() {
+        let d = dt("2017-05-07T13:24:43-07:00").unwrap();
+        assert_eq!(d.to_string(), "2017-05-07T13:24:43-07:00");
+        assert_eq!(d.unix_timestamp(), 1494188683);
+        assert_eq!(dt("1970-01-01T00:00:00Z").unwrap().unix_timestamp(), 0);
+        assert_eq!(dt("1969-12-31t23:59:59z").unwrap().unix_timestamp(), -1);
+        assert_eq!(
+            dt("2000-02-29 12:00:00.123456789123+05:30")
+                .unwrap()
+                .to_string(),
+            "2000-02-29T12:00:00.123456789+05:30"
+        );
+        assert_eq!(
+            dt("2016-12-31T23:59:60.01Z").unwrap().nanosecond(),
+            10_000_000
+        );
+        assert_eq!(
+            dt("2017-05-07T13:24:43-07:00").unwrap().unix_timestamp(),
+            dt("2017-05-07T20:24:43Z").unwrap().unix_timestamp()
+        );
+        for s in [
+            "",
+            "hello",
+            "2017-05-07",
+            "2017-05-07T13:24:43",
+            "2017-05-07T13:24:43+0700",
+            "2017-05-07T13:24:43.Z",
+            "2017-05-07T13:24:43Z ",
+            "2017-13-07T13:24:43Z",
+            "2017-02-29T13:24:43Z",
+            "1900-02-29T13:24:43Z",
+            "2017-05-07T24:00:00Z",
+            "2017-05-07T13:24:43+24:00",
+            "2017-05-07X13:24:43Z",
+            "2017-0a-07T13:24:43Z",
+        ] {
+            assert_eq!(dt(s), None, "{}", s);
+        }
+        let json = br#""2017-05-07T13:24:43\u005a""#;
+        let d = try_datetime(json, 0, json.len(), STRING | ESCAPED).unwrap();
+        assert_eq!(d.hour(), 13);
+        assert_eq!(try_datetime(b"12", 0, 2, NUMBER), None);
+    }
+}
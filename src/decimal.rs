@@ -0,0 +1,223 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::fmt;
+
+/// The largest scale of a Decimal, which is the number of digits after the
+/// decimal point.
+pub const MAX_SCALE: u32 = 28;
+
+/// An exact decimal number, with the value mantissa * 10^-scale.
+///
+/// The scale is kept as it appears in the json, so "1.50" has a mantissa of
+/// 150 and a scale of 2, and is not equal to "1.5". Use normalize to remove
+/// the trailing zeros.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    /// Create a Decimal with the value mantissa * 10^-scale. Returns None if
+    /// the scale is greater than MAX_SCALE.
+    pub fn new(mantissa: i128, scale: u32) -> Option<Decimal> {
+        if scale > MAX_SCALE {
+            return None;
+        }
+        Some(Decimal { mantissa, scale })
+    }
+    /// the digits of the number, without the decimal point
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+    /// the number of digits after the decimal point
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+    /// Returns the same number without trailing zeros after the decimal
+    /// point, such that "1.50" becomes "1.5".
+    pub fn normalize(&self) -> Decimal {
+        let mut d = *self;
+        while d.scale > 0 && d.mantissa % 10 == 0 {
+            d.mantissa /= 10;
+            d.scale -= 1;
+        }
+        d
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        if self.mantissa < 0 {
+            write!(f, "-")?;
+        }
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}", digits);
+        }
+        if digits.len() > scale {
+            let (int, frac) = digits.split_at(digits.len() - scale);
+            write!(f, "{}.{}", int, frac)
+        } else {
+            write!(f, "0.{:0>width$}", digits, width = scale)
+        }
+    }
+}
+
+/// Convert a Number element to an exact Decimal, without going through a
+/// float. The 'start', 'end', and 'info' params are those of a Number element,
+/// as passed to the iter function of parse. The info bits are used to skip
+/// the fraction and exponent handling when they are absent.
+///
+/// Returns None when the range is not a Number, or when the number does not
+/// fit in a Decimal because its mantissa overflows an i128 or it has more
+/// than MAX_SCALE digits after the decimal point.
+pub fn number_decimal(json: &[u8], start: usize, end: usize, info: usize) -> Option<Decimal> {
+    let num = &json[start..end];
+    let mut i = 0;
+    let neg = num.first() == Some(&b'-');
+    if neg {
+        i += 1;
+    }
+    let mut mantissa: i128 = 0;
+    let mut scale: i64 = 0;
+    let mark = i;
+    while i < num.len() && num[i].is_ascii_digit() {
+        mantissa = mantissa
+            .checked_mul(10)?
+            .checked_add((num[i] - b'0') as i128)?;
+        i += 1;
+    }
+    if i == mark {
+        return None;
+    }
+    if info & DOT == DOT && i < num.len() && num[i] == b'.' {
+        i += 1;
+        while i < num.len() && num[i].is_ascii_digit() {
+            mantissa = mantissa
+                .checked_mul(10)?
+                .checked_add((num[i] - b'0') as i128)?;
+            scale += 1;
+            i += 1;
+        }
+    }
+    if info & E == E && i < num.len() && (num[i] | 0x20) == b'e' {
+        i += 1;
+        let mut eneg = false;
+        if i < num.len() && (num[i] == b'-' || num[i] == b'+') {
+            eneg = num[i] == b'-';
+            i += 1;
+        }
+        let mut exp: i64 = 0;
+        while i < num.len() && num[i].is_ascii_digit() {
+            // any exponent this large is out of range anyway
+            exp = (exp * 10 + (num[i] - b'0') as i64).min(1 << 32);
+            i += 1;
+        }
+        scale += if eneg { exp } else { -exp };
+    }
+    if i != num.len() {
+        return None;
+    }
+    if scale < 0 && mantissa == 0 {
+        scale = 0;
+    } else if scale < -38 {
+        // any nonzero mantissa overflows an i128 at 10^39
+        return None;
+    }
+    while scale < 0 {
+        mantissa = mantissa.checked_mul(10)?;
+        scale += 1;
+    }
+    while scale > MAX_SCALE as i64 && mantissa % 10 == 0 && mantissa != 0 {
+        mantissa /= 10;
+        scale -= 1;
+    }
+    if mantissa == 0 {
+        scale = scale.min(MAX_SCALE as i64);
+    }
+    if neg {
+        mantissa = -mantissa;
+    }
+    if scale > MAX_SCALE as i64 {
+        return None;
+    }
+    Decimal::new(mantissa, scale as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(num: &str) -> Option<String> {
+        let json = num.as_bytes();
+        let mut out = None;
+        let ret = parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+            out = number_decimal(json, start, end, info);
+            1
+        });
+        assert_eq!(ret, json.len() as i64);
+        Some(out?.to_string())
+    }
+
+    #[test]
+    fn decimals() {
+        assert_eq!(dec("0").as_deref(), Some("0"));
+        assert_eq!(dec("-12").as_deref(), Some("-12"));
+        assert_eq!(dec("1.50").as_deref(), Some("1.50"));
+        assert_eq!(dec("-0.000123").as_deref(), Some("-0.000123"));
+        assert_eq!(dec("1.5e3").as_deref(), Some("1500"));
+        assert_eq!(dec("15E-4").as_deref(), Some("0.0015"));
+        assert_eq!(dec("1e+2").as_deref(), Some("100"));
+        assert_eq!(
+            dec("0.1e-27").as_deref(),
+            Some("0.0000000000000000000000000001")
+        );
+        assert_eq!(dec("0.1e-28"), None);
+        assert_eq!(
+            dec("0e-99").as_deref(),
+            Some("0.0000000000000000000000000000")
+        );
+        assert_eq!(
+            dec("10e-29").as_deref(),
+            Some("0.0000000000000000000000000001")
+        );
+        assert_eq!(
+            dec("1e38").as_deref(),
+            Some(&*format!("1{}", "0".repeat(38)))
+        );
+        assert_eq!(dec("1e39"), None);
+        assert_eq!(dec("1e99999999999999999999"), None);
+        assert_eq!(dec("0e99999999999999999999").as_deref(), Some("0"));
+        assert_eq!(dec("-0.000e4294967296").as_deref(), Some("0"));
+        assert_eq!(dec("0.000e4294967296").as_deref(), Some("0"));
+        assert_eq!(dec("0.000e2").as_deref(), Some("0.0"));
+        assert_eq!(
+            dec("170141183460469231731687303715884105727").as_deref(),
+            Some("170141183460469231731687303715884105727")
+        );
+        assert_eq!(dec("170141183460469231731687303715884105728"), None);
+        // the money use case, where f64 would lose precision
+        assert_eq!(
+            dec("12345678901234567.89").as_deref(),
+            Some("12345678901234567.89")
+        );
+    }
+
+    #[test]
+    fn values() {
+        let d = number_decimal(b"1.50", 0, 4, NUMBER | DOT).unwrap();
+        assert_eq!((d.mantissa(), d.scale()), (150, 2));
+        assert_eq!(d.normalize(), Decimal::new(15, 1).unwrap());
+        assert_ne!(d, d.normalize());
+        assert_eq!(Decimal::new(1, 29), None);
+        // the info bits must describe the number
+        assert_eq!(number_decimal(b"1.5", 0, 3, NUMBER), None);
+        assert_eq!(number_decimal(b"true", 0, 4, TRUE), None);
+        assert_eq!(number_decimal(b"-", 0, 1, NUMBER | SIGN), None);
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::borrow::Cow;
+
+/// An element of a JSON document with its value already decoded, as passed to
+/// the iter function of parse_decoded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodedEvent<'a> {
+    /// the '{' of an Object
+    ObjectBegin,
+    /// the '}' of an Object
+    ObjectEnd,
+    /// the '[' of an Array
+    ArrayBegin,
+    /// the ']' of an Array
+    ArrayEnd,
+    /// an Object key, which is followed by the member's value
+    Key(Cow<'a, str>),
+    /// a String value
+    Str(Cow<'a, str>),
+    /// a Number without a fraction or an exponent that fits in an i64
+    Int(i64),
+    /// any other Number
+    Float(f64),
+    /// a True or False value
+    Bool(bool),
+    /// a Null value
+    Null,
+}
+
+/// What parse_decoded should do after an element, as returned by its iter
+/// function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Control {
+    /// keep parsing
+    Continue,
+    /// skip the children of the Object or Array that was just begun, and
+    /// continue with its end. This works like Continue for other elements.
+    Skip,
+    /// stop parsing
+    Stop,
+}
+
+/// Parse JSON and call 'iter' for every value and Object key, with Strings
+/// and Numbers already decoded. This works like parse, but trades some speed
+/// for not having to decode the elements by hand.
+///
+/// A String or key that is not valid UTF-8 is a Syntax error at its offset,
+/// and a Number that is too large for an f64 is a NumberOutOfRange error. The
+/// status is the same as for parse_status, with Stopped when 'iter' returned
+/// Control::Stop.
+pub fn parse_decoded<'a, F>(json: &'a [u8], opts: usize, iter: F) -> ParseStatus
+where
+    F: FnMut(DecodedEvent<'a>) -> Control,
+{
+    let mut iter = iter;
+    let mut err = None;
+    let opts = opts & !EVENT_OPTS;
+    let status = parse_status(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        let event = if info & (COMMA | COLON) != 0 {
+            return 1;
+        } else if info & OPEN == OPEN {
+            if info & OBJECT == OBJECT {
+                DecodedEvent::ObjectBegin
+            } else {
+                DecodedEvent::ArrayBegin
+            }
+        } else if info & CLOSE == CLOSE {
+            if info & OBJECT == OBJECT {
+                DecodedEvent::ObjectEnd
+            } else {
+                DecodedEvent::ArrayEnd
+            }
+        } else if info & STRING == STRING {
+            if !is_valid_utf8_string(json, start, end, info) {
+                err = Some(ParseError::new(ErrorKind::Syntax, start));
+                return 0;
+            }
+            let s = unescape(json, start, end);
+            if info & KEY == KEY {
+                DecodedEvent::Key(s)
+            } else {
+                DecodedEvent::Str(s)
+            }
+        } else if info & NUMBER == NUMBER {
+            match number_i64(json, start, end) {
+                Some(n) => DecodedEvent::Int(n),
+                None => match number_f64(json, start, end) {
+                    Some(f) if f.is_finite() => DecodedEvent::Float(f),
+                    _ => {
+                        err = Some(ParseError::new(ErrorKind::NumberOutOfRange, start));
+                        return 0;
+                    }
+                },
+            }
+        } else if info & (TRUE | FALSE) != 0 {
+            DecodedEvent::Bool(info & TRUE == TRUE)
+        } else {
+            DecodedEvent::Null
+        };
+        match iter(event) {
+            Control::Continue => 1,
+            Control::Skip => -1,
+            Control::Stop => 0,
+        }
+    });
+    match err {
+        Some(err) => ParseStatus::Error(err),
+        None => status,
+    }
+}
+
+/// Parse JSON and pass every String and Object key to 'iter' both as its raw
+/// range and as its unescaped text, such as for a tool that compares the text
+/// but writes the original bytes back out. This works just like parse, except
+/// that 'iter' is also passed the text for each STRING element, and None for
+/// all other elements.
+///
+/// The text is borrowed from the json when the element has no escapes and is
+/// valid UTF-8. Otherwise it is unescaped into a new String for every element,
+/// which costs about as much as the parsing itself, so use parse and unescape
+/// only the elements that are needed when that matters.
+///
+/// The return value has the same meaning as for parse.
+pub fn parse_unescaped<'a, F>(json: &'a [u8], opts: usize, iter: F) -> i64
+where
+    F: FnMut(usize, usize, usize, Option<Cow<'a, str>>) -> i64,
+{
+    let mut iter = iter;
+    parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        if info & STRING != STRING {
+            return iter(start, end, info, None);
+        }
+        let text = match key_inner(json, start, end, info) {
+            Some(text) => Cow::Borrowed(text),
+            None => unescape(json, start, end),
+        };
+        iter(start, end, info, Some(text))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DecodedEvent::*;
+
+    fn events(json: &[u8]) -> Vec<DecodedEvent<'_>> {
+        let mut out = Vec::new();
+        let status = parse_decoded(json, 0, |event| {
+            out.push(event);
+            Control::Continue
+        });
+        assert_eq!(status, ParseStatus::Complete(json.len()));
+        out
+    }
+
+    #[test]
+    fn decoded() {
+        let json =
+            br#"{"a\tb":["x\u00e9",-12,1.5,1e2,99999999999999999999,true,false,null],"c":{}}"#;
+        assert_eq!(
+            events(json),
+            [
+                ObjectBegin,
+                Key("a\tb".into()),
+                ArrayBegin,
+                Str("x\u{e9}".into()),
+                Int(-12),
+                Float(1.5),
+                Float(100.0),
+                Float(1e20),
+                Bool(true),
+                Bool(false),
+                Null,
+                ArrayEnd,
+                Key("c".into()),
+                ObjectBegin,
+                ObjectEnd,
+                ObjectEnd,
+            ]
+        );
+        assert!(matches!(
+            events(br#""plain""#)[0],
+            Str(Cow::Borrowed("plain"))
+        ));
+        assert_eq!(events(b" 0 "), [Int(0)]);
+    }
+
+    #[test]
+    fn control() {
+        let json = br#"[{"a":1},[2],3]"#;
+        let mut out = Vec::new();
+        let status = parse_decoded(json, 0, |event| {
+            let control = match event {
+                ObjectBegin | ArrayBegin if !out.is_empty() => Control::Skip,
+                Int(3) => Control::Stop,
+                _ => Control::Continue,
+            };
+            out.push(event);
+            control
+        });
+        assert_eq!(status, ParseStatus::Stopped(14));
+        assert_eq!(
+            out,
+            [
+                ArrayBegin,
+                ObjectBegin,
+                ObjectEnd,
+                ArrayBegin,
+                ArrayEnd,
+                Int(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let status = |json: &[u8]| parse_decoded(json, 0, |_| Control::Continue);
+        let err = |kind, offset| ParseStatus::Error(ParseError::new(kind, offset));
+        assert_eq!(status(b"[1,1e999]"), err(ErrorKind::NumberOutOfRange, 3));
+        assert_eq!(status(b"[\"\xff\"]"), err(ErrorKind::Syntax, 1));
+        assert_eq!(status(br#"{"\ud800":1}"#), err(ErrorKind::Syntax, 1));
+        assert_eq!(status(b"[1,]"), err(ErrorKind::Syntax, 3));
+    }
+
+    #[test]
+    fn unescaped() {
+        let json = br#"{"a\u0062":["x\ny","plain",1]}"#;
+        let mut out = Vec::new();
+        let ret = parse_unescaped(json, 0, |start, end, info, text| {
+            assert_eq!(text.is_some(), info & STRING == STRING);
+            if let Some(text) = text {
+                let raw = std::str::from_utf8(&json[start..end]).unwrap();
+                out.push((raw, text, info & KEY == KEY));
+            }
+            1
+        });
+        assert_eq!(ret, json.len() as i64);
+        assert_eq!(
+            out,
+            [
+                (r#""a\u0062""#, Cow::from("ab"), true),
+                (r#""x\ny""#, Cow::from("x\ny"), false),
+                (r#""plain""#, Cow::from("plain"), false),
+            ]
+        );
+        assert!(matches!(out[2].1, Cow::Borrowed(_)));
+        assert_eq!(
+            parse_unescaped(json, 0, |_, _, _, text| text.is_none() as i64),
+            10
+        );
+    }
+}
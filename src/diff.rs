@@ -0,0 +1,209 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::collections::HashMap;
+
+/// A difference between two JSON documents, as returned by diff. The path is
+/// made in the same way as for parse_paths, such as "friends.0.first".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// the path is only in the new document
+    Added(String),
+    /// the path is only in the old document
+    Removed(String),
+    /// the path has a different value, with the ranges of the old and new
+    /// values in their documents
+    Modified(String, (usize, usize), (usize, usize)),
+}
+
+enum Work {
+    Compare(usize, usize, String),
+    Emit(Change),
+}
+
+/// Compare two JSON documents by path and report the differences, in the
+/// order of the paths in the old document followed by those that were added.
+///
+/// Values are compared in the same way as for equal, so Object keys can be in
+/// any order, and Strings and Numbers are compared by value. The elements of
+/// Arrays are compared by their index. A value that changes type, such as from
+/// an Object to an Array, is a single Modified change. When a key appears more
+/// than once the last one wins. It is an error if either document is invalid.
+pub fn diff(a: &[u8], b: &[u8]) -> Result<Vec<Change>, ParseError> {
+    let ta = to_tape(a, 0)?;
+    let tb = to_tape(b, 0)?;
+    let mut changes = Vec::new();
+    let mut work = vec![Work::Compare(0, 0, String::new())];
+    while let Some(item) = work.pop() {
+        let (ia, ib, path) = match item {
+            Work::Compare(ia, ib, path) => (ia, ib, path),
+            Work::Emit(change) => {
+                changes.push(change);
+                continue;
+            }
+        };
+        let (ea, eb) = (&ta[ia], &tb[ib]);
+        let mut next = Vec::new();
+        match (ea.kind, eb.kind) {
+            (TapeKind::ObjectStart, TapeKind::ObjectStart) => {
+                let ma = members(a, &ta, ia);
+                let mb = members(b, &tb, ib);
+                let lookup: HashMap<&str, usize> =
+                    mb.iter().map(|(key, i)| (key.as_str(), *i)).collect();
+                for (key, i) in ma.iter() {
+                    let path = join(&path, key);
+                    next.push(match lookup.get(key.as_str()) {
+                        Some(&j) => Work::Compare(*i, j, path),
+                        None => Work::Emit(Change::Removed(path)),
+                    });
+                }
+                let lookup: HashMap<&str, usize> =
+                    ma.iter().map(|(key, i)| (key.as_str(), *i)).collect();
+                for (key, _) in mb.iter() {
+                    if !lookup.contains_key(key.as_str()) {
+                        next.push(Work::Emit(Change::Added(join(&path, key))));
+                    }
+                }
+            }
+            (TapeKind::ArrayStart, TapeKind::ArrayStart) => {
+                let va = children(&ta, ia);
+                let vb = children(&tb, ib);
+                for k in 0..va.len().max(vb.len()) {
+                    let path = join(&path, &k.to_string());
+                    next.push(match (va.get(k), vb.get(k)) {
+                        (Some(&i), Some(&j)) => Work::Compare(i, j, path),
+                        (Some(_), None) => Work::Emit(Change::Removed(path)),
+                        _ => Work::Emit(Change::Added(path)),
+                    });
+                }
+            }
+            _ => {
+                if !scalar_equal(a, ea, b, eb) {
+                    let ra = (ea.start, ta[ea.link].end);
+                    let rb = (eb.start, tb[eb.link].end);
+                    changes.push(Change::Modified(path, ra, rb));
+                }
+            }
+        }
+        // in reverse, so that the work is done in order
+        work.extend(next.into_iter().rev());
+    }
+    Ok(changes)
+}
+
+// join a key or index onto a path
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+// the children of the container at tape[i], skipping over nested containers
+fn children(tape: &[TapeEntry], i: usize) -> Vec<usize> {
+    let mut out = Vec::new();
+    let mut j = i + 1;
+    while j < tape[i].link {
+        out.push(j);
+        j = match tape[j].kind {
+            TapeKind::ObjectStart | TapeKind::ArrayStart => tape[j].link + 1,
+            _ => j + 1,
+        };
+    }
+    out
+}
+
+// the decoded keys of the Object at tape[i] and the tape index of their
+// values, keeping only the last member of a duplicate key
+fn members(json: &[u8], tape: &[TapeEntry], i: usize) -> Vec<(String, usize)> {
+    let mut out: Vec<(String, usize)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for pair in children(tape, i).chunks(2) {
+        let key = unescape(json, tape[pair[0]].start, tape[pair[0]].end).into_owned();
+        match index.get(&key) {
+            Some(&k) => out[k].1 = pair[1],
+            None => {
+                index.insert(key.clone(), out.len());
+                out.push((key, pair[1]));
+            }
+        }
+    }
+    out
+}
+
+fn scalar_equal(a: &[u8], ea: &TapeEntry, b: &[u8], eb: &TapeEntry) -> bool {
+    if ea.kind != eb.kind {
+        return false;
+    }
+    match ea.kind {
+        TapeKind::String => unescape(a, ea.start, ea.end) == unescape(b, eb.start, eb.end),
+        TapeKind::Number => {
+            let info = |json: &[u8], e: &TapeEntry| -> usize {
+                let n = &json[e.start..e.end];
+                let mut info = 0;
+                if n[0] == b'-' {
+                    info |= SIGN;
+                }
+                if n.contains(&b'.') {
+                    info |= DOT;
+                }
+                if n.iter().any(|&ch| ch == b'e' || ch == b'E') {
+                    info |= E;
+                }
+                info
+            };
+            number_canonical(a, ea.start, ea.end, info(a, ea))
+                == number_canonical(b, eb.start, eb.end, info(b, eb))
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes() {
+        let a = br#"{"name":"tom","age":37,"tags":["a","b"],"pets":{"cat":1},"x":[1]}"#;
+        let b = br#"{"age":3.7e1,"name":"sam","tags":["a"],"pets":{"cat":1,"dog":2},"x":{},"new":null}"#;
+        let changes = diff(a, b).unwrap();
+        assert_eq!(
+            changes,
+            [
+                Change::Modified("name".to_string(), (8, 13), (20, 25)),
+                Change::Removed("tags.1".to_string()),
+                Change::Added("pets.dog".to_string()),
+                Change::Modified("x".to_string(), (61, 64), (68, 70)),
+                Change::Added("new".to_string()),
+            ]
+        );
+        assert_eq!(&a[8..13], br#""tom""#);
+        assert_eq!(&b[20..25], br#""sam""#);
+        assert_eq!(&a[61..64], b"[1]");
+        assert_eq!(&b[68..70], b"{}");
+    }
+
+    #[test]
+    fn same() {
+        assert!(diff(
+            br#"{"a":[1,{"b":"c"}]}"#,
+            br#"{ "a" : [ 1e0 , { "b" : "c" } ] }"#
+        )
+        .unwrap()
+        .is_empty());
+        assert!(diff(b"[]", b"[]").unwrap().is_empty());
+        assert_eq!(
+            diff(b"1", b"true").unwrap(),
+            [Change::Modified(String::new(), (0, 1), (0, 4))]
+        );
+        assert_eq!(diff(br#"{"a":1,"a":2}"#, br#"{"a":2}"#).unwrap(), []);
+        assert_eq!(
+            diff(b"[1]", b"[1,"),
+            Err(ParseError::new(ErrorKind::UnexpectedEof, 3))
+        );
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+
+/// Parse JSON and call 'iter' once for every Object member, at any depth, with
+/// the (start, end, info) of its key and of its value, in the order that they
+/// appear in the json.
+///
+/// For an Object or Array value, the value is its OPEN element. The full span
+/// of the container can be found with parse_value on json[start..]. Returning
+/// -1 from 'iter' skips the children of a container value, and returning 0
+/// stops the parsing, just like for parse.
+///
+/// Only Object members are reported. The elements of Arrays and a root value
+/// are not, but the members of Objects that are inside of them are. The return
+/// value has the same meaning as for parse.
+pub fn parse_entries<F>(json: &[u8], opts: usize, iter: F) -> i64
+where
+    F: FnMut((usize, usize, usize), (usize, usize, usize)) -> i64,
+{
+    let mut iter = iter;
+    let mut key = None;
+    let opts = opts & !EVENT_OPTS;
+    parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        if info & KEY == KEY {
+            key = Some((start, end, info));
+        } else if info & (VALUE | CLOSE) == VALUE {
+            // only the values of Object members have a key before them
+            if let Some(key) = key.take() {
+                return iter(key, (start, end, info));
+            }
+        }
+        1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(json: &[u8], skip: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        let ret = parse_entries(json, 0, |key, value| -> i64 {
+            let key = unescape(json, key.0, key.1);
+            out.push(format!(
+                "{}={}",
+                key,
+                std::str::from_utf8(&json[value.0..value.1]).unwrap()
+            ));
+            if key == skip {
+                -1
+            } else {
+                1
+            }
+        });
+        assert_eq!(ret, json.len() as i64);
+        out
+    }
+
+    #[test]
+    fn members() {
+        let json = br#"{"a":1,"b":{"c":"x","d":[true,{"e":null}]},"f":[]}"#;
+        assert_eq!(
+            entries(json, ""),
+            ["a=1", "b={", r#"c="x""#, "d=[", "e=null", "f=["]
+        );
+        assert_eq!(entries(json, "b"), ["a=1", "b={", "f=["]);
+        assert_eq!(entries(br#"[1,{"ab":2},[{}]]"#, ""), ["ab=2"]);
+        assert!(entries(b" 1 ", "").is_empty());
+        assert!(entries(b"{}", "").is_empty());
+    }
+
+    #[test]
+    fn span() {
+        let json = br#"{"a":{"b":[1,2]},"c":3}"#;
+        let mut spans = Vec::new();
+        parse_entries(json, 0, |_, value| -> i64 {
+            let (token, _) = parse_value(&json[value.0..]).unwrap();
+            spans.push(&json[value.0..value.0 + token.end]);
+            -1
+        });
+        assert_eq!(spans, [&br#"{"b":[1,2]}"#[..], b"3"]);
+    }
+
+    #[test]
+    fn stop() {
+        let json = br#"{"a":1,"b":2,"c":3}"#;
+        let mut keys = Vec::new();
+        let ret = parse_entries(json, 0, |key, _| -> i64 {
+            keys.push(key.0);
+            if keys.len() == 2 {
+                0
+            } else {
+                1
+            }
+        });
+        assert!(ret > 0 && (ret as usize) < json.len());
+        assert_eq!(keys, [1, 7]);
+        assert!(parse_entries(br#"{"a":1,}"#, 0, |_, _| 1) <= 0);
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::borrow::Cow;
+
+// A document in a form that can be compared, with decoded strings, canonical
+// numbers, and objects sorted by key.
+#[derive(PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+struct Comparable;
+
+impl TreeBuilder for Comparable {
+    type Value = Value;
+    type Object = Vec<(String, Value)>;
+    type Array = Vec<Value>;
+    fn begin_object(&mut self) -> Self::Object {
+        Vec::new()
+    }
+    fn key(&mut self, object: &mut Self::Object, key: Cow<'_, str>, value: Value) {
+        object.push((key.into_owned(), value));
+    }
+    fn end_object(&mut self, mut object: Self::Object) -> Value {
+        // stable, so the members of duplicate keys keep their order
+        object.sort_by(|a, b| a.0.cmp(&b.0));
+        Value::Object(object)
+    }
+    fn begin_array(&mut self) -> Self::Array {
+        Vec::new()
+    }
+    fn element(&mut self, array: &mut Self::Array, value: Value) {
+        array.push(value);
+    }
+    fn end_array(&mut self, array: Self::Array) -> Value {
+        Value::Array(array)
+    }
+    fn string(&mut self, s: Cow<'_, str>) -> Value {
+        Value::String(s.into_owned())
+    }
+    fn number(&mut self, num: &str, info: usize) -> Value {
+        Value::Number(number_canonical(num.as_bytes(), 0, num.len(), info))
+    }
+    fn bool(&mut self, b: bool) -> Value {
+        Value::Bool(b)
+    }
+    fn null(&mut self) -> Value {
+        Value::Null
+    }
+}
+
+/// Returns true if two JSON documents are equal by value, ignoring whitespace,
+/// the order of Object keys, and the text form of Numbers and Strings.
+///
+/// Strings are compared by their decoded value, and Numbers by their
+/// number_canonical form, so "1e2" equals "100". Objects with duplicate keys
+/// are equal when they have the same members for each key, in the same order.
+/// Returns false if either document is invalid.
+pub fn equal(a: &[u8], b: &[u8]) -> bool {
+    match (build(a, 0, &mut Comparable), build(b, 0, &mut Comparable)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equality() {
+        let pairs: [(&[u8], &[u8]); 8] = [
+            (b"1", b" 1 "),
+            (br#"{"a":1,"b":2}"#, br#"{ "b" : 2 , "a" : 1 }"#),
+            (br#"[1e2, 0.5, -0]"#, br#"[100, 5e-1, 0]"#),
+            (br#""caf\u00e9""#, "\"caf\u{e9}\"".as_bytes()),
+            (
+                br#"{"a":{"x":[true,null]},"b":"\/"}"#,
+                br#"{"b":"/","a":{"x":[true,null]}}"#,
+            ),
+            (br#"{"a":1,"a":2}"#, br#"{"a":1,"a":2}"#),
+            (br#"{"a":1,"b":0,"a":2}"#, br#"{"a":1,"a":2,"b":0}"#),
+            (b"[]", b"[ ]"),
+        ];
+        for (a, b) in pairs.iter() {
+            assert!(
+                equal(a, b),
+                "{} {}",
+                String::from_utf8_lossy(a),
+                String::from_utf8_lossy(b)
+            );
+        }
+        let pairs: [(&[u8], &[u8]); 9] = [
+            (b"1", b"2"),
+            (b"1", b"\"1\""),
+            (b"[1,2]", b"[2,1]"),
+            (br#"{"a":1}"#, br#"{"a":1,"b":2}"#),
+            (br#"{"a":1,"a":2}"#, br#"{"a":2,"a":1}"#),
+            (b"null", b"false"),
+            (b"[]", b"{}"),
+            (b"9007199254740993", b"9007199254740992"),
+            (b"[1,]", b"[1,]"),
+        ];
+        for (a, b) in pairs.iter() {
+            assert!(
+                !equal(a, b),
+                "{} {}",
+                String::from_utf8_lossy(a),
+                String::from_utf8_lossy(b)
+            );
+        }
+    }
+}
@@ -0,0 +1,360 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use std::fmt;
+
+/// The kind of error that occurred while parsing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// the json is not valid
+    Syntax,
+    /// the json ended before the document was complete
+    UnexpectedEof,
+    /// an Object has the same key more than once
+    DuplicateKey,
+    /// a Number cannot be represented as an f64
+    NumberOutOfRange,
+    /// the json is larger than the size limit of the options
+    LimitExceeded,
+    /// the json ended with a String escape that has only its backslash
+    UnterminatedEscape,
+    /// the json ended inside the hex digits of a String '\u' escape
+    InvalidUnicodeEscape,
+    /// the root is not an Object or Array, with the CONTAINER_ROOT option
+    ScalarRoot,
+}
+
+/// An error that occurred while parsing JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// the kind of error
+    pub kind: ErrorKind,
+    /// the position in the json where the error was discovered
+    pub offset: usize,
+}
+
+impl ParseError {
+    /// Create a new error.
+    pub fn new(kind: ErrorKind, offset: usize) -> ParseError {
+        ParseError { kind, offset }
+    }
+
+    // Create an error from a zero or negative return value of parse.
+    pub(crate) fn from_ret(json: &[u8], ret: i64) -> ParseError {
+        let offset = (-ret) as usize;
+        if offset >= json.len() {
+            if let Some((kind, offset)) = eof_escape(json) {
+                return ParseError::new(kind, offset);
+            }
+            ParseError::new(ErrorKind::UnexpectedEof, offset)
+        } else {
+            ParseError::new(ErrorKind::Syntax, offset)
+        }
+    }
+}
+
+// Returns the kind and the backslash position of an escape that was cut off
+// by the end of the json. Only a String can hold a backslash when the parser
+// reaches the end, so the tail alone is checked.
+fn eof_escape(json: &[u8]) -> Option<(ErrorKind, usize)> {
+    let mut j = json.len();
+    while j > 0 && json.len() - j < 3 && json[j - 1].is_ascii_hexdigit() {
+        j -= 1;
+    }
+    let kind = if j > 0 && json[j - 1] == b'u' {
+        j -= 1;
+        ErrorKind::InvalidUnicodeEscape
+    } else if j == json.len() {
+        ErrorKind::UnterminatedEscape
+    } else {
+        return None;
+    };
+    // the backslash must not itself be escaped
+    let slashes = json[..j]
+        .iter()
+        .rev()
+        .take_while(|&&ch| ch == b'\\')
+        .count();
+    if slashes % 2 == 1 {
+        Some((kind, j - 1))
+    } else {
+        None
+    }
+}
+
+/// The outcome of parse_status.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseStatus {
+    /// the whole document is valid, with the length of the json
+    Complete(usize),
+    /// 'iter' stopped the parsing, with the position the parser was at
+    Stopped(usize),
+    /// the document is invalid
+    Error(ParseError),
+}
+
+/// The offsets of where the parsing ended, as returned by parse_stop_offsets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct StopOffsets {
+    /// the end of the element that the parsing was stopped on, or the length
+    /// of the json for a complete document
+    pub stop_offset: usize,
+    /// the position of the next token after stop_offset, past any whitespace,
+    /// or the length of the json when there is none
+    pub next_token_offset: usize,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::Syntax => "invalid json",
+            ErrorKind::UnexpectedEof => "unexpected end of json",
+            ErrorKind::DuplicateKey => "duplicate object key",
+            ErrorKind::NumberOutOfRange => "number out of range",
+            ErrorKind::LimitExceeded => "size limit exceeded",
+            ErrorKind::UnterminatedEscape => "unterminated escape",
+            ErrorKind::InvalidUnicodeEscape => "invalid unicode escape",
+            ErrorKind::ScalarRoot => "root is not an object or array",
+        })
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Convert a byte offset into a 1-based line and column. Lines are separated
+/// by '\n' and the column counts UTF-8 characters, not bytes.
+/// An offset past the end of the json is clamped to the end.
+pub fn offset_to_line_col(json: &[u8], offset: usize) -> (usize, usize) {
+    let offset = offset.min(json.len());
+    let mut line = 1;
+    let mut line_start = 0;
+    for (i, &ch) in json[..offset].iter().enumerate() {
+        if ch == b'\n' {
+            line += 1;
+            line_start = i + 1;
+        }
+    }
+    let col = json[line_start..offset]
+        .iter()
+        .filter(|&&ch| ch & 0xC0 != 0x80)
+        .count();
+    (line, col + 1)
+}
+
+/// An index of the line starts in a json document, for converting many offsets
+/// into lines and columns without scanning from the start of the json each
+/// time. The results are the same as for offset_to_line_col.
+///
+/// ```
+/// let json = b"{\n  \"a\": x\n}";
+/// let lines = pjson::LineIndex::new(json);
+/// assert_eq!(lines.line_col(9), (2, 8));
+/// assert_eq!(lines.line_col(11), (3, 1));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LineIndex<'a> {
+    json: &'a [u8],
+    // the offset of the first byte of each line after the first
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    /// Build the index for a json document.
+    pub fn new(json: &'a [u8]) -> LineIndex<'a> {
+        let starts = json
+            .iter()
+            .enumerate()
+            .filter(|(_, &ch)| ch == b'\n')
+            .map(|(i, _)| i + 1)
+            .collect();
+        LineIndex { json, starts }
+    }
+
+    /// Convert a byte offset into a 1-based line and column, where the column
+    /// counts UTF-8 characters. An offset past the end of the json is clamped
+    /// to the end.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.json.len());
+        let line = self.starts.partition_point(|&start| start <= offset);
+        let line_start = if line == 0 { 0 } else { self.starts[line - 1] };
+        let col = self.json[line_start..offset]
+            .iter()
+            .filter(|&&ch| ch & 0xC0 != 0x80)
+            .count();
+        (line + 1, col + 1)
+    }
+
+    /// The number of lines in the json, which is one more than the number of
+    /// '\n' characters.
+    pub fn lines(&self) -> usize {
+        self.starts.len() + 1
+    }
+}
+
+/// Returns a snippet of the json around an offset, with a caret on the
+/// following line pointing at the exact position. Up to 'radius' bytes are
+/// shown on each side of the offset, without crossing line boundaries.
+/// Control characters and invalid UTF-8 are shown as '\xNN' escapes.
+///
+/// ```
+/// let json = br#"{"a": [1, 2,, 3]}"#;
+/// assert_eq!(pjson::error_context(json, 12, 4), ", 2,, 3]}\n    ^");
+/// ```
+pub fn error_context(json: &[u8], offset: usize, radius: usize) -> String {
+    let offset = offset.min(json.len());
+    let line_start = json[..offset]
+        .iter()
+        .rposition(|&ch| ch == b'\n')
+        .map_or(0, |i| i + 1);
+    let line_end = json[offset..]
+        .iter()
+        .position(|&ch| ch == b'\n' || ch == b'\r')
+        .map_or(json.len(), |i| offset + i);
+    let mut start = line_start.max(offset.saturating_sub(radius));
+    while start > line_start && start < json.len() && json[start] & 0xC0 == 0x80 {
+        start -= 1;
+    }
+    let mut end = line_end.min(offset.saturating_add(radius).saturating_add(1));
+    while end < line_end && json[end] & 0xC0 == 0x80 {
+        end += 1;
+    }
+    let mut snippet = String::new();
+    let mut caret = None;
+    let mut col = 0;
+    let mut i = start;
+    while i < end {
+        if i >= offset && caret.is_none() {
+            caret = Some(col);
+        }
+        let n = match json[i] {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        match std::str::from_utf8(&json[i..(i + n).min(end)]) {
+            Ok(s) if !s.chars().any(char::is_control) => {
+                snippet.push_str(s);
+                col += 1;
+                i += n;
+            }
+            _ => {
+                let esc = format!("\\x{:02X}", json[i]);
+                col += esc.len();
+                snippet.push_str(&esc);
+                i += 1;
+            }
+        }
+    }
+    let caret = caret.unwrap_or(col);
+    format!("{}\n{}^", snippet, " ".repeat(caret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col() {
+        let json = "{\n  \"a\": 1,\n  \"\u{e9}\": x\n}".as_bytes();
+        assert_eq!(offset_to_line_col(json, 0), (1, 1));
+        assert_eq!(offset_to_line_col(json, 1), (1, 2));
+        assert_eq!(offset_to_line_col(json, 2), (2, 1));
+        assert_eq!(offset_to_line_col(json, 4), (2, 3));
+        assert_eq!(offset_to_line_col(json, 20), (3, 8));
+        assert_eq!(offset_to_line_col(json, 22), (4, 1));
+        assert_eq!(offset_to_line_col(json, 1000), (4, 2));
+    }
+
+    #[test]
+    fn line_index() {
+        let json = "{\n  \"a\": 1,\n  \"\u{e9}\": x\n}\n".as_bytes();
+        let lines = LineIndex::new(json);
+        for offset in 0..json.len() + 2 {
+            assert_eq!(lines.line_col(offset), offset_to_line_col(json, offset));
+        }
+        assert_eq!(lines.line_col(20), (3, 8));
+        assert_eq!(lines.lines(), 5);
+        assert_eq!(LineIndex::new(b"").line_col(3), (1, 1));
+        assert_eq!(LineIndex::new(b"").lines(), 1);
+    }
+
+    #[test]
+    fn context() {
+        let json = br#"{"a": [1, 2,, 3]}"#;
+        assert_eq!(
+            error_context(json, 12, 100),
+            "{\"a\": [1, 2,, 3]}\n            ^"
+        );
+        assert_eq!(error_context(json, 0, 3), "{\"a\"\n^");
+        assert_eq!(error_context(json, 17, 3), "3]}\n   ^");
+        assert_eq!(error_context(json, 99, 0), "\n^");
+        let json = "[\n  \"\u{e9}t\u{e9}\" x,\n]".as_bytes();
+        assert_eq!(
+            error_context(json, 12, 100),
+            "  \"\u{e9}t\u{e9}\" x,\n        ^"
+        );
+        assert_eq!(error_context(json, 12, 4), "\u{e9}\" x,\n   ^");
+        let json = b"[\"a\tb\x00\xff\" ]";
+        assert_eq!(
+            error_context(json, 8, 100),
+            "[\"a\\x09b\\x00\\xFF\" ]\n                 ^"
+        );
+    }
+
+    #[test]
+    fn from_ret() {
+        let json = b"[1,2";
+        assert_eq!(
+            ParseError::from_ret(json, -4),
+            ParseError::new(ErrorKind::UnexpectedEof, 4)
+        );
+        assert_eq!(
+            ParseError::from_ret(json, -1),
+            ParseError::new(ErrorKind::Syntax, 1)
+        );
+        assert_eq!(
+            ParseError::from_ret(json, -1).to_string(),
+            "invalid json at offset 1"
+        );
+    }
+
+    #[test]
+    fn escape_eof() {
+        let status = |json: &[u8], opts| match crate::parse_status(json, opts, |_, _, _| -1) {
+            ParseStatus::Error(err) => (err.kind, err.offset),
+            status => panic!("{:?}", status),
+        };
+        for opts in [0, crate::UNCHECKED] {
+            let json = br#"["abc\u12AB"#;
+            assert_eq!(status(&json[..6], opts), (ErrorKind::UnterminatedEscape, 5));
+            for n in 7..10 {
+                let kind = ErrorKind::InvalidUnicodeEscape;
+                assert_eq!(status(&json[..n], opts), (kind, 5));
+            }
+            assert_eq!(status(&json[..11], opts), (ErrorKind::UnexpectedEof, 11));
+            assert_eq!(status(br#"["\\"#, opts), (ErrorKind::UnexpectedEof, 4));
+            assert_eq!(
+                status(br#"["\\\"#, opts),
+                (ErrorKind::UnterminatedEscape, 4)
+            );
+            assert_eq!(status(br#"["\\u1"#, opts), (ErrorKind::UnexpectedEof, 6));
+            assert_eq!(status(br#"["\n"#, opts), (ErrorKind::UnexpectedEof, 4));
+        }
+        assert_eq!(status(br#""\"#, 0), (ErrorKind::UnterminatedEscape, 1));
+        let kind = ErrorKind::InvalidUnicodeEscape;
+        assert_eq!(status(br#"{"a\u0"#, 0), (kind, 3));
+        assert_eq!(
+            ParseError::new(kind, 3).to_string(),
+            "invalid unicode escape at offset 3"
+        );
+    }
+}
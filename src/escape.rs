@@ -0,0 +1,473 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use std::borrow::Cow;
+
+/// Unescape a JSON String. The 'start' and 'end' params are the range of a
+/// String element, as passed to the iter function of parse, including the
+/// surrounding quotes.
+///
+/// A borrowed str is returned when the string has no escape characters. Any
+/// invalid UTF-8 or lone surrogate is replaced with U+FFFD.
+pub fn unescape(json: &[u8], start: usize, end: usize) -> Cow<'_, str> {
+    let mut s = &json[start..end];
+    if s.len() >= 2 && s[0] == b'"' && s[s.len() - 1] == b'"' {
+        s = &s[1..s.len() - 1];
+    }
+    if !s.contains(&b'\\') {
+        return String::from_utf8_lossy(s);
+    }
+    let mut out = Vec::with_capacity(s.len());
+    unescape_to(s, &mut out);
+    Cow::Owned(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// Get the length in bytes of an unescaped JSON String, which is the length of
+/// the result of unescape, without building the string. The 'start' and 'end'
+/// params are the range of the whole String element, including the quotes.
+///
+/// A surrogate pair is decoded as one character, also when the ESCAPES option
+/// reports its two halves as separate elements, so the range must always be
+/// that of the whole String and not that of a single escape.
+pub fn unescaped_len(json: &[u8], start: usize, end: usize) -> usize {
+    let mut s = &json[start..end];
+    if s.len() >= 2 && s[0] == b'"' && s[s.len() - 1] == b'"' {
+        s = &s[1..s.len() - 1];
+    }
+    if std::str::from_utf8(s).is_err() {
+        // the invalid bytes are replaced, which changes the length
+        return unescape(json, start, end).len();
+    }
+    let mut count = Count(0);
+    unescape_to(s, &mut count);
+    count.0
+}
+
+// The output of unescape_to.
+trait Sink {
+    fn push(&mut self, ch: u8);
+    fn push_char(&mut self, cp: u32);
+}
+
+impl Sink for Vec<u8> {
+    fn push(&mut self, ch: u8) {
+        Vec::push(self, ch);
+    }
+
+    fn push_char(&mut self, cp: u32) {
+        let ch = char::from_u32(cp).unwrap_or('\u{FFFD}');
+        let mut buf = [0; 4];
+        self.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+    }
+}
+
+// A Sink that only counts the bytes.
+struct Count(usize);
+
+impl Sink for Count {
+    fn push(&mut self, _: u8) {
+        self.0 += 1;
+    }
+
+    fn push_char(&mut self, cp: u32) {
+        self.0 += char::from_u32(cp).unwrap_or('\u{FFFD}').len_utf8();
+    }
+}
+
+// Decode the escapes of the inner bytes of a String into 'out'.
+fn unescape_to<S: Sink>(s: &[u8], out: &mut S) {
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] != b'\\' {
+            out.push(s[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        if i == s.len() {
+            break;
+        }
+        match s[i] {
+            b'"' => out.push(b'"'),
+            b'\\' => out.push(b'\\'),
+            b'/' => out.push(b'/'),
+            b'b' => out.push(8),
+            b'f' => out.push(12),
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'u' => {
+                let (cp, n) = decode_u(&s[i - 1..]);
+                out.push_char(cp);
+                i += n - 2;
+            }
+            b'\n' => {
+                // JSON5 line continuation
+            }
+            b'\r' => {
+                if i + 1 < s.len() && s[i + 1] == b'\n' {
+                    i += 1;
+                }
+            }
+            0xE2 if s.len() > i + 2
+                && s[i + 1] == 0x80
+                && (s[i + 2] == 0xA8 || s[i + 2] == 0xA9) =>
+            {
+                i += 2;
+            }
+            b'x' => {
+                // JSON5 '\xFF' escape
+                match hex2(s, i + 1) {
+                    Some(cp) => {
+                        out.push_char(cp);
+                        i += 2;
+                    }
+                    None => out.push_char(0xFFFD),
+                }
+            }
+            ch => out.push(ch),
+        }
+        i += 1;
+    }
+}
+
+/// Unescape a JSON String whose bytes are Latin-1 (ISO 8859-1) rather than
+/// UTF-8, such as from a legacy exporter. This is not standard JSON, which is
+/// always UTF-8, so use it only for sources that are known to be Latin-1. The
+/// 'start' and 'end' params are the range of a String element, as passed to the
+/// iter function of parse, including the surrounding quotes.
+///
+/// Every byte is taken as the character with the same code point, and then
+/// the escapes are decoded like unescape, so a "\u" escape is still Unicode.
+///
+/// ```
+/// let json = b"[\"caf\xe9 \\u20ac\"]";
+/// assert_eq!(pjson::decode_latin1(json, 1, json.len() - 1), "caf\u{e9} \u{20ac}");
+/// ```
+pub fn decode_latin1(json: &[u8], start: usize, end: usize) -> String {
+    let s: String = json[start..end].iter().map(|&ch| ch as char).collect();
+    unescape(s.as_bytes(), 0, s.len()).into_owned()
+}
+
+/// Get the text of a Key or String element without its quotes, such that the
+/// text is json[start + 1..end - 1]. The 'start', 'end', and 'info' params are
+/// those of the element, as passed to the iter function of parse.
+///
+/// Returns None when the info has the ESCAPED bit, because the text is then
+/// not the decoded string, in which case unescape should be used instead. Also
+/// returns None if the range is not a String or is not valid UTF-8.
+pub fn key_inner(json: &[u8], start: usize, end: usize, info: usize) -> Option<&str> {
+    if info & crate::ESCAPED == crate::ESCAPED {
+        return None;
+    }
+    let s = &json[start..end];
+    if s.len() < 2 || s[0] != b'"' || s[s.len() - 1] != b'"' {
+        return None;
+    }
+    std::str::from_utf8(&s[1..s.len() - 1]).ok()
+}
+
+/// Check that a String element is valid UTF-8. The 'start', 'end', and 'info'
+/// params are those of the element, as passed to the iter function of parse.
+///
+/// The parser does not validate UTF-8, so this is for checking only the
+/// Strings where it matters. When the info has the ESCAPED bit, every '\u'
+/// escape must also be a valid code point, so a surrogate escape must be a
+/// high surrogate that is directly followed by a low surrogate escape. Returns
+/// false if the range is not a String.
+pub fn is_valid_utf8_string(json: &[u8], start: usize, end: usize, info: usize) -> bool {
+    let s = &json[start..end];
+    if s.len() < 2 || s[0] != b'"' || s[s.len() - 1] != b'"' {
+        return false;
+    }
+    let s = &s[1..s.len() - 1];
+    if std::str::from_utf8(s).is_err() {
+        return false;
+    }
+    if info & crate::ESCAPED != crate::ESCAPED {
+        return true;
+    }
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] != b'\\' {
+            i += 1;
+            continue;
+        }
+        if i + 1 == s.len() || s[i + 1] != b'u' {
+            i += 2;
+            continue;
+        }
+        let r1 = match hex4(s, i + 2) {
+            Some(r1) => r1,
+            None => return false,
+        };
+        i += 6;
+        if (0xDC00..0xE000).contains(&r1) {
+            return false;
+        }
+        if (0xD800..0xDC00).contains(&r1) {
+            if i + 6 > s.len() || s[i] != b'\\' || s[i + 1] != b'u' {
+                return false;
+            }
+            match hex4(s, i + 2) {
+                Some(r2) if (0xDC00..0xE000).contains(&r2) => i += 6,
+                _ => return false,
+            }
+        }
+    }
+    true
+}
+
+/// Escape a string into a JSON String, with the surrounding quotes, such that
+/// unescape on the result gives back the original string. This is the inverse
+/// of unescape.
+///
+/// Quotes, backslashes, and control characters are escaped, using the short
+/// escapes such as '\n' where available, and lowercase '\u00XX' escapes
+/// otherwise. With the ASCII_ONLY option, all non-ASCII characters are also
+/// escaped as '\uXXXX', using a surrogate pair for characters beyond U+FFFF.
+/// With the REQUIRE_ESCAPED_SOLIDUS option, every '/' is escaped as '\/'.
+///
+/// ```
+/// assert_eq!(pjson::escape("a\"b\n", 0), r#""a\"b\n""#);
+/// assert_eq!(pjson::escape("caf\u{e9}", pjson::ASCII_ONLY), r#""caf\u00e9""#);
+/// ```
+pub fn escape(s: &str, opts: usize) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    escape_into(s, opts, &mut out);
+    out
+}
+
+/// Escape a string into a JSON String, appending it to 'out'. This works just
+/// like escape, except that the memory of 'out' can be reused.
+pub fn escape_into(s: &str, opts: usize, out: &mut String) {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let ascii = opts & crate::ASCII_ONLY == crate::ASCII_ONLY;
+    let solidus = opts & crate::REQUIRE_ESCAPED_SOLIDUS == crate::REQUIRE_ESCAPED_SOLIDUS;
+    out.push('"');
+    // the start of the text that has not been written yet
+    let mut mark = 0;
+    for (i, ch) in s.char_indices() {
+        let short = match ch {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\u{8}' => "\\b",
+            '\u{c}' => "\\f",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            '/' if solidus => "\\/",
+            '\u{0}'..='\u{1f}' => "",
+            _ if ascii && !ch.is_ascii() => "",
+            _ => continue,
+        };
+        out.push_str(&s[mark..i]);
+        mark = i + ch.len_utf8();
+        if !short.is_empty() {
+            out.push_str(short);
+            continue;
+        }
+        for unit in ch.encode_utf16(&mut [0; 2]) {
+            out.push_str("\\u");
+            for shift in [12, 8, 4, 0] {
+                out.push(HEX[(*unit >> shift) as usize & 15] as char);
+            }
+        }
+    }
+    out.push_str(&s[mark..]);
+    out.push('"');
+}
+
+// Decode a '\uXXXX' escape at the start of 's', combining a following
+// low surrogate escape when present. Returns the code point and the number of
+// bytes consumed. Invalid escapes or lone surrogates return U+FFFD.
+fn decode_u(s: &[u8]) -> (u32, usize) {
+    let r1 = match hex4(s, 2) {
+        Some(r1) => r1,
+        None => return (0xFFFD, 2),
+    };
+    if !(0xD800..0xE000).contains(&r1) {
+        return (r1, 6);
+    }
+    if r1 < 0xDC00 && s.len() >= 12 && s[6] == b'\\' && s[7] == b'u' {
+        if let Some(r2) = hex4(s, 8) {
+            if (0xDC00..0xE000).contains(&r2) {
+                return ((((r1 - 0xD800) << 10) | (r2 - 0xDC00)) + 0x10000, 12);
+            }
+        }
+    }
+    (0xFFFD, 6)
+}
+
+fn hex4(s: &[u8], i: usize) -> Option<u32> {
+    hexn(s, i, 4)
+}
+
+fn hex2(s: &[u8], i: usize) -> Option<u32> {
+    hexn(s, i, 2)
+}
+
+fn hexn(s: &[u8], i: usize, n: usize) -> Option<u32> {
+    if s.len() < i + n {
+        return None;
+    }
+    let mut r = 0;
+    for &ch in &s[i..i + n] {
+        r = (r << 4) | (ch as char).to_digit(16)?;
+    }
+    Some(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unesc(json: &[u8]) -> Cow<'_, str> {
+        unescape(json, 0, json.len())
+    }
+
+    #[test]
+    fn basic() {
+        assert!(matches!(unesc(br#""hello""#), Cow::Borrowed("hello")));
+        assert_eq!(unesc(br#""""#), "");
+        assert_eq!(unesc(br#""a\"b\\c\/d""#), "a\"b\\c/d");
+        assert_eq!(unesc(br#""\b\f\n\r\t""#), "\u{8}\u{c}\n\r\t");
+        assert_eq!(unesc(br#""\u0041\u00e9\u2028""#), "A\u{e9}\u{2028}");
+        assert_eq!(unesc(br#""\ud83d\ude00!""#), "\u{1F600}!");
+        assert_eq!(unesc(br#""\ud83d!""#), "\u{FFFD}!");
+        assert_eq!(unesc(br#""\ude00\ud83d""#), "\u{FFFD}\u{FFFD}");
+        assert_eq!(unesc("\"h\u{e9}llo\\n\"".as_bytes()), "h\u{e9}llo\n");
+        assert_eq!(unesc(br#""\x41\xe9\xFF""#), "A\u{e9}\u{ff}");
+        assert_eq!(unesc(b"\"a\\\nb\\\r\nc\\\rd\""), "abcd");
+        assert_eq!(unesc("\"a\\\u{2028}b\\\u{2029}c\"".as_bytes()), "abc");
+        let json = br#"{"key":"va\tlue"}"#;
+        assert_eq!(unescape(json, 7, 16), "va\tlue");
+    }
+
+    #[test]
+    fn latin1() {
+        let json = b"\"\xc0 b\xff\\t\\u00e9\\ud83d\\ude00\"";
+        assert_eq!(
+            decode_latin1(json, 0, json.len()),
+            "\u{c0} b\u{ff}\t\u{e9}\u{1f600}"
+        );
+        // bytes that would be valid UTF-8 are still taken one at a time
+        assert_eq!(decode_latin1(b"\"\xc3\xa9\"", 0, 4), "\u{c3}\u{a9}");
+        assert_eq!(decode_latin1(b"\"\"", 0, 2), "");
+    }
+
+    #[test]
+    fn lengths() {
+        for json in [
+            &br#""plain""#[..],
+            br#""a\"b\\c\n\u00e9\u20ac\ud83d\ude00!""#,
+            br#""\ud83d!\ude00\uZZZZ""#,
+            b"\"\xff\\n\"",
+            b"\"\\x41\\\n\"",
+        ] {
+            assert_eq!(unescaped_len(json, 0, json.len()), unesc(json).len());
+        }
+    }
+
+    #[test]
+    fn split_surrogates() {
+        // ESCAPES reports the halves of a surrogate pair as two elements, but
+        // the pair is still decoded as one character from the whole String
+        let json = br#"["x\ud83d\ude00y"]"#;
+        let mut escapes = Vec::new();
+        let mut string = (0, 0);
+        crate::parse(json, crate::ESCAPES, |start, end, info| {
+            if info & crate::ESCAPE == crate::ESCAPE {
+                escapes.push(&json[start..end]);
+            } else if info & crate::STRING == crate::STRING {
+                string = (start, end);
+            }
+            1
+        });
+        assert_eq!(escapes, [&br"\ud83d"[..], br"\ude00"]);
+        assert_eq!(unescape(json, string.0, string.1), "x\u{1F600}y");
+        assert_eq!(unescaped_len(json, string.0, string.1), 6);
+        // a half on its own is a lone surrogate
+        assert_eq!(unescape(json, 3, 9), "\u{FFFD}");
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("", 0), r#""""#);
+        assert_eq!(escape("hello", 0), r#""hello""#);
+        assert_eq!(
+            escape("a\"b\\c/d\u{8}\u{c}\n\r\t\u{0}\u{1f}", 0),
+            r#""a\"b\\c/d\b\f\n\r\t\u0000\u001f""#
+        );
+        assert_eq!(
+            escape("</a>/", crate::REQUIRE_ESCAPED_SOLIDUS),
+            r#""<\/a>\/""#
+        );
+        assert_eq!(escape("\u{7f}", 0), "\"\u{7f}\"");
+        assert_eq!(escape("caf\u{e9} \u{1F600}", 0), "\"caf\u{e9} \u{1F600}\"");
+        assert_eq!(
+            escape("caf\u{e9} \u{2028}\u{1F600}\n", crate::ASCII_ONLY),
+            "\"caf\\u00e9 \\u2028\\ud83d\\ude00\\n\""
+        );
+        let mut out = String::from("x=");
+        escape_into("\"", 0, &mut out);
+        escape_into("y", 0, &mut out);
+        assert_eq!(out, r#"x="\"""y""#);
+        for s in [
+            "",
+            "plain",
+            "quote \" and \\ backslash",
+            "controls \u{0}\u{1}\u{8}\u{b}\u{c}\u{1f} \n\r\t",
+            "caf\u{e9} \u{2028} \u{FFFF} \u{10000} \u{1F600}",
+        ] {
+            for opts in [0, crate::ASCII_ONLY] {
+                let json = escape(s, opts);
+                assert!(crate::parse(json.as_bytes(), opts, |_, _, _| 1) > 0);
+                assert_eq!(unesc(json.as_bytes()), s);
+            }
+        }
+    }
+
+    #[test]
+    fn inner() {
+        let json = r#"{"name":1,"a\"b":2,"":3,"café":4}"#.as_bytes();
+        let mut keys = Vec::new();
+        crate::parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+            if info & crate::KEY == crate::KEY {
+                keys.push(match key_inner(json, start, end, info) {
+                    Some(key) => key.to_string(),
+                    None => format!("escaped {}", unescape(json, start, end)),
+                });
+            }
+            1
+        });
+        assert_eq!(keys, ["name", "escaped a\"b", "", "caf\u{e9}"]);
+        assert_eq!(key_inner(b"123", 0, 3, crate::NUMBER), None);
+        assert_eq!(key_inner(b"\"\xff\"", 0, 3, crate::STRING), None);
+    }
+
+    #[test]
+    fn valid_utf8() {
+        let valid = |json: &[u8]| -> bool {
+            let mut res = None;
+            crate::parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+                res = Some(is_valid_utf8_string(json, start, end, info));
+                1
+            });
+            res.unwrap()
+        };
+        assert!(valid("\"caf\u{e9} \u{1F600}\"".as_bytes()));
+        assert!(valid(br#""a\"b\\c\n""#));
+        assert!(valid(br#""\u00e9\ud83d\ude00\uFFFD""#));
+        assert!(!valid(b"\"caf\xe9\""));
+        assert!(!valid(b"\"\xed\xa0\x80\""));
+        assert!(!valid(br#""\ud83d""#));
+        assert!(!valid(br#""\ud83d!""#));
+        assert!(!valid(br#""\ud83d\u0041""#));
+        assert!(!valid(br#""\ude00\ud83d""#));
+        assert!(!is_valid_utf8_string(b"123", 0, 3, crate::NUMBER));
+        // without the ESCAPED bit the escapes are not checked
+        assert!(is_valid_utf8_string(br#""\ud83d""#, 0, 8, crate::STRING));
+    }
+}
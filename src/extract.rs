@@ -0,0 +1,313 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::collections::HashMap;
+
+/// The expected type of a value that is requested from extract.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Type {
+    /// any type of value
+    Any,
+    /// a JSON String
+    Str,
+    /// a JSON Number
+    Num,
+    /// a JSON True or False
+    Bool,
+    /// a JSON Null
+    Null,
+    /// a JSON Object
+    Object,
+    /// a JSON Array
+    Array,
+}
+
+impl Type {
+    fn matches(self, info: usize) -> bool {
+        match self {
+            Type::Any => true,
+            Type::Str => info & STRING == STRING,
+            Type::Num => info & NUMBER == NUMBER,
+            Type::Bool => info & (TRUE | FALSE) != 0,
+            Type::Null => info & NULL == NULL,
+            Type::Object => info & OBJECT == OBJECT,
+            Type::Array => info & ARRAY == ARRAY,
+        }
+    }
+}
+
+/// Extract the values of top-level object keys in a single pass.
+///
+/// Each field is a key name and the expected type of its value. The returned
+/// map contains the byte range of every requested value that was found, such
+/// that json[start..end] is the complete value. Keys are compared by their
+/// decoded value. The children of Object and Array values are not reported to
+/// any callback and are skipped over quickly.
+///
+/// Keys that are missing, or whose value is not of the requested type, do not
+/// appear in the map. When a key appears more than once the last one wins.
+/// An empty map is returned if the json is invalid or is not an Object.
+pub fn extract<'a>(json: &[u8], fields: &[(&'a str, Type)]) -> HashMap<&'a str, (usize, usize)> {
+    let mut map = HashMap::new();
+    let mut field = None;
+    let mut mark = 0;
+    let ret = parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+        if info & START == START {
+            return if info & OBJECT == OBJECT { 1 } else { 0 };
+        }
+        if info & KEY == KEY {
+            let key = unescape(json, start, end);
+            field = fields.iter().find(|(name, _)| *name == key);
+        } else if info & VALUE == VALUE {
+            if let Some((name, kind)) = field {
+                if info & CLOSE == CLOSE {
+                    map.insert(*name, (mark, end));
+                } else if !kind.matches(info) {
+                    field = None;
+                } else if info & OPEN == OPEN {
+                    mark = start;
+                } else {
+                    map.insert(*name, (start, end));
+                }
+            }
+            if info & OPEN == OPEN {
+                return -1;
+            }
+        }
+        1
+    });
+    if ret <= 0 {
+        map.clear();
+    }
+    map
+}
+
+/// Map every top-level key of the root Object to the byte range of its value,
+/// such that json[start..end] is the complete value. Keys are decoded. The
+/// values are kept raw for lazy parsing later on, and the children of Object
+/// and Array values are skipped over quickly.
+///
+/// When a key appears more than once the last one wins. It is an error if the
+/// json is invalid, and a Syntax error at the root if it is not an Object.
+pub fn root_map(json: &[u8]) -> Result<HashMap<String, (usize, usize)>, ParseError> {
+    let mut map = HashMap::new();
+    let mut key = None;
+    let mut mark = 0;
+    let mut root = None;
+    let ret = parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+        if info & START == START {
+            if info & OBJECT == OBJECT {
+                return 1;
+            }
+            root = Some(start);
+            return 0;
+        }
+        if info & KEY == KEY {
+            key = Some(unescape(json, start, end).into_owned());
+        } else if info & VALUE == VALUE {
+            if info & OPEN == OPEN {
+                mark = start;
+                return -1;
+            }
+            let start = if info & CLOSE == CLOSE { mark } else { start };
+            map.insert(key.take().unwrap(), (start, end));
+        }
+        1
+    });
+    if ret <= 0 {
+        return Err(ParseError::from_ret(json, ret));
+    }
+    if let Some(offset) = root {
+        return Err(ParseError::new(ErrorKind::Syntax, offset));
+    }
+    Ok(map)
+}
+
+/// Find the Object keys, at any depth, whose decoded name starts with prefix.
+/// Returns the byte ranges of the keys, including their quotes, in the order
+/// that they appear in the json. An empty list is returned if the json is
+/// invalid.
+pub fn keys_with_prefix(json: &[u8], prefix: &str) -> Vec<(usize, usize)> {
+    keys_with_prefix_depth(json, prefix, usize::MAX)
+}
+
+/// Same as keys_with_prefix but only keys of Objects that are nested no
+/// deeper than max_depth are included, where the keys of the root Object are
+/// at depth 1. Containers below max_depth are skipped over quickly.
+pub fn keys_with_prefix_depth(json: &[u8], prefix: &str, max_depth: usize) -> Vec<(usize, usize)> {
+    let mut keys = Vec::new();
+    let mut depth = 0;
+    let ret = parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+        if info & OPEN == OPEN {
+            depth += 1;
+            if depth > max_depth {
+                return -1;
+            }
+        } else if info & CLOSE == CLOSE {
+            depth -= 1;
+        } else if info & KEY == KEY {
+            let matched = if info & ESCAPED == ESCAPED {
+                unescape(json, start, end).starts_with(prefix)
+            } else {
+                json[start + 1..end - 1].starts_with(prefix.as_bytes())
+            };
+            if matched {
+                keys.push((start, end));
+            }
+        }
+        1
+    });
+    if ret <= 0 {
+        keys.clear();
+    }
+    keys
+}
+
+/// Find every String value, at any depth, excluding Object keys. A String at
+/// the root of the json is also a value. Returns the byte ranges of the
+/// values, including their quotes, in the order that they appear in the json.
+/// Escaped Strings are returned as-is, so use unescape to decode them. An empty
+/// list is returned if the json is invalid.
+pub fn string_values(json: &[u8]) -> Vec<(usize, usize)> {
+    let mut values = Vec::new();
+    let ret = parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+        // a root String is not a VALUE, so rule out the keys instead
+        if info & (STRING | KEY) == STRING {
+            values.push((start, end));
+        }
+        1
+    });
+    if ret <= 0 {
+        values.clear();
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &[u8] = br#"
+    {
+      "name": {"first": "Tom", "last": "Anderson"},
+      "age":37,
+      "children": ["Sara","Alex","Jack"],
+      "fav.movie": "Deer Hunter",
+      "friends": [{"age": 44}, {"age": 68}]
+    }"#;
+
+    fn get<'a>(json: &'a [u8], map: &HashMap<&str, (usize, usize)>, key: &str) -> &'a str {
+        let (start, end) = map[key];
+        std::str::from_utf8(&json[start..end]).unwrap()
+    }
+
+    #[test]
+    fn fields() {
+        let map = extract(
+            JSON,
+            &[
+                ("name", Type::Object),
+                ("age", Type::Num),
+                ("children", Type::Any),
+                ("fav.movie", Type::Str),
+                ("friends", Type::Object),
+                ("missing", Type::Any),
+            ],
+        );
+        assert_eq!(map.len(), 4);
+        assert_eq!(
+            get(JSON, &map, "name"),
+            r#"{"first": "Tom", "last": "Anderson"}"#
+        );
+        assert_eq!(get(JSON, &map, "age"), "37");
+        assert_eq!(get(JSON, &map, "children"), r#"["Sara","Alex","Jack"]"#);
+        assert_eq!(get(JSON, &map, "fav.movie"), r#""Deer Hunter""#);
+        assert!(extract(br#"[{"a":1}]"#, &[("a", Type::Any)]).is_empty());
+        assert!(extract(br#"{"a":1,}"#, &[("a", Type::Any)]).is_empty());
+        let json = br#"{"a":1,"a":[2]}"#;
+        assert_eq!(get(json, &extract(json, &[("a", Type::Any)]), "a"), "[2]");
+    }
+
+    #[test]
+    fn prefix() {
+        let json = br#"{"db.host":"a","db.port":1,"dbx":2,"db\u002eesc":5,
+            "nested":{"db.user":{"db.pass":3}},"list":[{"db.name":4}]}"#;
+        let keys = |max_depth: usize| -> Vec<String> {
+            keys_with_prefix_depth(json, "db.", max_depth)
+                .into_iter()
+                .map(|(start, end)| unescape(json, start, end).into_owned())
+                .collect()
+        };
+        let all = keys_with_prefix(json, "db.");
+        assert_eq!(all.len(), 6);
+        assert_eq!(&json[all[0].0..all[0].1], br#""db.host""#);
+        assert_eq!(
+            keys(usize::MAX),
+            ["db.host", "db.port", "db.esc", "db.user", "db.pass", "db.name"]
+        );
+        assert_eq!(keys(1), ["db.host", "db.port", "db.esc"]);
+        assert_eq!(keys(2), ["db.host", "db.port", "db.esc", "db.user"]);
+        assert_eq!(
+            keys(3),
+            ["db.host", "db.port", "db.esc", "db.user", "db.pass", "db.name"]
+        );
+        assert_eq!(keys_with_prefix(json, "").len(), 9);
+        assert!(keys_with_prefix(br#"{"db.a":1,}"#, "db.").is_empty());
+    }
+
+    #[test]
+    fn strings() {
+        let values: Vec<&[u8]> = string_values(JSON)
+            .into_iter()
+            .map(|(start, end)| &JSON[start..end])
+            .collect();
+        assert_eq!(
+            values,
+            [
+                &br#""Tom""#[..],
+                br#""Anderson""#,
+                br#""Sara""#,
+                br#""Alex""#,
+                br#""Jack""#,
+                br#""Deer Hunter""#
+            ]
+        );
+        let json = br#"["a\"b", {"k": "v!"}, "root"]"#;
+        assert_eq!(string_values(json), [(1, 7), (15, 19), (22, 28)]);
+        assert_eq!(string_values(br#""lone""#), [(0, 6)]);
+        assert!(string_values(br#"{"k":1}"#).is_empty());
+        assert!(string_values(br#"["a","b""#).is_empty());
+    }
+
+    #[test]
+    fn root() {
+        let map = root_map(JSON).unwrap();
+        assert_eq!(map.len(), 5);
+        let (start, end) = map["children"];
+        assert_eq!(&JSON[start..end], br#"["Sara","Alex","Jack"]"#);
+        let (start, end) = map["age"];
+        assert_eq!(&JSON[start..end], b"37");
+        let json = br#"{"a":1,"b":{"c":2},"a":[3],"d\u0065":4}"#;
+        let map = root_map(json).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map["a"], (23, 26));
+        assert_eq!(map["b"], (11, 18));
+        assert!(map.contains_key("de"));
+        assert_eq!(root_map(b"{}").unwrap().len(), 0);
+        assert_eq!(
+            root_map(b" [1]"),
+            Err(ParseError::new(ErrorKind::Syntax, 1))
+        );
+        assert_eq!(root_map(b"1"), Err(ParseError::new(ErrorKind::Syntax, 0)));
+        assert_eq!(
+            root_map(br#"{"a":[1,}"#),
+            Err(ParseError::new(ErrorKind::Syntax, 8))
+        );
+        assert_eq!(
+            root_map(br#"{"a":1"#),
+            Err(ParseError::new(ErrorKind::UnexpectedEof, 6))
+        );
+    }
+}
@@ -0,0 +1,303 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::pointer::{walk, Child};
+use crate::*;
+
+enum Pat {
+    Char(char),
+    // '*' matches any number of characters
+    Star,
+    // '?' matches a single character
+    One,
+}
+
+struct Component {
+    pats: Vec<Pat>,
+    wild: bool,
+    // the unescaped text, when there are no wildcards
+    text: String,
+}
+
+impl Component {
+    fn matches(&self, child: Child) -> bool {
+        match child {
+            Child::Key(key) if self.wild => glob(&self.pats, key),
+            Child::Key(key) => key == self.text,
+            Child::Index(index) => {
+                !self.wild
+                    && !self.text.is_empty()
+                    && self.text.bytes().all(|ch| ch.is_ascii_digit())
+                    && self.text.parse() == Ok(index)
+            }
+        }
+    }
+}
+
+fn glob(pats: &[Pat], s: &str) -> bool {
+    let chars: Vec<char> = s.chars().collect();
+    let (mut p, mut c) = (0, 0);
+    // the positions to go back to when a '*' needs to match more
+    let mut star: Option<(usize, usize)> = None;
+    while c < chars.len() {
+        match pats.get(p) {
+            Some(Pat::Star) => {
+                star = Some((p, c));
+                p += 1;
+                continue;
+            }
+            Some(Pat::One) => {
+                p += 1;
+                c += 1;
+                continue;
+            }
+            Some(Pat::Char(ch)) if *ch == chars[c] => {
+                p += 1;
+                c += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match star {
+            Some((sp, sc)) => {
+                star = Some((sp, sc + 1));
+                p = sp + 1;
+                c = sc + 1;
+            }
+            None => return false,
+        }
+    }
+    pats[p..].iter().all(|pat| matches!(pat, Pat::Star))
+}
+
+// split a path into its components, returning None for unsupported syntax
+fn components(path: &str) -> Option<Vec<Component>> {
+    if path.is_empty() {
+        return None;
+    }
+    let mut comps = Vec::new();
+    let mut comp = Component {
+        pats: Vec::new(),
+        wild: false,
+        text: String::new(),
+    };
+    let mut chars = path.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                let ch = chars.next()?;
+                comp.pats.push(Pat::Char(ch));
+                comp.text.push(ch);
+            }
+            '.' => {
+                comps.push(comp);
+                comp = Component {
+                    pats: Vec::new(),
+                    wild: false,
+                    text: String::new(),
+                };
+            }
+            '*' | '?' => {
+                comp.pats.push(if ch == '*' { Pat::Star } else { Pat::One });
+                comp.wild = true;
+            }
+            '#' | '@' | '|' => return None,
+            _ => {
+                comp.pats.push(Pat::Char(ch));
+                comp.text.push(ch);
+            }
+        }
+    }
+    comps.push(comp);
+    Some(comps)
+}
+
+/// Get a value using a gjson path, such as "friends.1.last". Returns the byte
+/// range of the value, such that json[start..end] is the complete value, or
+/// None if there is no such value or the json is invalid.
+///
+/// The path is a series of components separated by '.'. A component is an
+/// Object key, or the index of an Array element. The '*' and '?' wildcards
+/// match any number of characters or a single character of a key, and the
+/// first matching key that leads to a value is used. A '\' escapes the
+/// character that follows it, such as "fav\.movie". Use get_count for the "#"
+/// count of an Array. The gjson queries, modifiers, multipaths, and pipes are
+/// not supported.
+///
+/// Parsing stops as soon as the value is found, and containers that are not
+/// on the path are skipped over quickly.
+pub fn get(json: &[u8], path: &str) -> Option<(usize, usize)> {
+    let (start, end, _) = get_info(json, path)?;
+    Some((start, end))
+}
+
+fn get_info(json: &[u8], path: &str) -> Option<(usize, usize, usize)> {
+    let comps = components(path)?;
+    let step = |depth: usize, child: Child| comps[depth].matches(child);
+    let exact = !comps.iter().any(|comp| comp.wild);
+    walk(json, comps.len(), step, false, exact).pop()
+}
+
+/// A path to a value, in one of the path syntaxes, as passed to lookup.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Path {
+    /// a JSON Pointer (RFC 6901), as for get_pointer, such as "/friends/0"
+    JsonPointer(String),
+    /// a gjson path, as for get, such as "friends.0"
+    GjsonPath(String),
+}
+
+/// A value that was found by lookup.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Match {
+    /// the start index of the value
+    pub start: usize,
+    /// the end index of the value, such that json[start..end] is the
+    /// complete value
+    pub end: usize,
+    /// the info bits of the value, as for get_pointer_info
+    pub info: usize,
+}
+
+/// Get a value using a path in any of the supported path syntaxes. This works
+/// just like get_pointer_info for a JsonPointer, and like get for a GjsonPath,
+/// and also returns the info bits of the value. Returns None if there is no
+/// such value, the path is malformed, or the json is invalid.
+///
+/// ```
+/// use pjson::{lookup, Path};
+/// let json = br#"{"a":[1,{"b":"c"}]}"#;
+/// let m = lookup(json, &Path::JsonPointer("/a/1/b".into())).unwrap();
+/// assert_eq!(&json[m.start..m.end], br#""c""#);
+/// assert_eq!(lookup(json, &Path::GjsonPath("a.1.b".into())), Some(m));
+/// ```
+pub fn lookup(json: &[u8], path: &Path) -> Option<Match> {
+    let (start, end, info) = match path {
+        Path::JsonPointer(pointer) => get_pointer_info(json, pointer)?,
+        Path::GjsonPath(path) => get_info(json, path)?,
+    };
+    Some(Match { start, end, info })
+}
+
+/// Get the number of elements of an Array using a gjson path that ends with
+/// "#", such as "friends.#", or just "#" for the root. Returns None if the
+/// value is not an Array or there is no such value.
+pub fn get_count(json: &[u8], path: &str) -> Option<usize> {
+    let (start, end) = if path == "#" {
+        let token = parse_value(json).ok()?.0;
+        (token.start, token.end)
+    } else if path.ends_with(".#") && !path[..path.len() - 2].ends_with('\\') {
+        get(json, &path[..path.len() - 2])?
+    } else {
+        return None;
+    };
+    let value = &json[start..end];
+    if value[0] != b'[' {
+        return None;
+    }
+    let mut count = 0;
+    parse(value, 0, |_: usize, _: usize, info: usize| -> i64 {
+        if info & START == START {
+            return 1;
+        }
+        if info & VALUE == VALUE && info & CLOSE == 0 {
+            count += 1;
+        }
+        -1
+    });
+    Some(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &[u8] = br#"{
+      "name": {"first": "Tom", "last": "Anderson"},
+      "age":37,
+      "children": ["Sara","Alex","Jack"],
+      "fav.movie": "Deer Hunter",
+      "friends": [
+        {"first": "Dale", "last": "Murphy", "age": 44, "nets": ["ig", "fb", "tw"]},
+        {"first": "Roger", "last": "Craig", "age": 68, "nets": ["fb", "tw"]},
+        {"first": "Jane", "last": "Murphy", "age": 47, "nets": ["ig", "tw"]}
+      ]
+    }"#;
+
+    fn g(path: &str) -> Option<&'static str> {
+        let (start, end) = get(JSON, path)?;
+        Some(std::str::from_utf8(&JSON[start..end]).unwrap())
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(g("name.last"), Some(r#""Anderson""#));
+        assert_eq!(g("age"), Some("37"));
+        assert_eq!(g("children"), Some(r#"["Sara","Alex","Jack"]"#));
+        assert_eq!(g("children.1"), Some(r#""Alex""#));
+        assert_eq!(g("children.3"), None);
+        assert_eq!(g("friends.1.last"), Some(r#""Craig""#));
+        assert_eq!(g("friends.2.nets.0"), Some(r#""ig""#));
+        assert_eq!(g(r"fav\.movie"), Some(r#""Deer Hunter""#));
+        assert_eq!(g("fav.movie"), None);
+        assert_eq!(g("nope"), None);
+        assert_eq!(g(""), None);
+        assert_eq!(g("friends.#.first"), None);
+        assert_eq!(g("@reverse"), None);
+        assert_eq!(get(br#"{"0":{"1":"x"}}"#, "0.1"), Some((10, 13)));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert_eq!(g("child*.2"), Some(r#""Jack""#));
+        assert_eq!(g("c?ildren.0"), Some(r#""Sara""#));
+        assert_eq!(g("*.first"), Some(r#""Tom""#));
+        assert_eq!(g("a*"), Some("37"));
+        assert_eq!(g("*e"), Some(r#"{"first": "Tom", "last": "Anderson"}"#));
+        assert_eq!(g("fav*ie"), Some(r#""Deer Hunter""#));
+        assert_eq!(g("fav?movie"), Some(r#""Deer Hunter""#));
+        assert_eq!(g("a?"), None);
+        assert_eq!(g("children.*"), None);
+        assert_eq!(get(br#"{"a*":1,"ab":2}"#, r"a\*"), Some((6, 7)));
+        assert_eq!(get(br#"{"ab":2,"a*":1}"#, r"a*"), Some((6, 7)));
+        let json = br#"{"a":{"x":1},"b":[{"first":2}],"c":{"first":3}}"#;
+        assert_eq!(get(json, "*.first"), Some((44, 45)));
+        assert_eq!(get(json, "?.0.first"), Some((27, 28)));
+    }
+
+    #[test]
+    fn counts() {
+        assert_eq!(get_count(JSON, "children.#"), Some(3));
+        assert_eq!(get_count(JSON, "friends.#"), Some(3));
+        assert_eq!(get_count(JSON, "friends.1.nets.#"), Some(2));
+        assert_eq!(get_count(JSON, "name.#"), None);
+        assert_eq!(get_count(JSON, "children"), None);
+        assert_eq!(get_count(b" [[1,2],{},3] ", "#"), Some(3));
+        assert_eq!(get_count(b"[]", "#"), Some(0));
+        assert_eq!(get_count(br#"{"a.#":[1]}"#, r"a\.#"), None);
+    }
+
+    #[test]
+    fn lookups() {
+        let pointer = |p: &str| lookup(JSON, &Path::JsonPointer(p.into()));
+        let gjson = |p: &str| lookup(JSON, &Path::GjsonPath(p.into()));
+        for (p, g) in [
+            ("/name/last", "name.last"),
+            ("/friends/1", "friends.1"),
+            ("/children", "children"),
+            ("/age", "age"),
+            ("/fav.movie", "fav\\.movie"),
+        ] {
+            let m = pointer(p).unwrap();
+            assert_eq!(gjson(g), Some(m));
+            assert_eq!(Some((m.start, m.end)), get(JSON, g));
+            assert_eq!(get_pointer_info(JSON, p), Some((m.start, m.end, m.info)));
+        }
+        assert_eq!(gjson("friends.1").unwrap().info & OBJECT, OBJECT);
+        assert_eq!(gjson("fr*.2.a?e").unwrap().info & NUMBER, NUMBER);
+        assert_eq!(pointer("/missing"), None);
+        assert_eq!(gjson("friends.#"), None);
+        assert_eq!(pointer("name"), None);
+    }
+}
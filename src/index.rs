@@ -0,0 +1,178 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::collections::HashMap;
+
+struct Member {
+    range: (usize, usize),
+    // the members of an Object value
+    children: HashMap<String, (usize, usize)>,
+}
+
+/// An index of the members of a root Object, for looking up the same json
+/// many times without parsing it again.
+///
+/// The index maps the unescaped keys of the root Object to the byte ranges of
+/// their values, and for the members whose value is an Object, the keys of
+/// that Object to the ranges of its values. Nothing deeper is indexed. The
+/// values themselves stay raw, so that json[start..end] can be handed to
+/// parse, get, or any of the other functions when it's needed.
+///
+/// When an Object has the same key more than once, the first member wins.
+///
+/// ```
+/// let json = br#"{"name":"Tom","friends":[1,2],"info":{"age":37}}"#;
+/// let index = pjson::Index::build(json).unwrap();
+/// let (start, end) = index.get("friends").unwrap();
+/// assert_eq!(&json[start..end], b"[1,2]");
+/// let (start, end) = index.get_child("info", "age").unwrap();
+/// assert_eq!(&json[start..end], b"37");
+/// ```
+pub struct Index {
+    members: HashMap<String, Member>,
+}
+
+impl Index {
+    /// Build the index for a json document. A root value that is not an
+    /// Object has an empty index. Containers below the second level are
+    /// skipped over without reporting their children.
+    pub fn build(json: &[u8]) -> Result<Index, ParseError> {
+        let mut members: HashMap<String, Member> = HashMap::new();
+        let mut children = HashMap::new();
+        let mut depth = 0;
+        let (mut key, mut child) = (String::new(), String::new());
+        // the start of the container value that is open at each level
+        let (mut start, mut child_start) = (0, 0);
+        let ret = parse(json, 0, |s: usize, e: usize, info: usize| -> i64 {
+            if info & OPEN == OPEN {
+                depth += 1;
+                match depth {
+                    1 if info & OBJECT == OBJECT => 1,
+                    2 if info & OBJECT == OBJECT && !members.contains_key(&key) => {
+                        start = s;
+                        1
+                    }
+                    2 => {
+                        start = s;
+                        -1
+                    }
+                    3 => {
+                        child_start = s;
+                        -1
+                    }
+                    _ => -1,
+                }
+            } else if info & CLOSE == CLOSE {
+                match depth {
+                    2 => {
+                        members.entry(key.clone()).or_insert(Member {
+                            range: (start, e),
+                            children: std::mem::take(&mut children),
+                        });
+                    }
+                    3 => {
+                        children.entry(child.clone()).or_insert((child_start, e));
+                    }
+                    _ => {}
+                }
+                depth -= 1;
+                1
+            } else if info & KEY == KEY {
+                match depth {
+                    1 => key = unescape(json, s, e).into_owned(),
+                    _ => child = unescape(json, s, e).into_owned(),
+                }
+                1
+            } else if info & (COMMA | COLON) != 0 {
+                1
+            } else {
+                match depth {
+                    1 => {
+                        members.entry(key.clone()).or_insert(Member {
+                            range: (s, e),
+                            children: HashMap::new(),
+                        });
+                    }
+                    2 => {
+                        children.entry(child.clone()).or_insert((s, e));
+                    }
+                    _ => {}
+                }
+                1
+            }
+        });
+        if ret <= 0 {
+            return Err(ParseError::from_ret(json, ret));
+        }
+        Ok(Index { members })
+    }
+
+    /// Get the (start, end) range of the value of a member of the root
+    /// Object.
+    pub fn get(&self, key: &str) -> Option<(usize, usize)> {
+        self.members.get(key).map(|member| member.range)
+    }
+
+    /// Get the (start, end) range of the value of a member of an Object that
+    /// is itself the value of the root Object member 'key'.
+    pub fn get_child(&self, key: &str, child: &str) -> Option<(usize, usize)> {
+        self.members.get(key)?.children.get(child).copied()
+    }
+
+    /// The number of members of the root Object, not counting duplicates.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns true when the root Object has no members.
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(json: &[u8], range: Option<(usize, usize)>) -> Option<&str> {
+        range.map(|(start, end)| std::str::from_utf8(&json[start..end]).unwrap())
+    }
+
+    #[test]
+    fn lookup() {
+        let json = br#" {"a":1, "b":{"c":[1,{"d":2}],"e":"x","c":3}, "fg":[{"h":4}],
+            "a":2, "b":{"i":5}, "j\u006b":6} "#;
+        let index = Index::build(json).unwrap();
+        assert_eq!(index.len(), 4);
+        assert_eq!(text(json, index.get("jk")), Some("6"));
+        assert_eq!(text(json, index.get("a")), Some("1"));
+        assert_eq!(
+            text(json, index.get("b")),
+            Some(r#"{"c":[1,{"d":2}],"e":"x","c":3}"#)
+        );
+        assert_eq!(text(json, index.get("fg")), Some(r#"[{"h":4}]"#));
+        assert_eq!(
+            text(json, index.get_child("b", "c")),
+            Some(r#"[1,{"d":2}]"#)
+        );
+        assert_eq!(text(json, index.get_child("b", "e")), Some(r#""x""#));
+        assert_eq!(index.get_child("b", "d"), None);
+        assert_eq!(index.get_child("b", "i"), None);
+        assert_eq!(index.get_child("fg", "h"), None);
+        assert_eq!(index.get_child("a", "a"), None);
+        assert_eq!(index.get("c"), None);
+    }
+
+    #[test]
+    fn roots() {
+        assert!(Index::build(b"{}").unwrap().is_empty());
+        assert!(Index::build(br#"[{"a":1}]"#).unwrap().is_empty());
+        assert!(Index::build(br#""a""#).unwrap().is_empty());
+        assert_eq!(
+            Index::build(br#"{"a":[1,}"#).err(),
+            Some(ParseError::new(ErrorKind::Syntax, 8))
+        );
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::collections::{BTreeSet, HashMap};
+
+/// A table of interned Object keys, as filled by parse_interned. Each distinct
+/// key is assigned a small id, starting at zero, in the order that the keys
+/// are first seen.
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    ids: HashMap<String, u32>,
+    names: Vec<String>,
+}
+
+impl Interner {
+    /// Create a new empty table.
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    /// Get the id of a key, or None if it has not been seen.
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    /// Get the unescaped key of an id, or None if there is no such id.
+    pub fn name(&self, id: u32) -> Option<&str> {
+        self.names.get(id as usize).map(String::as_str)
+    }
+
+    /// The number of distinct keys in the table.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns true when no keys have been seen.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len() as u32;
+        self.ids.insert(name.to_string(), id);
+        self.names.push(name.to_string());
+        id
+    }
+}
+
+/// Parse JSON, interning every Object key. This works just like parse, except
+/// that 'iter' is also passed the id of the key for each KEY element, and None
+/// for all other elements.
+///
+/// Keys are compared by their unescaped text, so "a" and "\u0061" have the same
+/// id. The same table can be used for many documents, such as the lines of a
+/// log, so that a key has the same id in all of them. Allocation only happens
+/// the first time that a key is seen.
+///
+/// The return value has the same meaning as for parse.
+pub fn parse_interned<F>(json: &[u8], opts: usize, interner: &mut Interner, iter: F) -> i64
+where
+    F: FnMut(Option<u32>, usize, usize, usize) -> i64,
+{
+    let mut iter = iter;
+    parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        if info & KEY != KEY {
+            return iter(None, start, end, info);
+        }
+        let id = match key_inner(json, start, end, info) {
+            Some(name) => interner.intern(name),
+            None => interner.intern(&unescape(json, start, end)),
+        };
+        iter(Some(id), start, end, info)
+    })
+}
+
+/// Collect the distinct Object keys from anywhere in a json document, such as
+/// for finding which fields appear in a dataset. The keys are unescaped, so
+/// "a" and "\u0061" are the same key.
+pub fn distinct_keys(json: &[u8]) -> Result<BTreeSet<String>, ParseError> {
+    let mut keys = BTreeSet::new();
+    let ret = parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+        if info & KEY == KEY {
+            match key_inner(json, start, end, info) {
+                Some(name) => {
+                    if !keys.contains(name) {
+                        keys.insert(name.to_string());
+                    }
+                }
+                None => {
+                    keys.insert(unescape(json, start, end).into_owned());
+                }
+            }
+        }
+        1
+    });
+    if ret <= 0 {
+        return Err(ParseError::from_ret(json, ret));
+    }
+    Ok(keys)
+}
+
+/// Count the Object members from anywhere in a json document that have the
+/// key 'name', such as for finding how many records have a certain field. The
+/// keys are unescaped before they are compared.
+pub fn count_key(json: &[u8], name: &str) -> Result<usize, ParseError> {
+    let mut count = 0;
+    let ret = parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+        if info & KEY == KEY {
+            let found = match key_inner(json, start, end, info) {
+                Some(key) => key == name,
+                None => unescape(json, start, end) == name,
+            };
+            if found {
+                count += 1;
+            }
+        }
+        1
+    });
+    if ret <= 0 {
+        return Err(ParseError::from_ret(json, ret));
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned() {
+        let mut interner = Interner::new();
+        let mut ids = Vec::new();
+        for json in [
+            &br#"{"a":1,"b":{"a":2,"c":"a"}}"#[..],
+            br#"{"c":[{"\u0061":3}],"d":4}"#,
+        ] {
+            let ret = parse_interned(json, 0, &mut interner, |id, _, _, info| {
+                assert_eq!(id.is_some(), info & KEY == KEY);
+                ids.extend(id);
+                1
+            });
+            assert_eq!(ret, json.len() as i64);
+        }
+        assert_eq!(ids, [0, 1, 0, 2, 2, 0, 3]);
+        assert_eq!(interner.len(), 4);
+        assert_eq!(interner.id("b"), Some(1));
+        assert_eq!(interner.id("e"), None);
+        assert_eq!(interner.name(3), Some("d"));
+        assert_eq!(interner.name(4), None);
+        assert!(Interner::new().is_empty());
+    }
+
+    #[test]
+    fn stop() {
+        let mut interner = Interner::new();
+        let json = br#"{"a":1,"b":2,"c":3}"#;
+        let ret = parse_interned(
+            json,
+            0,
+            &mut interner,
+            |id, _, _, _| {
+                if id == Some(1) {
+                    0
+                } else {
+                    1
+                }
+            },
+        );
+        assert_eq!(ret, 10);
+        assert_eq!(interner.len(), 2);
+        assert!(parse_interned(b"{\"a\":}", 0, &mut interner, |_, _, _, _| 1) <= 0);
+    }
+
+    #[test]
+    fn distinct() {
+        let json = br#"[{"b":1,"a":{"c":[{"a\t":2,"\u0062":3}]}},{"":4},"d"]"#;
+        let keys = distinct_keys(json).unwrap();
+        assert_eq!(
+            keys.iter().map(String::as_str).collect::<Vec<_>>(),
+            ["", "a", "a\t", "b", "c"]
+        );
+        assert!(distinct_keys(b"[1,2]").unwrap().is_empty());
+        let err = distinct_keys(br#"{"a":1,"b"}"#).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Syntax);
+    }
+
+    #[test]
+    fn count() {
+        let json = br#"[{"id":1,"deleted":true},{"id":2,"meta":{"deleted":false}},{"\u0064eleted":1,"x":"deleted"}]"#;
+        assert_eq!(count_key(json, "deleted"), Ok(3));
+        assert_eq!(count_key(json, "id"), Ok(2));
+        assert_eq!(count_key(json, "x"), Ok(1));
+        assert_eq!(count_key(json, "missing"), Ok(0));
+        assert_eq!(count_key(b"[1,2]", ""), Ok(0));
+        assert_eq!(count_key(br#"{"":1}"#, ""), Ok(1));
+        let err = count_key(br#"{"a":1,}"#, "a").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Syntax);
+    }
+}
@@ -1,9 +1,90 @@
 #![allow(unused)]
+#![allow(
+    clippy::needless_return,
+    clippy::collapsible_if,
+    clippy::never_loop,
+    clippy::tabs_in_doc_comments,
+    clippy::non_minimal_cfg,
+    clippy::assign_op_pattern,
+    clippy::needless_late_init,
+    clippy::identity_op,
+    clippy::neg_multiply,
+    clippy::needless_doctest_main
+)]
 
 // Copyright 2021 Joshua J Baker. All rights reserved.
 // Use of this source code is governed by an MIT-style
 // license that can be found in the LICENSE file.
 
+mod canonical;
+#[cfg(feature = "channel")]
+mod channel;
+#[cfg(feature = "datetime")]
+mod datetime;
+#[cfg(feature = "decimal")]
+mod decimal;
+mod decoded;
+mod diff;
+mod entries;
+mod equal;
+mod error;
+mod escape;
+mod extract;
+mod get;
+mod index;
+mod intern;
+mod number;
+mod paths;
+mod pointer;
+mod query;
+mod rewrite;
+mod stream;
+mod structural;
+mod tape;
+mod token;
+mod tree;
+mod utf16;
+
+pub use canonical::{canonical_hash, canonicalize, hash_lines};
+#[cfg(feature = "channel")]
+pub use channel::{parse_to_channel, CHANNEL_BOUND};
+#[cfg(feature = "datetime")]
+pub use datetime::{try_datetime, DateTime};
+#[cfg(feature = "decimal")]
+pub use decimal::{number_decimal, Decimal, MAX_SCALE};
+pub use decoded::{parse_decoded, parse_unescaped, Control, DecodedEvent};
+pub use diff::{diff, Change};
+pub use entries::parse_entries;
+pub use equal::equal;
+pub use error::{
+    error_context, offset_to_line_col, ErrorKind, LineIndex, ParseError, ParseStatus, StopOffsets,
+};
+pub use escape::{
+    decode_latin1, escape, escape_into, is_valid_utf8_string, key_inner, unescape, unescaped_len,
+};
+pub use extract::{
+    extract, keys_with_prefix, keys_with_prefix_depth, root_map, string_values, Type,
+};
+pub use get::{get, get_count, lookup, Match, Path};
+pub use index::Index;
+pub use intern::{count_key, distinct_keys, parse_interned, Interner};
+pub use number::{
+    number_canonical, number_f64, number_i64, number_is_exact_f64, number_u64, parse_number_array,
+};
+pub use paths::{error_path, parse_paths};
+pub use pointer::{get_pointer, get_pointer_all, get_pointer_info};
+pub use query::{query, query_first};
+pub use rewrite::{normalize_to, rewrite, whitespace_bytes, Style};
+pub use stream::{parse_stream_reader, parse_stream_reader_with, validate_lines};
+pub use structural::structural_indices;
+pub use tape::{to_tape, TapeEntry, TapeKind};
+pub use token::{
+    find_close, parse_into, parse_to_vec, parse_until, parse_value, revalidate, root_kind,
+    skip_values, trimmed_value, Kind, Token,
+};
+pub use tree::{build, TreeBuilder};
+pub use utf16::{transcode_utf16_to_utf8, utf8_to_utf16_offset};
+
 // Bit flags passed to the "info" parameter of the iter function which
 // provides additional information about the data
 
@@ -45,8 +126,146 @@ pub const SIGN: usize = 1 << 17;
 pub const DOT: usize = 1 << 18;
 /// the data is a Number in scientific notation (has 'E' or 'e')
 pub const E: usize = 1 << 19;
+/// the data is a complete Object member, from the opening quote of the key to
+/// the end of the value. Only reported when using the PAIRS option.
+pub const PAIR: usize = 1 << 20;
+/// the data is the integer digits of a Number. Only reported when using the
+/// SPLIT_NUMBERS option.
+pub const INTEGER: usize = 1 << 21;
+/// the data is the fraction digits of a Number, after the radix point. Only
+/// reported when using the SPLIT_NUMBERS option.
+pub const FRACTION: usize = 1 << 22;
+/// the data is the exponent digits of a Number, or the sign of the exponent
+/// when combined with SIGN. Only reported when using the SPLIT_NUMBERS option.
+pub const EXPONENT: usize = 1 << 23;
+/// the data is an escape sequence inside of a String or Object key, such as
+/// '\n' or '\u00e9'. Only reported when using the ESCAPES option.
+pub const ESCAPE: usize = 1 << 24;
+/// the data is the empty range at the start of the json, before the first
+/// element. Only reported when using the DOC_EVENTS option.
+pub const DOC_BEGIN: usize = 1 << 25;
+/// the data is the empty range at the end of the json, after the last element
+/// and any trailing whitespace. Only reported when using the DOC_EVENTS
+/// option.
+pub const DOC_END: usize = 1 << 26;
+
+// Bit flags passed to the "opts" parameter of the parse functions.
 
+/// skip validation of the children of a container when 'iter' returns -1.
+/// The children are assumed to be valid JSON, and only the balance of the
+/// brackets is verified. A truncated container is still reported as an error.
 pub const UNCHECKED: usize = 1 << 1;
+/// include Object keys in the elements reported by parse_values
+pub const INCLUDE_KEYS: usize = 1 << 2;
+/// allow the JSON5 extensions to Strings, such as '\xFF' escapes and line
+/// continuations (a '\' followed by a line terminator), and allow the U+2028
+/// and U+2029 line separators as whitespace between elements
+pub const JSON5: usize = 1 << 3;
+/// only allow ASCII characters in Strings, any byte >= 0x80 is an error
+pub const ASCII_ONLY: usize = 1 << 4;
+/// report a PAIR element after each Object member's value
+pub const PAIRS: usize = 1 << 5;
+/// require the keys of every Object to be in strictly ascending order, which
+/// also means that they must be unique. Keys are compared byte-wise as they
+/// appear in the json, without decoding escape sequences. An out of order key
+/// is an error at the key's offset.
+pub const SORTED_KEYS: usize = 1 << 6;
+/// skip a leading UTF-8 byte order mark and then a leading line that starts
+/// with '#', such as a "#!/usr/bin/env" shebang or a comment header. This is
+/// not standard JSON and is meant for executable config files.
+pub const CONFIG: usize = 1 << 7;
+/// allow empty slots in Arrays, such as "[1,,2]" or "[,1]", for best-effort
+/// ingestion of malformed json. Each empty slot is reported as a NULL VALUE
+/// with an empty range at the offset of the comma that follows it. A trailing
+/// comma is still an error.
+pub const EMPTY_SLOTS: usize = 1 << 8;
+/// reject leading zeros in the exponent of a Number, such as "1e05", in the
+/// same way that they are always rejected in the significand. The JSON grammar
+/// allows them, so by default they are accepted.
+pub const STRICT_EXPONENTS: usize = 1 << 9;
+/// allow raw tab, line feed, and carriage return characters in Strings, which
+/// is a common violation in hand-written and logged json. All other control
+/// characters are still an error. This is not standard JSON and is only meant
+/// for tolerant ingestion.
+pub const RAW_WHITESPACE: usize = 1 << 10;
+/// only allow ASCII characters in Object keys, any byte >= 0x80 in a key is an
+/// error at the key's offset. The key is checked as it appears in the json,
+/// so an escape such as "\u00e9" is allowed.
+pub const ASCII_KEYS: usize = 1 << 11;
+/// report each Number as its separate parts rather than as a single element,
+/// such as for a syntax highlighter. The parts are the sign (SIGN), the integer
+/// digits (INTEGER), the radix point (DOT), the fraction digits (FRACTION),
+/// the 'e' or 'E' (E), the sign of the exponent (EXPONENT and SIGN), and the
+/// exponent digits (EXPONENT). Every part also has the NUMBER bit, and for a
+/// root Number the first part has START and the last part has END.
+pub const SPLIT_NUMBERS: usize = 1 << 12;
+/// report an ESCAPE element for every escape sequence of a String or Object
+/// key, right after the element of the String or key itself. A surrogate pair
+/// is reported as two escapes.
+pub const ESCAPES: usize = 1 << 13;
+/// report a DOC_BEGIN element before all other elements and a DOC_END element
+/// after them, so that every document is wrapped in the same pair of elements
+/// whether its root is a container or not. DOC_END is only reported when the
+/// whole document is valid. Returning 0 from 'iter' for either stops the
+/// parsing, and any other value continues. A stop at DOC_BEGIN makes parse
+/// return 0, just like an error at the first byte, so use parse_status to tell
+/// them apart, which reports the stop as Stopped(0).
+pub const DOC_EVENTS: usize = 1 << 14;
+/// reject a Number in scientific notation, such as "1e5", with an error at the
+/// 'e' or 'E'
+pub const FORBID_EXPONENTS: usize = 1 << 15;
+/// reject a Number with a fraction, such as "1.5", with an error at the radix
+/// point
+pub const FORBID_FRACTIONS: usize = 1 << 16;
+/// reject a negative Number, including "-0", with an error at the '-'
+pub const FORBID_NEGATIVE: usize = 1 << 17;
+/// require the root value to be an Object or Array, as in the older RFC 4627.
+/// A scalar root, such as "42" or "true", is an error at its offset, which
+/// parse_status reports as a ScalarRoot error.
+pub const CONTAINER_ROOT: usize = 1 << 18;
+/// skip a single "/* ... */" comment block before the root value, such as a
+/// "generated by" notice that some exports put in front of the json. No other
+/// comments are allowed, and an unclosed block is an error. This is not
+/// standard JSON and is only meant for interop with such exports.
+pub const PRELUDE: usize = 1 << 19;
+/// reject a "\u0000" escape in a String or Object key, with an error at the
+/// escape's backslash, for consumers that use the strings as NUL-terminated C
+/// strings. With JSON5, a "\x00" escape is also rejected. A raw NUL byte is
+/// always an error.
+pub const NO_NUL: usize = 1 << 20;
+/// report each Object that is an element of an Array as a single OBJECT VALUE
+/// element that covers the whole Object, from its '{' to its '}', without the
+/// elements inside of it, such as for handing each record of an Array of
+/// records off to be processed separately. The Object is still validated. The
+/// Array itself and an Object that is not in an Array are reported as usual.
+pub const RECORDS: usize = 1 << 21;
+/// treat an empty document, or one with only whitespace, as a Null value, like
+/// some HTTP services do for an empty body. A single NULL element with START
+/// and END is reported, which is empty and at the end of the json. This has no
+/// effect with CONTAINER_ROOT, where an empty document is still an error.
+pub const EMPTY_AS_NULL: usize = 1 << 22;
+/// reject the "\/" escape in Strings and Object keys, which requires every '/'
+/// to be written bare, as in the canonical form made by canonicalize. The
+/// error is at the backslash.
+pub const FORBID_ESCAPED_SOLIDUS: usize = 1 << 23;
+/// reject a bare '/' in Strings and Object keys, which requires every '/' to be
+/// written as the "\/" escape, such as for embedding json in an HTML script
+/// tag. The error is at the '/'. The escape and escape_into functions also
+/// take this option, to write every '/' escaped.
+pub const REQUIRE_ESCAPED_SOLIDUS: usize = 1 << 24;
+/// report an Array that has only Numbers, or only Strings, as a single element
+/// that covers the whole Array, from its '[' to its ']', with the ARRAY bit and
+/// either the NUMBER or the STRING bit, but without OPEN or CLOSE, such as for
+/// reading a long list of numbers in one go. Use packed_len for the number of
+/// values. The values are still validated. Any other Array, including an empty
+/// one, is reported as usual, with all of its elements.
+pub const PACKED_ARRAYS: usize = 1 << 25;
+
+// The options that only change which elements are reported, and not what is
+// valid. The helpers that work on the plain elements of a document, such as
+// build and rewrite, clear these from the options that they are passed.
+pub(crate) const EVENT_OPTS: usize =
+    PAIRS | SPLIT_NUMBERS | ESCAPES | DOC_EVENTS | RECORDS | PACKED_ARRAYS;
 
 /// Parse JSON. The iter function is a callback that fires for every element in
 /// the JSON document. Elements include all values and tokens. The 'start' and
@@ -64,6 +283,9 @@ pub const UNCHECKED: usize = 1 << 1;
 /// This operation returns zero or a negative value if an error
 /// occured. This value represents the position that the parser was at when it
 /// discovered the error. To get the true offset multiple this value by -1.
+/// A document requires a value, so an empty or whitespace-only json is an
+/// error at the end of the json. Use parse_status to tell an error at offset
+/// zero apart from 'iter' stopping at the first element.
 ///
 /// This operation returns a positive value when successful. If the 'iter'
 /// stopped early then this value will be the position the parser was at when
@@ -118,16 +340,555 @@ pub const UNCHECKED: usize = 1 << 1;
 /// // "tw"
 /// ```
 pub fn parse<F>(json: &[u8], opts: usize, iter: F) -> i64
+where
+    F: FnMut(usize, usize, usize) -> i64,
+{
+    ParserState::new().parse_reuse(json, opts, iter)
+}
+
+/// Options for parse_with, for the settings that do not fit in the bit flags
+/// of the "opts" parameter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// the bit flags, such as JSON5, that are otherwise passed as "opts"
+    pub flags: usize,
+    /// the character that separates the elements of Objects and Arrays.
+    /// Anything other than ',' is not standard JSON and is only meant for
+    /// interop with broken exporters, such as one that uses ';'.
+    pub comma: u8,
+    /// the character that separates the keys and values of Objects. Anything
+    /// other than ':' is not standard JSON.
+    pub colon: u8,
+    /// the largest magnitude allowed for the exponent of a Number, such as
+    /// 400 in "1e-400". Leading zeros are not counted. A larger exponent is an
+    /// error at the digit that pushes it over the limit.
+    pub max_exponent: u64,
+    /// the most digits allowed in the significand of a Number, counting the
+    /// digits on both sides of the radix point. More digits are an error at
+    /// the first digit over the limit.
+    pub max_significand_digits: usize,
+    /// the largest json allowed, in bytes. A larger json is a LimitExceeded
+    /// error at this offset, without parsing any of it. For parse_with the
+    /// error is returned as the negative offset, so use parse_status_with to
+    /// tell it apart from other errors.
+    pub max_total_bytes: usize,
+    /// the deepest nesting allowed for Objects and Arrays, where a root
+    /// container is at depth one. A deeper container is an error at its
+    /// opening character, also when it is being skipped over, including with
+    /// the UNCHECKED option.
+    pub max_depth: usize,
+    /// the most members allowed in a single Object. Another member is an error
+    /// at its key. Objects that are skipped over are not counted.
+    pub max_members: usize,
+    /// the most elements allowed in a single Array. Another element is an error
+    /// at its first character. Arrays that are skipped over are not counted.
+    pub max_array_len: usize,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            flags: 0,
+            comma: b',',
+            colon: b':',
+            max_exponent: u64::MAX,
+            max_significand_digits: usize::MAX,
+            max_total_bytes: usize::MAX,
+            max_depth: usize::MAX,
+            max_members: usize::MAX,
+            max_array_len: usize::MAX,
+        }
+    }
+}
+
+/// Parse JSON with Options. This works just like parse, except that the
+/// settings come from the options.
+///
+/// The comma and colon characters must be ASCII and must not be whitespace or
+/// any other character that has a meaning in JSON, otherwise the parsing fails.
+/// The COMMA and COLON elements are reported with the configured characters.
+pub fn parse_with<F>(json: &[u8], options: &Options, iter: F) -> i64
+where
+    F: FnMut(usize, usize, usize) -> i64,
+{
+    ParserState::new().parse_with(json, options, iter)
+}
+
+/// Parse JSON with Options, returning the outcome as a ParseStatus. This works
+/// just like parse_status, except that the settings come from the options.
+pub fn parse_status_with<F>(json: &[u8], options: &Options, iter: F) -> ParseStatus
+where
+    F: FnMut(usize, usize, usize) -> i64,
+{
+    let mut st = ParserState::new();
+    st.set_options(options);
+    st.status(json, options.flags, iter)
+}
+
+/// A reusable parser state that holds onto the scratch memory needed for
+/// parsing, such as the stack used for skipping over the children of
+/// containers. Using the same state for many parses avoids reallocating that
+/// memory for every call.
+pub struct ParserState {
+    stack: Vec<u8>,
+    keys: Vec<(usize, usize)>,
+    comma: u8,
+    colon: u8,
+    max_exponent: u64,
+    max_significand_digits: usize,
+    max_total_bytes: usize,
+    // the deepest nesting allowed, see Options::max_depth
+    max_depth: usize,
+    // the depth of the container that is being parsed, zero at the root
+    depth: usize,
+    // the largest containers allowed, see Options::max_members and
+    // Options::max_array_len
+    max_members: usize,
+    max_array_len: usize,
+    // set by vdoc when a scalar root is rejected by the CONTAINER_ROOT option
+    scalar_root: bool,
+}
+
+impl Default for ParserState {
+    fn default() -> ParserState {
+        ParserState {
+            stack: Vec::new(),
+            keys: Vec::new(),
+            comma: b',',
+            colon: b':',
+            max_exponent: u64::MAX,
+            max_significand_digits: usize::MAX,
+            max_total_bytes: usize::MAX,
+            max_depth: usize::MAX,
+            depth: 0,
+            max_members: usize::MAX,
+            max_array_len: usize::MAX,
+            scalar_root: false,
+        }
+    }
+}
+
+impl ParserState {
+    /// Create a new parser state. No memory is allocated until needed.
+    pub fn new() -> ParserState {
+        ParserState::default()
+    }
+
+    /// Parse JSON. This works just like parse, except that the scratch memory
+    /// held by this state is reused across calls.
+    pub fn parse_reuse<F>(&mut self, json: &[u8], opts: usize, iter: F) -> i64
+    where
+        F: FnMut(usize, usize, usize) -> i64,
+    {
+        self.set_options(&Options::default());
+        self.run(json, opts, iter)
+    }
+
+    /// Parse JSON with Options. This works just like parse_with, except that
+    /// the scratch memory held by this state is reused across calls.
+    pub fn parse_with<F>(&mut self, json: &[u8], options: &Options, iter: F) -> i64
+    where
+        F: FnMut(usize, usize, usize) -> i64,
+    {
+        self.set_options(options);
+        self.run(json, options.flags, iter)
+    }
+
+    pub(crate) fn set_options(&mut self, options: &Options) {
+        self.comma = options.comma;
+        self.colon = options.colon;
+        self.max_exponent = options.max_exponent;
+        self.max_significand_digits = options.max_significand_digits;
+        self.max_total_bytes = options.max_total_bytes;
+        self.max_depth = options.max_depth;
+        self.max_members = options.max_members;
+        self.max_array_len = options.max_array_len;
+    }
+
+    pub(crate) fn status<F>(&mut self, json: &[u8], opts: usize, iter: F) -> ParseStatus
+    where
+        F: FnMut(usize, usize, usize) -> i64,
+    {
+        if json.len() > self.max_total_bytes {
+            let err = ParseError::new(ErrorKind::LimitExceeded, self.max_total_bytes);
+            return ParseStatus::Error(err);
+        }
+        let mut f = iter;
+        let (i, ok, stop) = vdoc(json, 0, opts, self, &mut f, false);
+        if !ok && self.scalar_root {
+            ParseStatus::Error(ParseError::new(ErrorKind::ScalarRoot, i))
+        } else if !ok {
+            ParseStatus::Error(ParseError::from_ret(json, -(i as i64)))
+        } else if stop {
+            ParseStatus::Stopped(i)
+        } else {
+            ParseStatus::Complete(i)
+        }
+    }
+
+    fn run<F>(&mut self, json: &[u8], opts: usize, iter: F) -> i64
+    where
+        F: FnMut(usize, usize, usize) -> i64,
+    {
+        if json.len() > self.max_total_bytes {
+            return -(self.max_total_bytes as i64);
+        }
+        let mut f = iter;
+        let (i, ok, _) = vdoc(json, 0, opts, self, &mut f, false);
+        if !ok {
+            i as i64 * -1
+        } else {
+            i as i64
+        }
+    }
+}
+
+/// Parse JSON, reporting only values. This works just like parse, except that
+/// the COMMA and COLON elements are not passed to the iter function. They are
+/// still parsed and validated, just not reported. Object keys are also not
+/// reported unless the INCLUDE_KEYS option is provided.
+///
+/// The return value has the same meaning as for parse.
+pub fn parse_values<F>(json: &[u8], opts: usize, iter: F) -> i64
+where
+    F: FnMut(usize, usize, usize) -> i64,
+{
+    let mut f = iter;
+    let mut mask = COMMA | COLON;
+    if opts & INCLUDE_KEYS != INCLUDE_KEYS {
+        mask |= KEY;
+    }
+    parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        if info & mask != 0 {
+            return 1;
+        }
+        f(start, end, info)
+    })
+}
+
+/// Parse JSON using a callback trait object. This works just like parse, but
+/// the iter function is dynamically dispatched, which allows for callbacks to
+/// be stored and passed around as `&mut dyn FnMut`.
+pub fn parse_dyn(
+    json: &[u8],
+    opts: usize,
+    iter: &mut dyn FnMut(usize, usize, usize) -> i64,
+) -> i64 {
+    parse(json, opts, iter)
+}
+
+/// A visitor of the elements of a JSON document, used by parse_visitor. This
+/// is an alternative to the iter function for when the state of the parsing is
+/// kept in a struct of its own.
+pub trait Visitor {
+    /// Called for every element in the JSON document. The params and the
+    /// return value have the same meaning as for the iter function of parse.
+    fn visit(&mut self, start: usize, end: usize, info: usize) -> i64;
+}
+
+/// Parse JSON using a Visitor. This works just like parse, except that each
+/// element is passed to the visitor.
+pub fn parse_visitor<V>(json: &[u8], opts: usize, visitor: &mut V) -> i64
+where
+    V: Visitor + ?Sized,
+{
+    parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        visitor.visit(start, end, info)
+    })
+}
+
+/// Parse JSON, searching for an element. This works just like parse, except
+/// for the return value.
+///
+/// Returns Some with the position the parser was at when 'iter' returned 0 to
+/// stop the parsing, which means that the element was found. Returns None when
+/// the parser reached the end of the document without being stopped, or when
+/// the document is invalid.
+pub fn parse_find<F>(json: &[u8], opts: usize, iter: F) -> Option<usize>
 where
     F: FnMut(usize, usize, usize) -> i64,
 {
     let mut f = iter;
-    let (i, ok, _) = vdoc(json, 0, opts, &mut f, false);
+    let mut st = ParserState::new();
+    let (i, ok, stop) = vdoc(json, 0, opts, &mut st, &mut f, false);
+    if ok && stop {
+        Some(i)
+    } else {
+        None
+    }
+}
+
+/// Parse JSON while reporting progress. This works just like parse, and the
+/// 'progress' function is also called with the number of bytes consumed so
+/// far, each time at least 'every' more bytes have been processed since the
+/// last call, and with the total length when the document is complete.
+/// Progress is only checked between elements, so a large String or a skipped
+/// container is reported as a single step.
+pub fn parse_with_progress<F, P>(
+    json: &[u8],
+    opts: usize,
+    every: usize,
+    iter: F,
+    progress: P,
+) -> i64
+where
+    F: FnMut(usize, usize, usize) -> i64,
+    P: FnMut(usize),
+{
+    let mut iter = iter;
+    let mut progress = progress;
+    let every = every.max(1);
+    let mut next = every;
+    let mut last = 0;
+    let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        if end >= next {
+            progress(end);
+            last = end;
+            next = end + every;
+        }
+        iter(start, end, info)
+    });
+    if ret > 0 && ret as usize == json.len() && last != json.len() {
+        progress(json.len());
+    }
+    ret
+}
+
+/// Parse JSON, returning the outcome as a ParseStatus. This works just like
+/// parse, except for the return value, which tells apart a complete document,
+/// one that was stopped by 'iter', and an error, including when they happen
+/// at offset zero.
+///
+/// An empty or whitespace-only json is an UnexpectedEof error.
+pub fn parse_status<F>(json: &[u8], opts: usize, iter: F) -> ParseStatus
+where
+    F: FnMut(usize, usize, usize) -> i64,
+{
+    ParserState::new().status(json, opts, iter)
+}
+
+/// Parse JSON, returning both where the parsing ended and where the next token
+/// begins. This works just like parse_status, except that a stop is always at
+/// the end of the element that 'iter' stopped on, whatever its kind, and the
+/// whitespace after the stop is also skipped, such as for starting another
+/// scan of the same json at the next token. For a complete document both
+/// offsets are the length of the json.
+///
+/// ```
+/// let json = br#"[1,  "two" ,3]"#;
+/// let offsets = pjson::parse_stop_offsets(json, 0, |_, _, info| {
+///     (info & pjson::STRING == 0) as i64
+/// });
+/// assert_eq!(offsets.unwrap().stop_offset, 10);
+/// assert_eq!(offsets.unwrap().next_token_offset, 11);
+/// ```
+pub fn parse_stop_offsets<F>(json: &[u8], opts: usize, iter: F) -> Result<StopOffsets, ParseError>
+where
+    F: FnMut(usize, usize, usize) -> i64,
+{
+    let mut iter = iter;
+    let mut last = 0;
+    let status = parse_status(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        last = end;
+        iter(start, end, info)
+    });
+    let stop_offset = match status {
+        ParseStatus::Error(err) => return Err(err),
+        ParseStatus::Complete(i) => i,
+        ParseStatus::Stopped(_) => last,
+    };
+    Ok(StopOffsets {
+        stop_offset,
+        next_token_offset: skipws(json, stop_offset, opts),
+    })
+}
+
+/// Parse at most the first 'n' elements of a root Array, or members of a root
+/// Object, and then stop, such as for previewing a large dataset. Unlike the
+/// max_array_len and max_members limits, a longer container is not an error.
+///
+/// The elements are reported to 'iter' as usual, including all of those
+/// inside of the first 'n' elements. The parsing stops at the ',' after the
+/// n-th element, whose offset is returned, and whatever follows it is not
+/// validated. With an 'n' of zero, the parsing stops right after the '[' or
+/// '{' is reported, and the offset just past that character is returned, so
+/// that the stop is never mistaken for an error at offset zero. When the
+/// container has no more than 'n' elements, or when the root is a scalar, the
+/// whole document is parsed. The return value is otherwise that of parse.
+///
+/// ```
+/// let json = br#"[{"a":1}, [2, 3], 4]"#;
+/// let mut count = 0;
+/// let ret = pjson::parse_head(json, 0, 2, |_, _, _| {
+///     count += 1;
+///     1
+/// });
+/// assert_eq!(ret, 16);
+/// assert_eq!(count, 12);
+/// assert_eq!(pjson::parse_head(json, 0, 3, |_, _, _| 1), json.len() as i64);
+/// ```
+pub fn parse_head<F>(json: &[u8], opts: usize, n: usize, iter: F) -> i64
+where
+    F: FnMut(usize, usize, usize) -> i64,
+{
+    let mut iter = iter;
+    let mut depth = 0;
+    let mut commas = 0;
+    let mut open = None;
+    let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        if depth == 1 && info & COMMA == COMMA {
+            commas += 1;
+            if commas == n {
+                return 0;
+            }
+        }
+        let r = iter(start, end, info);
+        if info & OPEN == OPEN {
+            depth += 1;
+            if depth == 1 && n == 0 && r != 0 {
+                open = Some(end);
+                return 0;
+            }
+        } else if info & CLOSE == CLOSE {
+            depth -= 1;
+        }
+        r
+    });
+    match open {
+        Some(end) => end as i64,
+        None => ret,
+    }
+}
+
+/// Validate the JSON value at the start of json, ignoring whatever follows it.
+/// This is for JSON that is embedded at the front of a larger buffer, such as
+/// a JSON header followed by binary data.
+///
+/// Returns Some with the index just past the first complete value, or None
+/// when json does not start with a valid value. Leading whitespace is allowed,
+/// but the trailing whitespace is not consumed.
+///
+/// The header that is skipped by the CONFIG or PRELUDE option may come before
+/// the value, and CONTAINER_ROOT requires the value to be an Object or Array.
+/// The value is always validated, so the UNCHECKED option is ignored, as is
+/// EMPTY_AS_NULL.
+pub fn valid_prefix(json: &[u8], opts: usize) -> Option<usize> {
+    let mut st = ParserState::new();
+    let opts = opts & !UNCHECKED;
+    let (i, ok) = skip_header(json, 0, opts);
     if !ok {
-        i as i64 * -1
+        return None;
+    }
+    if opts & CONTAINER_ROOT == CONTAINER_ROOT {
+        let i = skipws(json, i, opts);
+        if i < json.len() && json[i] != b'{' && json[i] != b'[' {
+            return None;
+        }
+    }
+    let mut f = |_: usize, _: usize, _: usize| -> i64 { 1 };
+    let (i, ok, stop) = vany(json, i, opts, &mut st, START, &mut f, true);
+    if ok && !stop {
+        Some(i)
+    } else {
+        None
+    }
+}
+
+/// Validate JSON, also requiring that no Object or Array is nested deeper
+/// than max_depth. The root container is at depth one, so a max_depth of zero
+/// only allows a scalar document. The iter function is not used and the
+/// containers are skipped over, so this is faster than tracking the depth
+/// during a parse, and the memory used is bounded by max_depth.
+pub fn valid_within_depth(json: &[u8], opts: usize, max_depth: usize) -> bool {
+    let mut st = ParserState::new();
+    st.max_depth = max_depth;
+    let opts = opts & !UNCHECKED;
+    let mut f = |_: usize, _: usize, _: usize| -> i64 { 1 };
+    let (_, ok, _) = vdoc(json, 0, opts, &mut st, &mut f, true);
+    ok
+}
+
+/// Parse JSON and return the offsets of the Objects and Arrays that are nested
+/// deeper than max_depth, such as for linting a config for complexity. Unlike
+/// valid_within_depth, the deep containers are not an error, and the whole
+/// document is still validated. The root container is at depth one. Every
+/// container that is too deep is reported, including those that are nested in
+/// another one that is too deep.
+///
+/// ```
+/// let json = br#"{"a":[1,{"b":[]}],"c":{}}"#;
+/// assert_eq!(pjson::deep_containers(json, 0, 2).unwrap(), [8, 13]);
+/// ```
+pub fn deep_containers(
+    json: &[u8],
+    opts: usize,
+    max_depth: usize,
+) -> Result<Vec<usize>, ParseError> {
+    let mut offsets = Vec::new();
+    let mut depth = 0;
+    let opts = opts & !EVENT_OPTS;
+    let ret = parse(json, opts, |start: usize, _: usize, info: usize| -> i64 {
+        if info & OPEN == OPEN {
+            depth += 1;
+            if depth > max_depth {
+                offsets.push(start);
+            }
+        } else if info & CLOSE == CLOSE {
+            depth -= 1;
+        }
+        1
+    });
+    if ret <= 0 {
+        return Err(ParseError::from_ret(json, ret));
+    }
+    Ok(offsets)
+}
+
+/// Get the number of values of an Array that was reported as a single element
+/// by the PACKED_ARRAYS option. The 'start', 'end', and 'info' params are those
+/// of the element, as passed to the iter function of parse.
+///
+/// ```
+/// let json = br#"{"a":[1, 2.5, -3]}"#;
+/// let mut len = 0;
+/// pjson::parse(json, pjson::PACKED_ARRAYS, |start, end, info| {
+///     if info & pjson::ARRAY == pjson::ARRAY && info & pjson::OPEN == 0 {
+///         len = pjson::packed_len(json, start, end, info);
+///     }
+///     1
+/// });
+/// assert_eq!(len, 3);
+/// ```
+pub fn packed_len(json: &[u8], start: usize, end: usize, info: usize) -> usize {
+    let mut count = 0;
+    let mut i = start + 1;
+    if info & STRING == STRING {
+        while i < end {
+            if json[i] == b'"' {
+                count += 1;
+                i += 1;
+                while i < end && json[i] != b'"' {
+                    i += if json[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            i += 1;
+        }
     } else {
-        i as i64
+        // each Number is a run of the characters that Numbers are made of
+        let mut prev = false;
+        while i < end - 1 {
+            let cur = json[i] == b'-'
+                || json[i] == b'.'
+                || json[i] == b'+'
+                || json[i].is_ascii_alphanumeric();
+            if cur && !prev {
+                count += 1;
+            }
+            prev = cur;
+            i += 1;
+        }
     }
+    count
 }
 
 const CHWS: u8 = 1 << 1;
@@ -136,6 +897,7 @@ const CHSTRTOK: u8 = 1 << 3;
 const CHSQUASH: u8 = 1 << 4;
 const CHOPEN: u8 = 1 << 5;
 const CHCLOSE: u8 = 1 << 6;
+const CHSTRUCT: u8 = 1 << 7;
 
 static CHTABLE: [u8; 256] = {
     let mut table = [0; 256];
@@ -196,6 +958,14 @@ static CHTABLE: [u8; 256] = {
     table[b'}' as usize] |= CHSQUASH | CHCLOSE;
     table[b']' as usize] |= CHSQUASH | CHCLOSE;
 
+    table[b'"' as usize] |= CHSTRUCT;
+    table[b'{' as usize] |= CHSTRUCT;
+    table[b'[' as usize] |= CHSTRUCT;
+    table[b'}' as usize] |= CHSTRUCT;
+    table[b']' as usize] |= CHSTRUCT;
+    table[b':' as usize] |= CHSTRUCT;
+    table[b',' as usize] |= CHSTRUCT;
+
     table
 };
 
@@ -204,21 +974,109 @@ fn isws(ch: u8) -> bool {
     CHTABLE[ch as usize] & CHWS == CHWS
 }
 
+// isls returns true for the UTF-8 encoding of the U+2028 and U+2029 line
+// separators, which are whitespace in JSON5.
+fn isls(json: &[u8], i: usize) -> bool {
+    i + 2 < json.len() && json[i] == 0xE2 && json[i + 1] == 0x80 && (json[i + 2] | 1) == 0xA9
+}
+
+// skip_header returns the index after the parts of the json before the root
+// value that are skipped by the CONFIG and PRELUDE options. The bool is false
+// when the PRELUDE comment is not closed.
+fn skip_header(json: &[u8], mut i: usize, opts: usize) -> (usize, bool) {
+    if opts & CONFIG == CONFIG {
+        if json[i..].starts_with(b"\xEF\xBB\xBF") {
+            i += 3;
+        }
+        if i < json.len() && json[i] == b'#' {
+            while i < json.len() && json[i] != b'\n' {
+                i += 1;
+            }
+        }
+    }
+    if opts & PRELUDE == PRELUDE {
+        let j = skipws(json, i, opts);
+        if json[j..].starts_with(b"/*") {
+            match json[j + 2..].windows(2).position(|w| w == b"*/") {
+                Some(n) => i = j + 2 + n + 2,
+                None => return (json.len(), false),
+            }
+        }
+    }
+    (i, true)
+}
+
+// skipws returns the index of the next non-whitespace character.
+fn skipws(json: &[u8], mut i: usize, opts: usize) -> usize {
+    while i < json.len() {
+        if isws(json[i]) {
+            i += 1;
+        } else if opts & JSON5 == JSON5 && isls(json, i) {
+            i += 3;
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+// isscalar returns true for the first character of a String, Number, True,
+// False, or Null.
+fn isscalar(ch: u8) -> bool {
+    matches!(ch, b'"' | b'-' | b't' | b'f' | b'n') || isnum(ch)
+}
+
 fn isnum(ch: u8) -> bool {
     // ch >= b'0' && ch <= b'9'
     CHTABLE[ch as usize] & CHNUM == CHNUM
 }
 
+fn ishex(ch: u8) -> bool {
+    ch.is_ascii_hexdigit()
+}
+
 fn isstrtok(ch: u8) -> bool {
     // ch < b' ' || ch == b'"' || ch == b'\\'
     CHTABLE[ch as usize] & CHSTRTOK == CHSTRTOK
 }
 
-fn vdoc<F>(json: &[u8], i: usize, opts: usize, f: &mut F, skip: bool) -> (usize, bool, bool)
+fn vdoc<F>(
+    json: &[u8],
+    i: usize,
+    opts: usize,
+    st: &mut ParserState,
+    f: &mut F,
+    skip: bool,
+) -> (usize, bool, bool)
 where
     F: FnMut(usize, usize, usize) -> i64,
 {
-    let (mut i, ok, stop) = vany(json, i, opts, START, f, skip);
+    let mut i = i;
+    let events = !skip && opts & DOC_EVENTS == DOC_EVENTS;
+    if events && f(0, 0, DOC_BEGIN) == 0 {
+        return (0, true, true);
+    }
+    let (i_, ok) = skip_header(json, i, opts);
+    if !ok {
+        return (i_, false, true);
+    }
+    i = i_;
+    st.scalar_root = false;
+    if opts & CONTAINER_ROOT == CONTAINER_ROOT {
+        i = skipws(json, i, opts);
+        if i < json.len() && json[i] != b'{' && json[i] != b'[' {
+            st.scalar_root = isscalar(json[i]);
+            return (i, false, true);
+        }
+    }
+    let empty = opts & (EMPTY_AS_NULL | CONTAINER_ROOT) == EMPTY_AS_NULL
+        && skipws(json, i, opts) == json.len();
+    let (mut i, ok, stop) = if empty {
+        let i = json.len();
+        (i, true, !skip && f(i, i, NULL | START | END) == 0)
+    } else {
+        vany(json, i, opts, st, START, f, skip)
+    };
     if stop {
         return (i, ok, stop);
     }
@@ -227,13 +1085,23 @@ where
             i += 1;
             continue;
         }
+        if opts & JSON5 == JSON5 && isls(json, i) {
+            i += 3;
+            continue;
+        }
         return (i, false, true);
     }
+    if events && f(i, i, DOC_END) == 0 {
+        return (i, true, true);
+    }
     return (i, true, false);
 }
 
-// squash an object or array and return the next index after the '{' or '['.
-fn squash(json: &[u8], mut i: usize) -> usize {
+// squash an object or array and return the next index after the matching
+// '}' or ']'. The bool is false when the end of the json was reached before
+// the container was closed, or when the nesting is deeper than 'limit', where
+// the container that is being squashed is at depth one.
+fn squash(json: &[u8], mut i: usize, limit: usize) -> (usize, bool) {
     // opening character has been already parsed
     let mut depth = 1;
     let mut ch: usize = 0;
@@ -322,21 +1190,172 @@ fn squash(json: &[u8], mut i: usize) -> usize {
             }
         } else if CHTABLE[ch] & CHOPEN == CHOPEN {
             depth += 1;
+            if depth > limit {
+                return (i, false);
+            }
         } else if CHTABLE[ch] & CHCLOSE == CHCLOSE {
             depth -= 1;
             if depth == 0 {
-                return i + 1;
+                return (i + 1, true);
             }
         }
         i += 1;
     }
-    return i;
+    return (i, false);
+}
+
+// squash_checked works like squash, but fully validates the children of the
+// object or array while skipping over them. No callbacks are made. The 'open'
+// param is the '{' or '[' character that has been already parsed.
+// The containers are tracked using an explicit stack rather than recursion, so
+// deeply nested input cannot overflow the call stack.
+fn squash_checked(
+    json: &[u8],
+    mut i: usize,
+    open: u8,
+    opts: usize,
+    st: &mut ParserState,
+) -> (usize, bool) {
+    let comma = st.comma;
+    let colon = st.colon;
+    let max_exponent = st.max_exponent;
+    let max_digits = st.max_significand_digits;
+    // the depth allowed below the container that is being squashed
+    let limit = st.max_depth - st.depth;
+    let stack = &mut st.stack;
+    let keys = &mut st.keys;
+    stack.clear();
+    keys.clear();
+    let sorted = opts & SORTED_KEYS == SORTED_KEYS;
+    let mut prev = (0, 0);
+    let mut top = open;
+    let mut first = true;
+    loop {
+        i = skipws(json, i, opts);
+        if i == json.len() {
+            return (i, false);
+        }
+        let mut closed = false;
+        if first && (json[i] == b'}' || json[i] == b']') {
+            if json[i] != top + 2 {
+                // '{' + 2 == '}' and '[' + 2 == ']'
+                return (i, false);
+            }
+            closed = true;
+        } else {
+            if top == b'{' {
+                if json[i] != b'"' {
+                    return (i, false);
+                }
+                let (i_, _, ok, _) = vstring(json, i + 1, opts);
+                if !ok {
+                    return (i_, false);
+                }
+                if sorted {
+                    if !keyorder(json, prev, (i, i_)) {
+                        return (i, false);
+                    }
+                    prev = (i, i_);
+                }
+                if opts & ASCII_KEYS == ASCII_KEYS && !json[i..i_].is_ascii() {
+                    return (i, false);
+                }
+                let (i_, ok, _) = vcolon(json, i_, opts, colon);
+                if !ok {
+                    return (i_, false);
+                }
+                i = i_;
+                i = skipws(json, i, opts);
+                if i == json.len() {
+                    return (i, false);
+                }
+            }
+            let (i_, ok) = match json[i] {
+                b'{' | b'[' => {
+                    if stack.len() + 2 > limit {
+                        return (i, false);
+                    }
+                    stack.push(top);
+                    if sorted {
+                        keys.push(prev);
+                        prev = (0, 0);
+                    }
+                    top = json[i];
+                    first = true;
+                    i += 1;
+                    continue;
+                }
+                b'"' => {
+                    let (i_, _, ok, _) = vstring(json, i + 1, opts);
+                    (i_, ok)
+                }
+                b't' => {
+                    let (i_, ok, _) = vtrue(json, i + 1);
+                    (i_, ok)
+                }
+                b'f' => {
+                    let (i_, ok, _) = vfalse(json, i + 1);
+                    (i_, ok)
+                }
+                b'n' => {
+                    let (i_, ok, _) = vnull(json, i + 1);
+                    (i_, ok)
+                }
+                ch if ch == b'-' || isnum(ch) => {
+                    let (i_, _, ok, _) = vnumber(json, i + 1, opts);
+                    if ok {
+                        vlimits(json, i, i_, max_exponent, max_digits)
+                    } else {
+                        (i_, ok)
+                    }
+                }
+                ch if ch == comma && top == b'[' && opts & EMPTY_SLOTS == EMPTY_SLOTS => (i, true),
+                _ => (i, false),
+            };
+            if !ok {
+                return (i_, false);
+            }
+            i = i_;
+        }
+        // look for the next comma or the closing character
+        loop {
+            if !closed {
+                let (i_, ok, _) = vcomma(json, i, top + 2, opts, comma);
+                if !ok {
+                    return (i_, false);
+                }
+                i = i_;
+                if json[i] == comma {
+                    i += 1;
+                    first = false;
+                    break;
+                }
+            }
+            i += 1;
+            match stack.pop() {
+                Some(ch) => top = ch,
+                None => return (i, true),
+            }
+            if sorted {
+                prev = keys.pop().unwrap_or((0, 0));
+            }
+            closed = false;
+        }
+    }
+}
+
+// keyorder returns true if the key is strictly greater than the previous
+// key of the same object, or when there is no previous key. Both are ranges of
+// Object keys, including the quotes.
+fn keyorder(json: &[u8], prev: (usize, usize), key: (usize, usize)) -> bool {
+    prev.1 == 0 || json[key.0 + 1..key.1 - 1] > json[prev.0 + 1..prev.1 - 1]
 }
 
 fn vany<F>(
     json: &[u8],
     mut i: usize,
     opts: usize,
+    st: &mut ParserState,
     mut dinfo: usize,
     f: &mut F,
     skip: bool,
@@ -349,18 +1368,25 @@ where
             i += 1;
             continue;
         }
+        if opts & JSON5 == JSON5 && isls(json, i) {
+            i += 3;
+            continue;
+        }
         let mark = i;
         let mut info = 0;
         let ok;
         let stop;
         if json[i] == b'"' {
-            let (i_, info_, ok_, stop_) = vstring(json, i + 1);
+            let (i_, info_, ok_, stop_) = vstring(json, i + 1, opts);
             i = i_;
             info = info_;
             ok = ok_;
             stop = stop_;
             info |= STRING;
         } else if json[i] == b'{' {
+            if st.depth >= st.max_depth {
+                return (i, false, true);
+            }
             let mut oskip = skip;
             if !skip {
                 let r = f(i, i + 1, OBJECT | OPEN | dinfo);
@@ -371,10 +1397,20 @@ where
                     oskip = true;
                 }
             }
-            if opts & UNCHECKED == UNCHECKED && oskip {
-                i = squash(json, i + 1);
+            if oskip {
+                let (i_, ok_) = if opts & UNCHECKED == UNCHECKED {
+                    squash(json, i + 1, st.max_depth - st.depth)
+                } else {
+                    squash_checked(json, i + 1, b'{', opts, st)
+                };
+                i = i_;
+                if !ok_ {
+                    return (i, false, true);
+                }
             } else {
-                let (i_, ok_, stop_) = vobject(json, i + 1, opts, f, oskip);
+                st.depth += 1;
+                let (i_, ok_, stop_) = vobject(json, i + 1, opts, st, f, oskip);
+                st.depth -= 1;
                 i = i_;
                 ok = ok_;
                 stop = stop_;
@@ -382,6 +1418,7 @@ where
                     return (i, ok, stop);
                 }
             }
+            debug_assert!(i > mark + 1);
             if !skip {
                 if dinfo & START == START {
                     dinfo = dinfo ^ START; // TODO: IS THIS RIGHT? ¯\_(ツ)_/¯
@@ -393,20 +1430,44 @@ where
             }
             return (i, true, false);
         } else if json[i] == b'[' {
-            let mut oskip = skip;
-            if !skip {
-                let r = f(i, i + 1, ARRAY | OPEN | dinfo);
-                if r == 0 {
+            if st.depth >= st.max_depth {
+                return (i, false, true);
+            }
+            if !skip && opts & PACKED_ARRAYS == PACKED_ARRAYS {
+                if let Some((end, kind)) = vpacked(json, i + 1, opts, st) {
+                    if dinfo & START == START {
+                        dinfo |= END;
+                    }
+                    if f(i, end, ARRAY | kind | dinfo) == 0 {
+                        return (end, true, true);
+                    }
+                    return (end, true, false);
+                }
+            }
+            let mut oskip = skip;
+            if !skip {
+                let r = f(i, i + 1, ARRAY | OPEN | dinfo);
+                if r == 0 {
                     return (i, true, true);
                 }
                 if r == -1 {
                     oskip = true;
                 }
             }
-            if opts & UNCHECKED == UNCHECKED && oskip {
-                i = squash(json, i + 1);
+            if oskip {
+                let (i_, ok_) = if opts & UNCHECKED == UNCHECKED {
+                    squash(json, i + 1, st.max_depth - st.depth)
+                } else {
+                    squash_checked(json, i + 1, b'[', opts, st)
+                };
+                i = i_;
+                if !ok_ {
+                    return (i, false, true);
+                }
             } else {
-                let (i_, ok_, stop_) = varray(json, i + 1, opts, f, oskip);
+                st.depth += 1;
+                let (i_, ok_, stop_) = varray(json, i + 1, opts, st, f, oskip);
+                st.depth -= 1;
                 i = i_;
                 ok = ok_;
                 stop = stop_;
@@ -414,6 +1475,7 @@ where
                     return (i, ok, stop);
                 }
             }
+            debug_assert!(i > mark + 1);
             if !skip {
                 if dinfo & START == START {
                     dinfo = dinfo ^ START; // TODO: IS THIS RIGHT? ¯\_(ツ)_/¯
@@ -425,7 +1487,16 @@ where
             }
             return (i, true, false);
         } else if json[i] == b'-' || isnum(json[i]) {
-            let (i_, info_, ok_, stop_) = vnumber(json, i + 1);
+            let (i_, info_, ok_, stop_) = vnumber(json, i + 1, opts);
+            if ok_ {
+                let (i_, ok_) = vlimits(json, i, i_, st.max_exponent, st.max_significand_digits);
+                if !ok_ {
+                    return (i_, false, true);
+                }
+                if !skip && opts & SPLIT_NUMBERS == SPLIT_NUMBERS {
+                    return vnumber_parts(json, i, i_, dinfo, f);
+                }
+            }
             i = i_;
             info = info_;
             ok = ok_;
@@ -462,31 +1533,81 @@ where
             if f(mark, i, info | dinfo) == 0 {
                 return (i, true, true);
             }
+            if info & ESCAPED == ESCAPED && opts & ESCAPES == ESCAPES {
+                if !vescapes(json, mark, i, f) {
+                    return (i, true, true);
+                }
+            }
         }
         return (i, ok, stop);
     }
     return (i, false, true);
 }
 
-fn vobject<F>(json: &[u8], mut i: usize, opts: usize, f: &mut F, skip: bool) -> (usize, bool, bool)
+// vescapes reports an ESCAPE element for every escape sequence of the valid
+// String at json[start..end], for the ESCAPES option. Returns false when 'iter'
+// stopped the parsing.
+fn vescapes<F>(json: &[u8], start: usize, end: usize, f: &mut F) -> bool
+where
+    F: FnMut(usize, usize, usize) -> i64,
+{
+    let mut i = start + 1;
+    while i < end - 1 {
+        if json[i] != b'\\' {
+            i += 1;
+            continue;
+        }
+        let n = match json[i + 1] {
+            b'u' => 6,
+            b'x' => 4,
+            b'\r' if json[i + 2] == b'\n' => 3,
+            0xE2 => 4,
+            _ => 2,
+        };
+        if f(i, i + n, ESCAPE) == 0 {
+            return false;
+        }
+        i += n;
+    }
+    true
+}
+
+fn vobject<F>(
+    json: &[u8],
+    mut i: usize,
+    opts: usize,
+    st: &mut ParserState,
+    f: &mut F,
+    skip: bool,
+) -> (usize, bool, bool)
 where
     F: FnMut(usize, usize, usize) -> i64,
 {
+    let mut prev = (0, 0);
+    let mut count = 0;
     while i < json.len() {
         if isws(json[i]) {
             i += 1;
             continue;
         }
+        if opts & JSON5 == JSON5 && isls(json, i) {
+            i += 3;
+            continue;
+        }
         if json[i] == b'}' {
             return (i + 1, true, false);
         }
         if json[i] == b'"' {
             'key: loop {
                 let mark = i;
+                count += 1;
+                if count > st.max_members {
+                    return (mark, false, true);
+                }
                 let info;
                 let mut ok;
                 let mut stop;
-                let (i_, info_, ok_, stop_) = vstring(json, i + 1);
+                let (i_, info_, ok_, stop_) = vstring(json, i + 1, opts);
                 i = i_;
                 info = info_;
                 ok = ok_;
@@ -494,12 +1615,26 @@ where
                 if stop {
                     return (i, ok, stop);
                 }
+                if opts & SORTED_KEYS == SORTED_KEYS {
+                    if !keyorder(json, prev, (mark, i)) {
+                        return (mark, false, true);
+                    }
+                    prev = (mark, i);
+                }
+                if opts & ASCII_KEYS == ASCII_KEYS && !json[mark..i].is_ascii() {
+                    return (mark, false, true);
+                }
                 if !skip {
                     if f(mark, i, info | KEY | STRING) == 0 {
                         return (i, true, true);
                     }
+                    if info & ESCAPED == ESCAPED && opts & ESCAPES == ESCAPES {
+                        if !vescapes(json, mark, i, f) {
+                            return (i, true, true);
+                        }
+                    }
                 }
-                let (i_, ok_, stop_) = vcolon(json, i);
+                let (i_, ok_, stop_) = vcolon(json, i, opts, st.colon);
                 i = i_;
                 ok = ok_;
                 stop = stop_;
@@ -511,14 +1646,19 @@ where
                         return (i, true, true);
                     }
                 }
-                let (i_, ok_, stop_) = vany(json, i, opts, VALUE, f, skip);
+                let (i_, ok_, stop_) = vany(json, i, opts, st, VALUE, f, skip);
                 i = i_;
                 ok = ok_;
                 stop = stop_;
                 if stop {
                     return (i, ok, stop);
                 }
-                let (i_, ok_, stop_) = vcomma(json, i, b'}');
+                if !skip && opts & PAIRS == PAIRS {
+                    if f(mark, i, PAIR) == 0 {
+                        return (i, true, true);
+                    }
+                }
+                let (i_, ok_, stop_) = vcomma(json, i, b'}', opts, st.comma);
                 i = i_;
                 ok = ok_;
                 stop = stop_;
@@ -539,6 +1679,10 @@ where
                         i += 1;
                         continue;
                     }
+                    if opts & JSON5 == JSON5 && isls(json, i) {
+                        i += 3;
+                        continue;
+                    }
                     if json[i] == b'"' {
                         continue 'key;
                     }
@@ -552,15 +1696,27 @@ where
     return (i, false, true);
 }
 
-fn varray<F>(json: &[u8], mut i: usize, opts: usize, f: &mut F, skip: bool) -> (usize, bool, bool)
+fn varray<F>(
+    json: &[u8],
+    mut i: usize,
+    opts: usize,
+    st: &mut ParserState,
+    f: &mut F,
+    skip: bool,
+) -> (usize, bool, bool)
 where
     F: FnMut(usize, usize, usize) -> i64,
 {
+    let mut count = 0;
     while i < json.len() {
         if isws(json[i]) {
             i += 1;
             continue;
         }
+        if opts & JSON5 == JSON5 && isls(json, i) {
+            i += 3;
+            continue;
+        }
         if json[i] == b']' {
             return (i + 1, true, false);
         }
@@ -569,16 +1725,37 @@ where
                 i += 1;
                 continue;
             }
+            if opts & JSON5 == JSON5 && isls(json, i) {
+                i += 3;
+                continue;
+            }
             let mut ok;
             let mut stop;
-            let (i_, ok_, stop_) = vany(json, i, opts, VALUE, f, skip);
+            count += 1;
+            if count > st.max_array_len {
+                return (i, false, true);
+            }
+            if json[i] == st.comma && opts & EMPTY_SLOTS == EMPTY_SLOTS {
+                if !skip {
+                    if f(i, i, NULL | VALUE) == 0 || f(i, i + 1, COMMA) == 0 {
+                        return (i, true, true);
+                    }
+                }
+                i += 1;
+                continue;
+            }
+            let (i_, ok_, stop_) = if !skip && opts & RECORDS == RECORDS && json[i] == b'{' {
+                vrecord(json, i, opts, st, f)
+            } else {
+                vany(json, i, opts, st, VALUE, f, skip)
+            };
             i = i_;
             ok = ok_;
             stop = stop_;
             if stop {
                 return (i, ok, stop);
             }
-            let (i_, ok_, stop_) = vcomma(json, i, b']');
+            let (i_, ok_, stop_) = vcomma(json, i, b']', opts, st.comma);
             i = i_;
             ok = ok_;
             stop = stop_;
@@ -599,8 +1776,90 @@ where
     return (i, false, true);
 }
 
-fn vstring(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
+// vrecord validates the Object at json[i] and reports it as a single element,
+// for the RECORDS option.
+fn vrecord<F>(
+    json: &[u8],
+    i: usize,
+    opts: usize,
+    st: &mut ParserState,
+    f: &mut F,
+) -> (usize, bool, bool)
+where
+    F: FnMut(usize, usize, usize) -> i64,
+{
+    if st.depth >= st.max_depth {
+        return (i, false, true);
+    }
+    let (end, ok) = squash_checked(json, i + 1, b'{', opts, st);
+    if !ok {
+        return (end, false, true);
+    }
+    if f(i, end, OBJECT | VALUE) == 0 {
+        return (end, true, true);
+    }
+    (end, true, false)
+}
+
+// vpacked looks ahead at the Array that starts at json[i], right after the
+// '[', for the PACKED_ARRAYS option. When all of its values are valid Numbers,
+// or all are valid Strings, it returns the index after the ']' and the NUMBER
+// or STRING bit. Otherwise None is returned, also for an error, so that the
+// Array is parsed as usual, which reports the error.
+fn vpacked(json: &[u8], mut i: usize, opts: usize, st: &ParserState) -> Option<(usize, usize)> {
+    let mut kind = 0;
+    let mut count = 0;
+    loop {
+        i = skipws(json, i, opts);
+        if i == json.len() {
+            return None;
+        }
+        let mark = i;
+        let k = if json[i] == b'"' {
+            STRING
+        } else if json[i] == b'-' || isnum(json[i]) {
+            NUMBER
+        } else {
+            return None;
+        };
+        if kind != 0 && k != kind {
+            return None;
+        }
+        kind = k;
+        count += 1;
+        if count > st.max_array_len {
+            return None;
+        }
+        let (i_, _, ok, _) = if k == STRING {
+            vstring(json, i + 1, opts)
+        } else {
+            vnumber(json, i + 1, opts)
+        };
+        if !ok {
+            return None;
+        }
+        i = i_;
+        if k == NUMBER && !vlimits(json, mark, i, st.max_exponent, st.max_significand_digits).1 {
+            return None;
+        }
+        i = skipws(json, i, opts);
+        if i == json.len() {
+            return None;
+        }
+        if json[i] == b']' {
+            return Some((i + 1, kind));
+        }
+        if json[i] != st.comma {
+            return None;
+        }
+        i += 1;
+    }
+}
+
+fn vstring(json: &[u8], mut i: usize, opts: usize) -> (usize, usize, bool, bool) {
+    let mark = i;
     let mut info: usize = 0;
+    let ascii = opts & ASCII_ONLY == ASCII_ONLY;
     'outer: loop {
         let mut ch = 0;
         'tok: loop {
@@ -620,7 +1879,8 @@ fn vstring(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
                         _mm_movemask_epi8(_mm_cmpeq_epi8(block, _mm_set1_epi8(b'"' as i8)));
                     let slash_mask =
                         _mm_movemask_epi8(_mm_cmpeq_epi8(block, _mm_set1_epi8(b'\\' as i8)));
-                    let mask = ctrl_mask | quote_mask | slash_mask;
+                    let high_mask = if ascii { _mm_movemask_epi8(block) } else { 0 };
+                    let mask = ctrl_mask | quote_mask | slash_mask | high_mask;
                     if mask == 0 {
                         i += 16;
                         continue;
@@ -635,7 +1895,7 @@ fn vstring(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
                     // SAFETY: the call is made safe because the bounds were
                     // checked in the parent while loop condition.
                     ch = unsafe { *json.get_unchecked(i) };
-                    if isstrtok(ch) {
+                    if isstrtok(ch) || (ascii && ch >= 0x80) {
                         break 'tok;
                     }
                     i += 1;
@@ -643,7 +1903,7 @@ fn vstring(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
             }
             while i < json.len() {
                 ch = json[i];
-                if isstrtok(ch) {
+                if isstrtok(ch) || (ascii && ch >= 0x80) {
                     break 'tok;
                 }
                 i += 1;
@@ -651,10 +1911,18 @@ fn vstring(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
             break 'outer;
         }
         if ch == b'"' {
+            if opts & REQUIRE_ESCAPED_SOLIDUS == REQUIRE_ESCAPED_SOLIDUS {
+                if let Some(j) = bare_solidus(json, mark, i) {
+                    return (j, info, false, true);
+                }
+            }
             return (i + 1, info, true, false);
         }
-        if ch < b' ' {
-            return (i, info, false, true);
+        if !(b' '..0x80).contains(&ch) {
+            let raw_ws = matches!(ch, b'\t' | b'\n' | b'\r');
+            if !raw_ws || opts & RAW_WHITESPACE != RAW_WHITESPACE {
+                return (i, info, false, true);
+            }
         }
         if ch == b'\\' {
             info |= ESCAPED;
@@ -663,6 +1931,9 @@ fn vstring(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
                 return (i, info, false, true);
             }
             match json[i] {
+                b'/' if opts & FORBID_ESCAPED_SOLIDUS == FORBID_ESCAPED_SOLIDUS => {
+                    return (i - 1, info, false, true);
+                }
                 b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => {}
                 b'u' => {
                     for _ in 0..4 {
@@ -670,13 +1941,43 @@ fn vstring(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
                         if i == json.len() {
                             return (i, info, false, true);
                         }
-                        if !((json[i] >= b'0' && json[i] <= b'9')
-                            || (json[i] >= b'a' && json[i] <= b'f')
-                            || (json[i] >= b'A' && json[i] <= b'F'))
-                        {
+                        if !ishex(json[i]) {
+                            return (i, info, false, true);
+                        }
+                    }
+                    if opts & NO_NUL == NO_NUL && &json[i - 3..=i] == b"0000" {
+                        return (i - 5, info, false, true);
+                    }
+                }
+                b'\n' if opts & JSON5 == JSON5 => {}
+                b'\r' if opts & JSON5 == JSON5 => {
+                    if i + 1 < json.len() && json[i + 1] == b'\n' {
+                        i += 1;
+                    }
+                }
+                0xE2 if opts & JSON5 == JSON5 => {
+                    // U+2028 and U+2029 line separators
+                    if i + 2 >= json.len()
+                        || json[i + 1] != 0x80
+                        || (json[i + 2] != 0xA8 && json[i + 2] != 0xA9)
+                    {
+                        return (i, info, false, true);
+                    }
+                    i += 2;
+                }
+                b'x' if opts & JSON5 == JSON5 => {
+                    for _ in 0..2 {
+                        i += 1;
+                        if i == json.len() {
+                            return (i, info, false, true);
+                        }
+                        if !ishex(json[i]) {
                             return (i, info, false, true);
                         }
                     }
+                    if opts & NO_NUL == NO_NUL && &json[i - 1..=i] == b"00" {
+                        return (i - 3, info, false, true);
+                    }
                 }
                 _ => {
                     return (i, info, false, true);
@@ -688,12 +1989,28 @@ fn vstring(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
     return (i, info, false, true);
 }
 
-fn vnumber(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
+// bare_solidus returns the index of the first '/' in json[i..end] that is not
+// part of an escape, for the REQUIRE_ESCAPED_SOLIDUS option.
+fn bare_solidus(json: &[u8], mut i: usize, end: usize) -> Option<usize> {
+    while i < end {
+        match json[i] {
+            b'\\' => i += 2,
+            b'/' => return Some(i),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn vnumber(json: &[u8], mut i: usize, opts: usize) -> (usize, usize, bool, bool) {
     let mut info: usize = 0;
 
     i -= 1; // go back one byte
 
     if json[i] == b'-' {
+        if opts & FORBID_NEGATIVE == FORBID_NEGATIVE {
+            return (i, info, false, true);
+        }
         info |= SIGN;
         i += 1;
         if i == json.len() || !isnum(json[i]) {
@@ -705,11 +2022,9 @@ fn vnumber(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
         if json[i] == b'0' {
             i += 1;
         } else {
-            while i < json.len() {
-                if !isnum(json[i]) {
-                    break 'significand;
-                }
-                i += 1;
+            i = scan_digits(json, i + 1);
+            if i < json.len() {
+                break 'significand;
             }
         }
         if i == json.len() {
@@ -720,6 +2035,9 @@ fn vnumber(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
 
     'base: loop {
         if json[i] == b'.' {
+            if opts & FORBID_FRACTIONS == FORBID_FRACTIONS {
+                return (i, info, false, true);
+            }
             info |= DOT;
             i += 1;
             if i == json.len() {
@@ -728,22 +2046,9 @@ fn vnumber(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
             if !isnum(json[i]) {
                 return (i, info, false, true);
             }
-            i += 1;
-            while i + 4 < json.len() {
-                for _ in 0..4 {
-                    // SAFETY: the call is made safe because the bounds were
-                    // checked in the parent while loop condition.
-                    if !isnum(unsafe { *json.get_unchecked(i) }) {
-                        break 'base;
-                    }
-                    i += 1;
-                }
-            }
-            while i < json.len() {
-                if !isnum(json[i]) {
-                    break 'base;
-                }
-                i += 1;
+            i = scan_digits(json, i + 1);
+            if i < json.len() {
+                break 'base;
             }
         }
         if i == json.len() {
@@ -754,6 +2059,9 @@ fn vnumber(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
 
     // 'exponent: loop {
     if json[i] == b'e' || json[i] == b'E' {
+        if opts & FORBID_EXPONENTS == FORBID_EXPONENTS {
+            return (i, info, false, true);
+        }
         info |= E;
         i += 1;
         if i == json.len() {
@@ -768,18 +2076,155 @@ fn vnumber(json: &[u8], mut i: usize) -> (usize, usize, bool, bool) {
         if !isnum(json[i]) {
             return (i, info, false, true);
         }
-        i += 1;
-        while i < json.len() {
-            if !isnum(json[i]) {
-                break;
+        if json[i] == b'0' && opts & STRICT_EXPONENTS == STRICT_EXPONENTS {
+            if i + 1 < json.len() && isnum(json[i + 1]) {
+                return (i + 1, info, false, true);
             }
-            i += 1;
         }
+        i = scan_digits(json, i + 1);
     }
     return (i, info, true, false);
     // }
 }
 
+// vnumber_parts reports the parts of the valid Number at json[start..end] as
+// separate elements, for the SPLIT_NUMBERS option.
+fn vnumber_parts<F>(
+    json: &[u8],
+    start: usize,
+    end: usize,
+    dinfo: usize,
+    f: &mut F,
+) -> (usize, bool, bool)
+where
+    F: FnMut(usize, usize, usize) -> i64,
+{
+    let mut parts = [(0, 0, 0); 7];
+    let mut n = 0;
+    let mut i = start;
+    if json[i] == b'-' {
+        parts[n] = (i, i + 1, SIGN);
+        n += 1;
+        i += 1;
+    }
+    let mark = i;
+    i = scan_digits(json, i).min(end);
+    parts[n] = (mark, i, INTEGER);
+    n += 1;
+    if i < end && json[i] == b'.' {
+        parts[n] = (i, i + 1, DOT);
+        n += 1;
+        i += 1;
+        let mark = i;
+        i = scan_digits(json, i).min(end);
+        parts[n] = (mark, i, FRACTION);
+        n += 1;
+    }
+    if i < end {
+        // 'e' or 'E'
+        parts[n] = (i, i + 1, E);
+        n += 1;
+        i += 1;
+        if json[i] == b'+' || json[i] == b'-' {
+            parts[n] = (i, i + 1, EXPONENT | SIGN);
+            n += 1;
+            i += 1;
+        }
+        parts[n] = (i, end, EXPONENT);
+        n += 1;
+    }
+    for (k, &(s, e, part)) in parts[..n].iter().enumerate() {
+        let mut info = NUMBER | part | (dinfo & !START);
+        if dinfo & START == START {
+            if k == 0 {
+                info |= START;
+            }
+            if k == n - 1 {
+                info |= END;
+            }
+        }
+        if f(s, e, info) == 0 {
+            return (e, true, true);
+        }
+    }
+    (end, true, false)
+}
+
+// vlimits checks a valid Number at json[start..end] against the limits on
+// the size of its exponent and significand. On failure, the index of the
+// offending digit is returned.
+fn vlimits(
+    json: &[u8],
+    start: usize,
+    end: usize,
+    max_exponent: u64,
+    max_digits: usize,
+) -> (usize, bool) {
+    if max_exponent == u64::MAX && max_digits == usize::MAX {
+        return (end, true);
+    }
+    let mut i = start;
+    let mut digits = 0;
+    while i < end {
+        match json[i] {
+            b'e' | b'E' => break,
+            b'0'..=b'9' => {
+                digits += 1;
+                if digits > max_digits {
+                    return (i, false);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    let mut exp: u64 = 0;
+    while i < end {
+        if isnum(json[i]) {
+            exp = exp
+                .saturating_mul(10)
+                .saturating_add((json[i] - b'0') as u64);
+            if exp > max_exponent {
+                return (i, false);
+            }
+        }
+        i += 1;
+    }
+    (end, true)
+}
+
+// scan_digits returns the index of the first byte at or after i that is not
+// a digit, or the length of the json.
+fn scan_digits(json: &[u8], mut i: usize) -> usize {
+    #[cfg(all(any(target_arch = "x86_64"), target_feature = "sse2"))]
+    // SAFETY: the call is made safe because the bounds are
+    // checked in the following while loop condition.
+    unsafe {
+        use std::arch::x86_64::*;
+        while i + 16 <= json.len() {
+            let ptr = json.get_unchecked(i) as *const _ as *const __m128i;
+            let block = _mm_loadu_si128(ptr);
+            // bytes >= 0x80 are negative and are never digits
+            let digit_mask = _mm_movemask_epi8(_mm_and_si128(
+                _mm_cmpgt_epi8(block, _mm_set1_epi8(b'0' as i8 - 1)),
+                _mm_cmplt_epi8(block, _mm_set1_epi8(b'9' as i8 + 1)),
+            ));
+            if digit_mask == 0xFFFF {
+                i += 16;
+                continue;
+            }
+            return i + (!digit_mask).trailing_zeros() as usize;
+        }
+    }
+    while i < json.len() {
+        if !isnum(json[i]) {
+            break;
+        }
+        i += 1;
+    }
+    i
+}
+
 fn vtrue(json: &[u8], i: usize) -> (usize, bool, bool) {
     if i + 3 <= json.len() {
         if json[i] == b'r' && json[i + 1] == b'u' && json[i + 2] == b'e' {
@@ -807,36 +2252,48 @@ fn vfalse(json: &[u8], i: usize) -> (usize, bool, bool) {
     return (i, false, true);
 }
 
-fn vcolon(json: &[u8], mut i: usize) -> (usize, bool, bool) {
+fn vcolon(json: &[u8], mut i: usize, opts: usize, colon: u8) -> (usize, bool, bool) {
     while i < json.len() {
-        if json[i] == b':' {
+        if json[i] == colon {
             return (i + 1, true, false);
         }
-        if !isws(json[i]) {
+        if isws(json[i]) {
+            i += 1;
+        } else if opts & JSON5 == JSON5 && isls(json, i) {
+            i += 3;
+        } else {
             break;
         }
-        i += 1;
     }
     return (i, false, true);
 }
 
-fn vcomma(json: &[u8], mut i: usize, end: u8) -> (usize, bool, bool) {
+fn vcomma(json: &[u8], mut i: usize, end: u8, opts: usize, comma: u8) -> (usize, bool, bool) {
     while i < json.len() {
-        if json[i] == b',' {
+        if json[i] == comma {
             return (i, true, false);
         }
         if json[i] == end {
             return (i, true, false);
         }
-        if !isws(json[i]) {
+        if isws(json[i]) {
+            i += 1;
+        } else if opts & JSON5 == JSON5 && isls(json, i) {
+            i += 3;
+        } else {
             break;
         }
-        i += 1;
     }
     return (i, false, true);
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::iter_cloned_collect,
+    clippy::useless_conversion,
+    clippy::needless_borrow,
+    clippy::comparison_to_empty
+)]
 mod tests {
     use crate::*;
     use std::fs;
@@ -1142,51 +2599,1408 @@ mod tests {
         }
     }
     #[test]
-    fn simples() {
-        assert_eq!(parse_simple(br#" 10 "#), NUMBER | START | END);
-        assert_eq!(parse_simple(br#" 10.0 "#), NUMBER | DOT | START | END);
-        assert_eq!(
-            parse_simple(br#" -0.0 "#),
-            NUMBER | SIGN | DOT | START | END
-        );
-        assert_eq!(
-            parse_simple(br#" -1230.1230e10 "#),
-            NUMBER | SIGN | DOT | E | START | END
-        );
-        assert_eq!(
-            parse_simple(br#" -1230.1230e-10 "#),
-            NUMBER | SIGN | DOT | E | START | END
-        );
-        assert_eq!(
-            parse_simple(br#" 1230.1230e+10 "#),
-            NUMBER | DOT | E | START | END
-        );
-        assert_eq!(
-            parse_simple(br#" -1230e10 "#),
-            NUMBER | SIGN | E | START | END
-        );
-        assert_eq!(parse_simple(br#" 1230e10 "#), NUMBER | E | START | END);
-        assert_eq!(parse_simple(br#" "" "#), STRING | START | END);
-        assert_eq!(parse_simple(br#" "hello" "#), STRING | START | END);
-        assert_eq!(
-            parse_simple(br#" "hell\no" "#),
-            STRING | ESCAPED | START | END
-        );
-        assert_eq!(
-            parse_simple(br#" "hell\no" "#),
-            STRING | ESCAPED | START | END
+    fn values_only() {
+        let json = br#" { "a" : "b" , "c" : [ 1 , 2 , 3 ] } "#;
+        let mut out = String::new();
+        let ret = parse_values(json, 0, |start: usize, end: usize, _: usize| -> i64 {
+            out.push_str(&frag(json, start, end));
+            1
+        });
+        assert_eq!(ret as usize, json.len());
+        assert_eq!(out, r#"{"b"[123]}"#);
+        out.clear();
+        parse_values(
+            json,
+            INCLUDE_KEYS,
+            |start: usize, end: usize, _: usize| -> i64 {
+                out.push_str(&frag(json, start, end));
+                1
+            },
         );
-        assert_eq!(parse_simple(br#" true "#), TRUE | START | END);
-        assert_eq!(parse_simple(br#" false "#), FALSE | START | END);
-        assert_eq!(parse_simple(br#" null "#), NULL | START | END);
+        assert_eq!(out, r#"{"a""b""c"[123]}"#);
+        // separators are still validated
+        let ret = parse_values(br#"[1 2]"#, 0, |_: usize, _: usize, _: usize| -> i64 { 1 });
+        assert!(ret <= 0);
     }
 
-    fn parse_simple(json: &[u8]) -> usize {
-        let mut oinfo: usize = 0;
-        let ret = parse(json, 0, |_: usize, _: usize, info: usize| -> i64 {
-            oinfo = info;
-            -1
-        });
+    #[test]
+    fn unchecked_truncated() {
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        assert_eq!(parse(br#"[1,2"#, UNCHECKED, skip), -4);
+        assert_eq!(parse(br#"{"a":[1,2]"#, UNCHECKED, skip), -10);
+        assert_eq!(parse(br#"{"a":"b"#, UNCHECKED, skip), -7);
+        assert_eq!(parse(br#"["#, UNCHECKED, skip), -1);
+        assert_eq!(parse(br#"[1,[2]]"#, UNCHECKED, skip), 7);
+        assert_eq!(parse(br#"{"a":"]"}"#, UNCHECKED, skip), 9);
+    }
+
+    #[test]
+    fn skip_checked() {
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        let jsons: &[&[u8]] = &[
+            br#"{}"#,
+            br#"[]"#,
+            br#" { "a" : [ 1 , { } , [ ] , "x\"y" , true , false , null , -1.5e3 ] } "#,
+            br#"[[[[[[{"a":{"b":[{}]}}]]]]]]"#,
+            br#"{"a":1,}"#,
+            br#"{"a":1 "b":2}"#,
+            br#"{"a" 1}"#,
+            br#"{1:1}"#,
+            br#"[1,]"#,
+            br#"[1 2]"#,
+            br#"[1,2}"#,
+            br#"{"a":[1}]"#,
+            br#"["a\q"]"#,
+            br#"[tru]"#,
+            br#"[01]"#,
+            br#"[[[[[[{"a":{"b":[{}]}}]]]]]"#,
+            br#"["#,
+            br#"{"a":"#,
+        ];
+        for json in jsons {
+            assert_eq!(parse(json, 0, skip), parse(json, 0, each));
+        }
+        let mut deep = vec![b'['; 100000];
+        deep.append(&mut vec![b']'; 100000]);
+        assert_eq!(parse(&deep, 0, skip), deep.len() as i64);
+        deep.pop();
+        assert_eq!(parse(&deep, 0, skip), -(deep.len() as i64));
+    }
+
+    #[test]
+    fn dyn_iter() {
+        let json = br#"[1,{"a":2}]"#;
+        let mut count = 0;
+        type Iter<'a> = Box<dyn FnMut(usize, usize, usize) -> i64 + 'a>;
+        let mut iters: Vec<Iter> = vec![
+            Box::new(|_: usize, _: usize, _: usize| -> i64 { 1 }),
+            Box::new(|_: usize, _: usize, info: usize| -> i64 {
+                if info & OPEN == OPEN {
+                    return -1;
+                }
+                1
+            }),
+            Box::new(|_: usize, _: usize, _: usize| -> i64 {
+                count += 1;
+                1
+            }),
+        ];
+        for iter in iters.iter_mut() {
+            assert_eq!(parse_dyn(json, 0, iter.as_mut()), json.len() as i64);
+        }
+        drop(iters);
+        assert_eq!(count, 9);
+    }
+
+    #[test]
+    fn visitor() {
+        struct Stats {
+            values: usize,
+            depth: usize,
+            max_depth: usize,
+        }
+        impl Visitor for Stats {
+            fn visit(&mut self, _: usize, _: usize, info: usize) -> i64 {
+                if info & OPEN == OPEN {
+                    self.depth += 1;
+                    self.max_depth = self.max_depth.max(self.depth);
+                } else if info & CLOSE == CLOSE {
+                    self.depth -= 1;
+                } else if info & VALUE == VALUE {
+                    self.values += 1;
+                }
+                1
+            }
+        }
+        let json = br#"[1,{"a":[2,3]},"b"]"#;
+        let mut stats = Stats {
+            values: 0,
+            depth: 0,
+            max_depth: 0,
+        };
+        assert_eq!(parse_visitor(json, 0, &mut stats), json.len() as i64);
+        assert_eq!((stats.values, stats.depth, stats.max_depth), (4, 0, 3));
+        let mut stats = Stats {
+            values: 0,
+            depth: 0,
+            max_depth: 0,
+        };
+        assert_eq!(parse_visitor(br#"[1,2"#, 0, &mut stats), -4);
+    }
+
+    #[test]
+    fn find() {
+        fn find_el(json: &[u8], el: &[u8]) -> Option<usize> {
+            parse_find(json, 0, |start: usize, end: usize, _: usize| -> i64 {
+                if &json[start..end] == el {
+                    return 0;
+                }
+                1
+            })
+        }
+        let json = br#" { "a" : [ 1 , 2 , 3 ] } "#;
+        assert_eq!(find_el(json, b"2"), Some(16));
+        assert_eq!(find_el(json, b"4"), None);
+        assert_eq!(find_el(json, b"}"), Some(24));
+        assert_eq!(find_el(br#"[1,2,"#, b"4"), None);
+        assert_eq!(find_el(br#"[1,2,"#, b"2"), Some(4));
+    }
+
+    #[test]
+    fn json5_hex_escapes() {
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        assert_eq!(parse(br#""\xFF""#, 0, each), -2);
+        assert_eq!(parse(br#""\xFF""#, JSON5, each), 6);
+        assert_eq!(parse(br#"{"\x41":"\xe9"}"#, JSON5, each), 15);
+        assert_eq!(parse(br#"["\x4"]"#, JSON5, each), -5);
+        assert_eq!(parse(br#"["\x4g"]"#, JSON5, each), -5);
+        assert_eq!(parse(br#""\x"#, JSON5, each), -3);
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        assert_eq!(parse(br#"["\x41"]"#, 0, skip), -3);
+        assert_eq!(parse(br#"["\x41"]"#, JSON5, skip), 8);
+        assert_eq!(
+            parse_simple_opts(br#""\x41""#, JSON5),
+            STRING | ESCAPED | START | END
+        );
+    }
+
+    #[test]
+    fn json5_line_continuations() {
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        let jsons: &[&[u8]] = &[
+            b"\"a\\\nb\"",
+            b"\"a\\\rb\"",
+            b"\"a\\\r\nb\"",
+            b"\"a\\\xE2\x80\xA8b\"",
+            b"\"a\\\xE2\x80\xA9b\"",
+        ];
+        for json in jsons {
+            assert_eq!(parse(json, 0, each), -3);
+            assert_eq!(parse(json, JSON5, each), json.len() as i64);
+            assert_eq!(
+                parse_simple_opts(json, JSON5),
+                STRING | ESCAPED | START | END
+            );
+            let mut arr = b"[".to_vec();
+            arr.extend_from_slice(json);
+            arr.push(b']');
+            assert_eq!(parse(&arr, JSON5, skip), arr.len() as i64);
+        }
+        assert_eq!(parse(b"\"a\\\n\nb\"", JSON5, each), -4);
+        assert_eq!(parse(b"\"a\\\xE2\x80\xAAb\"", JSON5, each), -3);
+        assert_eq!(parse(b"\"a\\\xE2\x80", JSON5, each), -3);
+    }
+
+    #[test]
+    fn ascii_only() {
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        let json = "[\"hello\", \"caf\u{e9}\"]".as_bytes();
+        assert_eq!(parse(json, 0, each), json.len() as i64);
+        assert_eq!(parse(json, ASCII_ONLY, each), -14);
+        assert_eq!(parse(json, ASCII_ONLY, skip), -14);
+        let json = "{\"a long string value that is scanned in blocks \u{1F600}\":1}".as_bytes();
+        assert_eq!(parse(json, 0, each), json.len() as i64);
+        assert_eq!(parse(json, ASCII_ONLY, each), -48);
+        let json = br#"{"escaped \u00e9 is fine":"ok"}"#;
+        assert_eq!(parse(json, ASCII_ONLY, each), json.len() as i64);
+    }
+
+    #[test]
+    fn reuse_state() {
+        let mut st = ParserState::new();
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        let json = br#"[[[[[[{"a":{"b":[{}]}}]]]]]]"#;
+        assert_eq!(st.parse_reuse(json, 0, skip), json.len() as i64);
+        let cap = st.stack.capacity();
+        assert!(cap > 0);
+        for _ in 0..10 {
+            assert_eq!(st.parse_reuse(json, 0, skip), json.len() as i64);
+            assert_eq!(st.parse_reuse(br#"[[1,]]"#, 0, skip), -4);
+            assert_eq!(st.stack.capacity(), cap);
+        }
+        let mut a = String::new();
+        let mut b = String::new();
+        let json = br#" { "a" : [ 1 , { "b" : null } ] } "#;
+        let ra = parse(json, 0, |start: usize, end: usize, _: usize| -> i64 {
+            a.push_str(&frag(json, start, end));
+            1
+        });
+        let rb = st.parse_reuse(json, 0, |start: usize, end: usize, _: usize| -> i64 {
+            b.push_str(&frag(json, start, end));
+            1
+        });
+        assert_eq!((ra, a), (rb, b));
+    }
+
+    #[test]
+    fn pairs() {
+        let json = br#" { "a" : "b" , "c" : [ 1 , { "d" : 2 } ] , "e":{} } "#;
+        let mut pairs = Vec::new();
+        let ret = parse(
+            json,
+            PAIRS,
+            |start: usize, end: usize, info: usize| -> i64 {
+                if info & PAIR == PAIR {
+                    assert_eq!(info, PAIR);
+                    pairs.push(frag(json, start, end));
+                }
+                1
+            },
+        );
+        assert_eq!(ret, json.len() as i64);
+        assert_eq!(
+            pairs,
+            [
+                r#""a" : "b""#,
+                r#""d" : 2"#,
+                r#""c" : [ 1 , { "d" : 2 } ]"#,
+                r#""e":{}"#
+            ]
+        );
+        pairs.clear();
+        parse(
+            json,
+            PAIRS,
+            |start: usize, end: usize, info: usize| -> i64 {
+                if info & PAIR == PAIR {
+                    pairs.push(frag(json, start, end));
+                    return 0;
+                }
+                if info & OPEN == OPEN && info & START == 0 {
+                    return -1;
+                }
+                1
+            },
+        );
+        assert_eq!(pairs, [r#""a" : "b""#]);
+        parse(json, 0, |_: usize, _: usize, info: usize| -> i64 {
+            assert_eq!(info & PAIR, 0);
+            1
+        });
+    }
+
+    #[test]
+    fn sorted_keys() {
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        let jsons: &[(&[u8], i64)] = &[
+            (br#"{}"#, 2),
+            (br#"{"a":1,"b":2,"c":3}"#, 19),
+            (br#"{"a":1,"ab":2,"b":3}"#, 20),
+            (br#"{"":1,"a":2}"#, 12),
+            (br#"{"b":{"z":1,"y":2},"a":3}"#, -12),
+            (br#"{"b":1,"a":2}"#, -7),
+            (br#"{"a":1,"a":2}"#, -7),
+            (
+                br#"[{"b":1},{"a":1,"c":{"b":2,"d":[{"x":0,"y":0}]},"d":{}}]"#,
+                56,
+            ),
+            (
+                br#"[{"b":1},{"a":1,"c":{"b":2,"d":[{"x":0,"y":0}]},"b":{}}]"#,
+                -48,
+            ),
+            (br#"{"a!":1,"a":2}"#, -8),
+        ];
+        for (json, ret) in jsons {
+            assert!(parse(json, 0, each) > 0);
+            assert_eq!(parse(json, SORTED_KEYS, each), *ret);
+            assert_eq!(parse(json, SORTED_KEYS, skip), *ret);
+        }
+    }
+
+    #[test]
+    fn config_header() {
+        fn root(json: &[u8], opts: usize) -> (i64, String) {
+            let mut out = String::new();
+            let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+                if info & START == START {
+                    out = frag(json, start, end);
+                }
+                return -1;
+            });
+            return (ret, out);
+        }
+        let json = b"\xEF\xBB\xBF#!/usr/bin/env app\n{\"a\":1}\n";
+        assert_eq!(root(json, CONFIG), (json.len() as i64, "{".to_string()));
+        assert_eq!(root(json, 0).0, 0);
+        assert_eq!(root(b"# header\r\n[1]", CONFIG), (13, "[".to_string()));
+        assert_eq!(root(b"\xEF\xBB\xBF true", CONFIG), (8, "true".to_string()));
+        // only the first line may be a comment
+        assert_eq!(root(b"#a\n#b\n1", CONFIG).0, -3);
+        assert_eq!(root(b"#a", CONFIG).0, -2);
+    }
+
+    #[test]
+    fn empty_slots() {
+        let json = b"[1,,2, ,[,3]]";
+        let mut out = Vec::new();
+        let ret = parse(
+            json,
+            EMPTY_SLOTS,
+            |start: usize, end: usize, info: usize| -> i64 {
+                if info & VALUE == VALUE && info & (OPEN | CLOSE) == 0 {
+                    out.push((start, frag(json, start, end), info & NULL == NULL));
+                }
+                1
+            },
+        );
+        assert_eq!(ret, json.len() as i64);
+        let slot = |i: usize| (i, String::new(), true);
+        assert_eq!(
+            out,
+            [
+                (1, "1".to_string(), false),
+                slot(3),
+                (4, "2".to_string(), false),
+                slot(7),
+                slot(9),
+                (10, "3".to_string(), false),
+            ]
+        );
+        assert_eq!(parse(json, 0, |_, _, _| 1), -3);
+        assert_eq!(parse(b"[1,]", EMPTY_SLOTS, |_, _, _| 1), -3);
+        assert_eq!(parse(b"[,]", EMPTY_SLOTS, |_, _, _| 1), -2);
+        assert_eq!(parse(br#"{"a":,}"#, EMPTY_SLOTS, |_, _, _| 1), -5);
+        // skipped containers
+        let json = b"[[1,,2],[,3]]";
+        assert_eq!(parse(json, EMPTY_SLOTS, |_, _, _| -1), json.len() as i64);
+        let ret = parse(json, EMPTY_SLOTS, |_, _, info| {
+            if info & START == START {
+                1
+            } else {
+                -1
+            }
+        });
+        assert_eq!(ret, json.len() as i64);
+        assert_eq!(parse(b"[[,]]", EMPTY_SLOTS, |_, _, _| -1), -3);
+    }
+
+    #[test]
+    fn progress() {
+        let json = br#"[1, 2, "three", [4, 5], {"six": 6}]"#;
+        let mut steps = Vec::new();
+        let mut count = 0;
+        let ret = parse_with_progress(
+            json,
+            0,
+            10,
+            |_, _, _| -> i64 {
+                count += 1;
+                1
+            },
+            |n: usize| steps.push(n),
+        );
+        assert_eq!(ret, json.len() as i64);
+        assert_eq!(count, 19);
+        assert_eq!(steps, [14, 25, 35]);
+        let mut steps = Vec::new();
+        let ret = parse_with_progress(json, 0, 10, |_, _, _| -1, |n| steps.push(n));
+        assert_eq!(ret, json.len() as i64);
+        assert_eq!(steps, [json.len()]);
+        let mut steps = Vec::new();
+        let ret = parse_with_progress(b"[1,2,3", 0, 1, |_, _, _| 1, |n| steps.push(n));
+        assert_eq!(ret, -6);
+        assert_eq!(steps, [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn empty_input() {
+        for json in [&b""[..], b"   ", b"\n\t "].iter() {
+            let eof = ParseError::new(ErrorKind::UnexpectedEof, json.len());
+            assert_eq!(parse(json, 0, |_, _, _| 1), -(json.len() as i64));
+            assert_eq!(parse_status(json, 0, |_, _, _| 1), ParseStatus::Error(eof));
+            assert_eq!(parse_to_vec(json, 0), Err(eof));
+        }
+        // a stop at offset zero is not an error
+        assert_eq!(parse(b"[1]", 0, |_, _, _| 0), 0);
+        assert_eq!(
+            parse_status(b"[1]", 0, |_, _, _| 0),
+            ParseStatus::Stopped(0)
+        );
+        assert_eq!(
+            parse_status(b" [1] ", 0, |_, _, _| 1),
+            ParseStatus::Complete(5)
+        );
+        assert_eq!(
+            parse_status(b"[1]]", 0, |_, _, _| 1),
+            ParseStatus::Error(ParseError::new(ErrorKind::Syntax, 3))
+        );
+    }
+
+    #[test]
+    fn separators() {
+        let options = Options {
+            comma: b';',
+            colon: b'=',
+            ..Options::default()
+        };
+        let json = br#"{"a" = [1; 2 ;3]; "b"={}}"#;
+        let mut out = String::new();
+        let ret = parse_with(
+            json,
+            &options,
+            |start: usize, end: usize, _: usize| -> i64 {
+                out.push_str(&frag(json, start, end));
+                1
+            },
+        );
+        assert_eq!(ret, json.len() as i64);
+        assert_eq!(out, r#"{"a"=[1;2;3];"b"={}}"#);
+        assert!(parse(json, 0, |_, _, _| 1) <= 0);
+        assert_eq!(parse_with(br#"[1,2]"#, &options, |_, _, _| 1), -2);
+        // skipped containers
+        assert_eq!(parse_with(json, &options, |_, _, _| -1), json.len() as i64);
+        let options = Options {
+            flags: UNCHECKED,
+            ..options
+        };
+        assert_eq!(parse_with(json, &options, |_, _, _| -1), json.len() as i64);
+        // the defaults are standard json
+        let json = br#"{"a":[1,2]}"#;
+        assert_eq!(
+            parse_with(json, &Options::default(), |_, _, _| 1),
+            json.len() as i64
+        );
+        // a state goes back to the defaults with parse_reuse
+        let mut st = ParserState::new();
+        assert_eq!(st.parse_with(b"[1;2]", &options, |_, _, _| 1), 5);
+        assert_eq!(st.parse_reuse(b"[1;2]", 0, |_, _, _| 1), -2);
+    }
+
+    #[test]
+    fn strict_exponents() {
+        for json in [&b"10E-0123"[..], b"1e01", b"1.5E+00", b"[1e00]"].iter() {
+            assert_eq!(parse(json, 0, |_, _, _| 1), json.len() as i64);
+        }
+        assert_eq!(parse(b"10E-0123", STRICT_EXPONENTS, |_, _, _| 1), -5);
+        assert_eq!(parse(b"1e01", STRICT_EXPONENTS, |_, _, _| 1), -3);
+        assert_eq!(parse(b"[1e00]", STRICT_EXPONENTS, |_, _, _| -1), -4);
+        for json in [&b"1e0"[..], b"1e10", b"1.5E+0", b"[1e-100]", b"0e0"].iter() {
+            assert_eq!(
+                parse(json, STRICT_EXPONENTS, |_, _, _| 1),
+                json.len() as i64
+            );
+        }
+    }
+
+    #[test]
+    fn within_depth() {
+        let json = br#"{"a":[1,{"b":[]}],"c":{}}"#;
+        assert!(valid_within_depth(json, 0, 4));
+        assert!(valid_within_depth(json, 0, usize::MAX));
+        assert!(!valid_within_depth(json, 0, 3));
+        assert!(!valid_within_depth(json, 0, 1));
+        assert!(!valid_within_depth(json, 0, 0));
+        assert!(valid_within_depth(b" 123 ", 0, 0));
+        assert!(valid_within_depth(b"[]", 0, 1));
+        assert!(!valid_within_depth(b"[[]]", 0, 1));
+        assert!(!valid_within_depth(b"[[]]", UNCHECKED, 1));
+        assert!(!valid_within_depth(b"[1,2", 0, 10));
+        assert!(!valid_within_depth(b"[1] x", 0, 10));
+        let mut deep = vec![b'['; 100000];
+        deep.append(&mut vec![b']'; 100000]);
+        assert!(valid_within_depth(&deep, 0, 100000));
+        assert!(!valid_within_depth(&deep, 0, 99999));
+    }
+
+    #[test]
+    fn deep() {
+        let json = br#"{"a":[1,{"b":[]}],"c":{}}"#;
+        assert_eq!(deep_containers(json, 0, 4), Ok(vec![]));
+        assert_eq!(deep_containers(json, 0, 3), Ok(vec![13]));
+        assert_eq!(deep_containers(json, 0, 1), Ok(vec![5, 8, 13, 22]));
+        assert_eq!(deep_containers(json, 0, 0), Ok(vec![0, 5, 8, 13, 22]));
+        assert_eq!(deep_containers(b" 123 ", 0, 0), Ok(vec![]));
+        let packed = deep_containers(b"[[1,2],[3]]", PACKED_ARRAYS | RECORDS, 1);
+        assert_eq!(packed, Ok(vec![1, 7]));
+        assert_eq!(
+            deep_containers(b"[[[]], [1,]]", 0, 1),
+            Err(ParseError::new(ErrorKind::Syntax, 10))
+        );
+    }
+
+    #[test]
+    fn head() {
+        let json = br#" {"a": 1, "b": [2, 3], "c": {"d": 4}} "#;
+        let count = |opts, n| {
+            let mut events = Vec::new();
+            let ret = parse_head(json, opts, n, |start, _, info| {
+                events.push((start, info));
+                1
+            });
+            (ret, events.len())
+        };
+        assert_eq!(count(0, 0), (2, 1));
+        assert_eq!(parse_head(b"[1,2]", 0, 0, |_, _, _| 1), 1);
+        assert_eq!(parse_head(b"{}", 0, 0, |_, _, _| 1), 1);
+        assert_eq!(parse_head(b"]", 0, 0, |_, _, _| 1), 0);
+        assert_eq!(count(0, 1), (8, 4));
+        assert_eq!(count(0, 2), (21, 12));
+        assert_eq!(count(0, 3), (json.len() as i64, 21));
+        assert_eq!(count(0, 100), (json.len() as i64, 21));
+        assert_eq!(count(PAIRS, 2), (21, 14));
+        assert_eq!(parse_head(b"[1, 2, x]", 0, 2, |_, _, _| 1), 5);
+        assert_eq!(parse_head(b"[1, 2, x]", 0, 3, |_, _, _| 1), -7);
+        assert_eq!(parse_head(b" 123 ", 0, 0, |_, _, _| 1), 5);
+        assert_eq!(parse_head(b"[1, 2]", 0, 1, |_, _, _| 0), 0);
+        // skipped children are still counted
+        let ret = parse_head(b"[[1], [2], [3]]", 0, 2, |_, _, info| {
+            if info & OPEN == OPEN && info & START == 0 {
+                -1
+            } else {
+                1
+            }
+        });
+        assert_eq!(ret, 9);
+    }
+
+    #[test]
+    fn ascii_keys() {
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        let json = "{\"a\":{\"caf\u{e9}\":1}}".as_bytes();
+        assert_eq!(parse(json, 0, each), json.len() as i64);
+        assert_eq!(parse(json, ASCII_KEYS, each), -6);
+        assert_eq!(parse(json, ASCII_KEYS, skip), -6);
+        let json = "{\"caf\\u00e9\":\"caf\u{e9}\"}".as_bytes();
+        assert_eq!(parse(json, ASCII_KEYS, each), json.len() as i64);
+        assert_eq!(parse(json, ASCII_KEYS, skip), json.len() as i64);
+        let json = "[{\"ok\":1},{\"\u{1F600}\":2}]".as_bytes();
+        assert_eq!(parse(json, ASCII_KEYS, each), -11);
+        assert_eq!(parse(json, ASCII_KEYS, skip), -11);
+    }
+
+    #[test]
+    fn split_numbers() {
+        fn parts(json: &[u8], opts: usize) -> Vec<(String, usize)> {
+            let mut out = Vec::new();
+            let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+                if info & NUMBER == NUMBER {
+                    let el = std::str::from_utf8(&json[start..end]).unwrap();
+                    out.push((el.to_string(), info));
+                }
+                1
+            });
+            assert_eq!(ret, json.len() as i64);
+            out
+        }
+        let p = |el: &str, info: usize| (el.to_string(), info);
+        assert_eq!(
+            parts(b"-12.50e+3", SPLIT_NUMBERS),
+            [
+                p("-", NUMBER | SIGN | START),
+                p("12", NUMBER | INTEGER),
+                p(".", NUMBER | DOT),
+                p("50", NUMBER | FRACTION),
+                p("e", NUMBER | E),
+                p("+", NUMBER | EXPONENT | SIGN),
+                p("3", NUMBER | EXPONENT | END),
+            ]
+        );
+        assert_eq!(
+            parts(b"[0,1E5]", SPLIT_NUMBERS),
+            [
+                p("0", NUMBER | INTEGER | VALUE),
+                p("1", NUMBER | INTEGER | VALUE),
+                p("E", NUMBER | E | VALUE),
+                p("5", NUMBER | EXPONENT | VALUE),
+            ]
+        );
+        assert_eq!(
+            parts(b"7", SPLIT_NUMBERS),
+            [p("7", NUMBER | INTEGER | START | END)]
+        );
+        assert_eq!(
+            parts(b"-1.5", 0),
+            [p("-1.5", NUMBER | SIGN | DOT | START | END)]
+        );
+        // children that are skipped over are not reported
+        let ret = parse(
+            b"[[1.5]]",
+            SPLIT_NUMBERS,
+            |_: usize, _: usize, info: usize| -> i64 {
+                assert_eq!(info & NUMBER, 0);
+                -1
+            },
+        );
+        assert_eq!(ret, 7);
+        let mut seen = Vec::new();
+        let ret = parse(
+            b"[1.25,2]",
+            SPLIT_NUMBERS,
+            |start: usize, _: usize, info: usize| -> i64 {
+                seen.push(start);
+                if info & DOT == DOT {
+                    0
+                } else {
+                    1
+                }
+            },
+        );
+        assert_eq!(ret, 3);
+        assert_eq!(seen, [0, 1, 2]);
+    }
+
+    #[test]
+    fn escapes() {
+        fn escapes(json: &[u8], opts: usize) -> Vec<(String, usize)> {
+            let mut out = Vec::new();
+            let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+                if info & (STRING | ESCAPE) != 0 {
+                    let el = std::str::from_utf8(&json[start..end]).unwrap();
+                    out.push((el.to_string(), info));
+                }
+                1
+            });
+            assert_eq!(ret, json.len() as i64);
+            out
+        }
+        let e = |el: &str, info: usize| (el.to_string(), info);
+        let json = br#"{"a\tb":["x\"y\u00e9\ud83d\ude00", "plain", "\\"]}"#;
+        assert_eq!(
+            escapes(json, ESCAPES),
+            [
+                e(r#""a\tb""#, KEY | STRING | ESCAPED),
+                e(r#"\t"#, ESCAPE),
+                e(r#""x\"y\u00e9\ud83d\ude00""#, VALUE | STRING | ESCAPED),
+                e(r#"\""#, ESCAPE),
+                e(r#"\u00e9"#, ESCAPE),
+                e(r#"\ud83d"#, ESCAPE),
+                e(r#"\ude00"#, ESCAPE),
+                e(r#""plain""#, VALUE | STRING),
+                e(r#""\\""#, VALUE | STRING | ESCAPED),
+                e(r#"\\"#, ESCAPE),
+            ]
+        );
+        assert_eq!(escapes(json, 0).len(), 4);
+        assert_eq!(
+            escapes(b"\"a\\\r\nb\\x41\"", ESCAPES | JSON5),
+            [
+                e("\"a\\\r\nb\\x41\"", START | END | STRING | ESCAPED),
+                e("\\\r\n", ESCAPE),
+                e("\\x41", ESCAPE),
+            ]
+        );
+        // stopping at an escape
+        let json = br#"["a\nb\tc"]"#;
+        let ret = parse(json, ESCAPES, |_: usize, _: usize, info: usize| -> i64 {
+            if info & ESCAPE == ESCAPE {
+                0
+            } else {
+                1
+            }
+        });
+        assert_eq!(ret, 10);
+    }
+
+    #[test]
+    fn container_root() {
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        for json in [&b"42"[..], b" \"x\"", b"true", b"false", b"null", b"-1"] {
+            assert_eq!(parse(json, 0, each), json.len() as i64);
+            let offset = json.iter().position(|&ch| ch != b' ').unwrap();
+            assert_eq!(parse(json, CONTAINER_ROOT, each), -(offset as i64));
+            assert_eq!(
+                parse_status(json, CONTAINER_ROOT, each),
+                ParseStatus::Error(ParseError::new(ErrorKind::ScalarRoot, offset))
+            );
+        }
+        for json in [&b" {} "[..], b"[1]", b"{\"a\":[true]}"] {
+            assert_eq!(parse(json, CONTAINER_ROOT, each), json.len() as i64);
+            assert_eq!(parse(json, CONTAINER_ROOT, skip), json.len() as i64);
+        }
+        let json = b"#!/bin/x\n [1]";
+        assert_eq!(
+            parse(json, CONTAINER_ROOT | CONFIG, each),
+            json.len() as i64
+        );
+        assert_eq!(
+            parse_status(b"  x", CONTAINER_ROOT, each),
+            ParseStatus::Error(ParseError::new(ErrorKind::Syntax, 2))
+        );
+        assert_eq!(
+            parse_status(b"  ", CONTAINER_ROOT, each),
+            ParseStatus::Error(ParseError::new(ErrorKind::UnexpectedEof, 2))
+        );
+        assert!(!valid_within_depth(b"1", CONTAINER_ROOT, 10));
+    }
+
+    #[test]
+    fn prelude() {
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        let json = b" /* generated by x\n * v1 */\n{\"a\":[1]}";
+        assert_eq!(parse(json, 0, each), -1);
+        assert_eq!(parse(json, PRELUDE, each), json.len() as i64);
+        assert_eq!(parse(json, PRELUDE, skip), json.len() as i64);
+        let mut first = None;
+        parse(json, PRELUDE, |start, _, _| {
+            first.get_or_insert(start);
+            1
+        });
+        assert_eq!(first, Some(json.len() - 9));
+        for json in [&b"/**/1"[..], b"/* a */ \"x\" ", b"[]"] {
+            assert_eq!(parse(json, PRELUDE, each), json.len() as i64);
+        }
+        // only a single block before the root value
+        assert_eq!(parse(b"/*a*/ /*b*/ 1", PRELUDE, each), -6);
+        assert_eq!(parse(b"[/*a*/1]", PRELUDE, each), -1);
+        assert_eq!(parse(b"1 /*a*/", PRELUDE, each), -2);
+        assert_eq!(
+            parse_status(b"/*/ 1", PRELUDE, each),
+            ParseStatus::Error(ParseError::new(ErrorKind::UnexpectedEof, 5))
+        );
+        let json = b"#!/bin/x\n/* v1 */ {}";
+        assert_eq!(parse(json, CONFIG | PRELUDE, each), json.len() as i64);
+        assert_eq!(crate::whitespace_bytes(json, CONFIG | PRELUDE), Ok(1));
+    }
+
+    #[test]
+    fn no_nul() {
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        let json = br#"["a\u0000b"]"#;
+        assert_eq!(parse(json, 0, each), json.len() as i64);
+        assert_eq!(parse(json, NO_NUL, each), -3);
+        assert_eq!(parse(json, NO_NUL, skip), -3);
+        assert_eq!(parse(br#"{"\u0000":1}"#, NO_NUL, each), -2);
+        assert_eq!(parse(br#""\\u0000""#, NO_NUL, each), 9);
+        for json in [&br#""\u0100""#[..], br#""\u0001""#, br#""\u1000""#] {
+            assert_eq!(parse(json, NO_NUL, each), json.len() as i64);
+        }
+        assert_eq!(parse(b"\"a\x00\"", NO_NUL, each), -2);
+        let json = br#""\x00 \x10""#;
+        assert_eq!(parse(json, JSON5, each), json.len() as i64);
+        assert_eq!(parse(json, JSON5 | NO_NUL, each), -1);
+        assert_eq!(parse(br#""\x10""#, JSON5 | NO_NUL, each), 6);
+    }
+
+    #[test]
+    fn empty_as_null() {
+        for json in [&b""[..], b" \r\n\t "] {
+            let mut elems = Vec::new();
+            let ret = parse(json, EMPTY_AS_NULL, |start, end, info| {
+                elems.push((start, end, info));
+                1
+            });
+            assert_eq!(ret, json.len() as i64);
+            assert_eq!(elems, [(json.len(), json.len(), NULL | START | END)]);
+            assert!(parse(json, 0, |_, _, _| 1) <= 0);
+            assert!(parse(json, EMPTY_AS_NULL | CONTAINER_ROOT, |_, _, _| 1) <= 0);
+        }
+        assert_eq!(
+            parse(" \u{2028}".as_bytes(), EMPTY_AS_NULL | JSON5, |_, _, _| 1),
+            4
+        );
+        assert_eq!(parse(b" ", EMPTY_AS_NULL, |_, _, _| 0), 1);
+        assert_eq!(parse(b" 1 ", EMPTY_AS_NULL, |_, _, _| 1), 3);
+        let mut infos = Vec::new();
+        parse(b"", EMPTY_AS_NULL | DOC_EVENTS, |_, _, info| {
+            infos.push(info);
+            1
+        });
+        assert_eq!(infos, [DOC_BEGIN, NULL | START | END, DOC_END]);
+    }
+
+    #[test]
+    fn stop_offsets() {
+        let json = b" {\"a\" : 1 ,\n\t\"b\": [ ] } ";
+        let at = |n: usize| {
+            let mut count = 0;
+            parse_stop_offsets(json, 0, |_, _, _| {
+                count += 1;
+                (count != n) as i64
+            })
+            .unwrap()
+        };
+        let offsets = |stop_offset, next_token_offset| StopOffsets {
+            stop_offset,
+            next_token_offset,
+        };
+        // every kind of element, from the '{' to the '}'
+        assert_eq!(at(1), offsets(2, 2));
+        assert_eq!(at(2), offsets(5, 6));
+        assert_eq!(at(3), offsets(7, 8));
+        assert_eq!(at(4), offsets(9, 10));
+        assert_eq!(at(5), offsets(11, 13));
+        assert_eq!(at(6), offsets(16, 16));
+        assert_eq!(at(7), offsets(17, 18));
+        assert_eq!(at(8), offsets(19, 20));
+        assert_eq!(at(9), offsets(21, 22));
+        assert_eq!(at(10), offsets(23, 24));
+        assert_eq!(at(100), offsets(24, 24));
+        let json = b"[1] ";
+        let stop = parse_stop_offsets(json, DOC_EVENTS, |_, _, info| (info & DOC_END == 0) as i64);
+        assert_eq!(stop, Ok(offsets(4, 4)));
+        assert_eq!(
+            parse_stop_offsets(b"[1,]", 0, |_, _, _| 1),
+            Err(ParseError::new(ErrorKind::Syntax, 3))
+        );
+    }
+
+    #[test]
+    fn solidus() {
+        let json = br#"{"a\/b":"c/d","e":"\\/"}"#;
+        assert_eq!(parse(json, 0, |_, _, _| 1), json.len() as i64);
+        assert_eq!(parse(json, FORBID_ESCAPED_SOLIDUS, |_, _, _| 1), -3);
+        assert_eq!(parse(json, REQUIRE_ESCAPED_SOLIDUS, |_, _, _| 1), -10);
+        let json = br#"["a\/b","\/","\\\/"]"#;
+        assert_eq!(
+            parse(json, REQUIRE_ESCAPED_SOLIDUS, |_, _, _| 1),
+            json.len() as i64
+        );
+        let json = br#"["ab","c/"]"#;
+        assert_eq!(
+            parse(json, FORBID_ESCAPED_SOLIDUS, |_, _, _| 1),
+            json.len() as i64
+        );
+        // also when skipping
+        assert_eq!(parse(json, REQUIRE_ESCAPED_SOLIDUS, |_, _, _| -1), -8);
+        assert_eq!(
+            parse(br#"[["\/"]]"#, FORBID_ESCAPED_SOLIDUS, |_, _, _| -1),
+            -3
+        );
+    }
+
+    #[test]
+    fn packed_arrays() {
+        fn elements(json: &[u8], opts: usize) -> (i64, Vec<(String, usize, usize)>) {
+            let mut out = Vec::new();
+            let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+                let text = String::from_utf8(json[start..end].to_vec()).unwrap();
+                let len = if info & (ARRAY | OPEN | CLOSE) == ARRAY {
+                    packed_len(json, start, end, info)
+                } else {
+                    0
+                };
+                out.push((text, info, len));
+                1
+            });
+            (ret, out)
+        }
+        let json = br#"{"a":[1, -2.5e3 ,3],"b":["x","y\"]"],"c":[1,"x"],"d":[],"e":[[1],[]]}"#;
+        let (ret, out) = elements(json, PACKED_ARRAYS);
+        assert_eq!(ret, json.len() as i64);
+        let packed: Vec<_> = out
+            .iter()
+            .filter(|(_, info, _)| info & (ARRAY | OPEN | CLOSE) == ARRAY)
+            .map(|(text, info, len)| (text.as_str(), info & (NUMBER | STRING), *len))
+            .collect();
+        assert_eq!(
+            packed,
+            [
+                ("[1, -2.5e3 ,3]", NUMBER, 3),
+                (r#"["x","y\"]"]"#, STRING, 2),
+                ("[1]", NUMBER, 1)
+            ]
+        );
+        assert_eq!(out.len(), elements(json, 0).1.len() - 12);
+        let (_, out) = elements(b" [1,2] ", PACKED_ARRAYS);
+        assert_eq!(
+            out,
+            [("[1,2]".to_string(), ARRAY | NUMBER | START | END, 2)]
+        );
+        // errors are found as usual
+        assert_eq!(elements(b"[1,2,]", PACKED_ARRAYS).0, -5);
+        assert_eq!(elements(b"[1,02]", PACKED_ARRAYS).0, -4);
+        assert_eq!(elements(b"[\"a\",\"b]", PACKED_ARRAYS).0, -8);
+        let options = Options {
+            flags: PACKED_ARRAYS,
+            max_array_len: 2,
+            ..Options::default()
+        };
+        assert_eq!(parse_with(b"[1,2,3]", &options, |_, _, _| 1), -5);
+    }
+
+    #[test]
+    fn records() {
+        fn elements(json: &[u8], opts: usize) -> (i64, Vec<String>) {
+            let mut out = Vec::new();
+            let ret = parse_values(json, opts, |start: usize, end: usize, _: usize| -> i64 {
+                out.push(String::from_utf8(json[start..end].to_vec()).unwrap());
+                1
+            });
+            (ret, out)
+        }
+        let json = br#"{"rows":[{"a":[1,{"b":2}]}, 3, {}, [{"c":4}]]}"#;
+        let (ret, out) = elements(json, RECORDS);
+        assert_eq!(ret, json.len() as i64);
+        assert_eq!(
+            out,
+            [
+                "{",
+                "[",
+                r#"{"a":[1,{"b":2}]}"#,
+                "3",
+                "{}",
+                "[",
+                r#"{"c":4}"#,
+                "]",
+                "]",
+                "}"
+            ]
+        );
+        assert_eq!(elements(json, 0).1.len(), 20);
+        let mut infos = Vec::new();
+        parse(b"[{}]", RECORDS, |_, _, info| {
+            infos.push(info);
+            1
+        });
+        assert_eq!(
+            infos,
+            [START | OPEN | ARRAY, OBJECT | VALUE, END | CLOSE | ARRAY]
+        );
+        // the records are still validated
+        assert_eq!(elements(br#"[{"a":1,}]"#, RECORDS).0, -8);
+        assert_eq!(elements(br#"[{"b":1,"a":2}]"#, RECORDS | SORTED_KEYS).0, -8);
+        assert_eq!(elements(br#"[{"a":[1}]"#, RECORDS).0, -8);
+        let options = Options {
+            flags: RECORDS,
+            max_depth: 2,
+            ..Options::default()
+        };
+        assert_eq!(parse_with(b"[{},{}]", &options, |_, _, _| 1), 7);
+        assert_eq!(parse_with(b"[{\"a\":[]}]", &options, |_, _, _| 1), -6);
+        // stopping at a record
+        let ret = parse(b"[1,{},2]", RECORDS, |_, _, info| {
+            if info & OBJECT == OBJECT {
+                0
+            } else {
+                1
+            }
+        });
+        assert_eq!(ret, 5);
+    }
+
+    #[test]
+    fn forbid_numbers() {
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        let json = b"[1, -2.5e+3]";
+        assert_eq!(parse(json, 0, each), json.len() as i64);
+        let all = FORBID_EXPONENTS | FORBID_FRACTIONS | FORBID_NEGATIVE;
+        for (opts, ret) in [
+            (FORBID_NEGATIVE, -4),
+            (FORBID_FRACTIONS, -6),
+            (FORBID_EXPONENTS, -8),
+            (all, -4),
+        ] {
+            assert_eq!(parse(json, opts, each), ret);
+            assert_eq!(parse(json, opts, skip), ret);
+        }
+        assert_eq!(parse(b"-0", FORBID_NEGATIVE, each), 0);
+        assert_eq!(parse(b"1.0", FORBID_FRACTIONS, each), -1);
+        assert_eq!(parse(b"1E5", FORBID_EXPONENTS, each), -1);
+        let json = b"[0, 12, 300]";
+        assert_eq!(parse(json, all, each), json.len() as i64);
+        assert_eq!(parse(json, all | SPLIT_NUMBERS, each), json.len() as i64);
+    }
+
+    #[test]
+    fn doc_events() {
+        fn events(json: &[u8], opts: usize, skip: bool) -> (i64, Vec<(usize, usize, usize)>) {
+            let mut out = Vec::new();
+            let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+                out.push((start, end, info));
+                if skip {
+                    -1
+                } else {
+                    1
+                }
+            });
+            (ret, out)
+        }
+        let (ret, out) = events(b" 12 ", DOC_EVENTS, false);
+        assert_eq!(ret, 4);
+        assert_eq!(
+            out,
+            [
+                (0, 0, DOC_BEGIN),
+                (1, 3, START | END | NUMBER),
+                (4, 4, DOC_END)
+            ]
+        );
+        let (ret, out) = events(b"[1]\n", DOC_EVENTS, true);
+        assert_eq!(ret, 4);
+        assert_eq!(
+            out,
+            [
+                (0, 0, DOC_BEGIN),
+                (0, 1, START | OPEN | ARRAY),
+                (2, 3, END | CLOSE | ARRAY),
+                (4, 4, DOC_END)
+            ]
+        );
+        let json = b"#!/bin/x\n{}";
+        let (ret, out) = events(json, DOC_EVENTS | CONFIG, false);
+        assert_eq!(ret, json.len() as i64);
+        assert_eq!((out[0], out[3]), ((0, 0, DOC_BEGIN), (11, 11, DOC_END)));
+        // the stream is unchanged without the option
+        assert_eq!(events(b"[1]", 0, false).1.len(), 3);
+        // no DOC_END for an invalid document
+        let (ret, out) = events(b"[1,]", DOC_EVENTS, false);
+        assert_eq!(ret, -3);
+        assert!(out.iter().all(|&(_, _, info)| info & DOC_END == 0));
+        // stopping
+        assert_eq!(parse(b"[1]", DOC_EVENTS, |_, _, _| 0), 0);
+        assert_eq!(
+            parse_status(b"[1]", DOC_EVENTS, |_, _, _| 0),
+            ParseStatus::Stopped(0)
+        );
+        assert_eq!(
+            parse_status(b"]", DOC_EVENTS, |_, _, _| 1),
+            ParseStatus::Error(ParseError::new(ErrorKind::Syntax, 0))
+        );
+        let ret = parse(b"[1] ", DOC_EVENTS, |_, _, info| {
+            if info & DOC_END == DOC_END {
+                0
+            } else {
+                1
+            }
+        });
+        assert_eq!(ret, 4);
+    }
+
+    #[test]
+    fn raw_whitespace() {
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        let json = b"[\"line 1\nline 2\", \"a\tb\r\n\"]";
+        assert_eq!(parse(json, 0, each), -8);
+        assert_eq!(parse(json, 0, skip), -8);
+        assert_eq!(parse(json, RAW_WHITESPACE, each), json.len() as i64);
+        assert_eq!(parse(json, RAW_WHITESPACE, skip), json.len() as i64);
+        let json = b"{\"a long key that is scanned in blocks\t\":\"\x01\"}";
+        assert_eq!(parse(json, RAW_WHITESPACE, each), -42);
+        assert_eq!(parse(json, RAW_WHITESPACE, skip), -42);
+    }
+
+    #[test]
+    fn total_bytes() {
+        let options = Options {
+            max_total_bytes: 5,
+            ..Options::default()
+        };
+        let mut count = 0;
+        let ret = parse_with(b"[1,2,3]", &options, |_, _, _| {
+            count += 1;
+            1
+        });
+        assert_eq!((ret, count), (-5, 0));
+        assert_eq!(
+            parse_status_with(b"[1,2,3]", &options, |_, _, _| 1),
+            ParseStatus::Error(ParseError::new(ErrorKind::LimitExceeded, 5))
+        );
+        assert_eq!(parse_with(b"[1,2]", &options, |_, _, _| 1), 5);
+        assert_eq!(
+            parse_status_with(b"[1,2]", &options, |_, _, _| 1),
+            ParseStatus::Complete(5)
+        );
+        assert_eq!(
+            parse_status_with(b"[1,", &options, |_, _, _| 1),
+            ParseStatus::Error(ParseError::new(ErrorKind::UnexpectedEof, 3))
+        );
+    }
+
+    #[test]
+    fn max_container_len() {
+        let options = Options {
+            max_members: 2,
+            max_array_len: 3,
+            ..Options::default()
+        };
+        let each = |_, _, _| 1;
+        assert_eq!(
+            parse_with(br#"{"a":[1,2,3],"b":{"c":1,"d":2}}"#, &options, each),
+            31
+        );
+        assert_eq!(parse_with(br#"{"a":1,"b":2,"c":3}"#, &options, each), -13);
+        assert_eq!(parse_with(b"[[1,2,3,4]]", &options, each), -8);
+        assert_eq!(parse_with(b"[{},{},{},{}]", &options, each), -10);
+        assert_eq!(
+            parse_status_with(b"[1, 2, 3, 4]", &options, each),
+            ParseStatus::Error(ParseError::new(ErrorKind::Syntax, 10))
+        );
+        // empty slots are elements
+        let slots = Options {
+            flags: EMPTY_SLOTS,
+            ..options.clone()
+        };
+        assert_eq!(parse_with(b"[1,,2]", &slots, each), 6);
+        assert_eq!(parse_with(b"[1,,,2]", &slots, each), -5);
+        // skipped containers are not counted
+        let skip = |_, _, info| if info & OPEN == OPEN { -1 } else { 1 };
+        assert_eq!(parse_with(b"[1,2,3,4]", &options, skip), 9);
+    }
+
+    #[test]
+    fn max_depth() {
+        let options = Options {
+            max_depth: 3,
+            ..Options::default()
+        };
+        let json = b"{\"a\":[[[1]]],\"b\":2}";
+        assert_eq!(parse_with(json, &options, |_, _, _| 1), -7);
+        assert_eq!(parse_with(b"{\"a\":[[1]]}", &options, |_, _, _| 1), 11);
+        for flags in [0, UNCHECKED] {
+            let options = Options { flags, ..options };
+            // skipping over the root still honors the limit
+            let ret = parse_with(
+                json,
+                &options,
+                |_, _, info| {
+                    if info & OPEN == OPEN {
+                        -1
+                    } else {
+                        1
+                    }
+                },
+            );
+            assert_eq!(ret, -7);
+            assert_eq!(
+                parse_status_with(json, &options, |_, _, _| -1),
+                ParseStatus::Error(ParseError::new(ErrorKind::Syntax, 7))
+            );
+        }
+        let zero = Options {
+            flags: UNCHECKED,
+            max_depth: 0,
+            ..Options::default()
+        };
+        assert_eq!(parse_with(b"[]", &zero, |_, _, _| 1), 0);
+        assert_eq!(parse_with(b"1", &zero, |_, _, _| 1), 1);
+        let bomb = [b'['; 100_000];
+        assert_eq!(parse_with(&bomb, &zero, |_, _, _| -1), 0);
+        let options = Options {
+            flags: UNCHECKED,
+            ..options
+        };
+        assert_eq!(parse_with(&bomb, &options, |_, _, _| -1), -3);
+    }
+
+    #[test]
+    fn valid_prefix_() {
+        assert_eq!(valid_prefix(b"{\"a\":[1,2]}\x00\xff\x01", 0), Some(11));
+        assert_eq!(valid_prefix(b"  [1, 2] [3]", 0), Some(8));
+        assert_eq!(valid_prefix(b"\"hello\"world", 0), Some(7));
+        assert_eq!(valid_prefix(b"true\n", 0), Some(4));
+        assert_eq!(valid_prefix(b"-12.5,", 0), Some(5));
+        assert_eq!(valid_prefix(b"{\"a\":}\x00", 0), None);
+        assert_eq!(valid_prefix(b"[1,2", 0), None);
+        assert_eq!(valid_prefix(b"tru", 0), None);
+        assert_eq!(valid_prefix(b"", 0), None);
+        assert_eq!(valid_prefix(b"   ", 0), None);
+        assert_eq!(valid_prefix(b"\x00{}", 0), None);
+        assert_eq!(valid_prefix(b"[1,}] x", UNCHECKED), None);
+        assert_eq!(valid_prefix(b"[1,2] x", UNCHECKED), Some(5));
+        assert_eq!(valid_prefix(b"#!/bin/x\n[1] x", CONFIG), Some(12));
+        assert_eq!(valid_prefix(b"#!/bin/x\n[1] x", 0), None);
+        assert_eq!(valid_prefix(b" 1 x", CONTAINER_ROOT), None);
+        assert_eq!(valid_prefix(b" {} x", CONTAINER_ROOT), Some(3));
+    }
+
+    #[test]
+    fn number_limits() {
+        let options = Options {
+            max_exponent: 308,
+            max_significand_digits: 5,
+            ..Options::default()
+        };
+        let run = |json: &[u8], ret: i64| {
+            assert_eq!(parse_with(json, &options, |_, _, _| 1), ret);
+            // also when skipped over
+            let json = [b"[", json, b"]"].concat();
+            let ret = if ret > 0 { ret + 2 } else { ret - 1 };
+            assert_eq!(parse_with(&json, &options, |_, _, _| -1), ret);
+        };
+        for json in [
+            &b"1e308"[..],
+            b"-1E-308",
+            b"1e+0000308",
+            b"123.45",
+            b"-0.0001e5",
+        ]
+        .iter()
+        {
+            run(json, json.len() as i64);
+        }
+        run(b"1e309", -4);
+        run(b"1e99999999999999999999999", -4);
+        run(b"-1.5E-1000", -9);
+        run(b"123456", -5);
+        run(b"-1234.56", -7);
+        run(b"0.00000", -6);
+        // the defaults are unlimited
+        let json = b"123456789012345678901234567890e99999999999999999999999";
+        assert_eq!(parse(json, 0, |_, _, _| 1), json.len() as i64);
+    }
+
+    #[test]
+    fn digits() {
+        let mut json = Vec::new();
+        for n in 0..40 {
+            json.resize(json.len() + n, b'7');
+            json.extend_from_slice(&[b'x', b'0', 0xFF, b'9', b'/', b':']);
+        }
+        for i in 0..json.len() {
+            let mut j = i;
+            while j < json.len() && json[j].is_ascii_digit() {
+                j += 1;
+            }
+            assert_eq!(scan_digits(&json, i), j);
+            // digits that run to the end of the json
+            for end in i..(i + 20).min(json.len()) {
+                assert_eq!(scan_digits(&json[..end], i), j.min(end));
+            }
+        }
+        // long numbers in every part
+        let long = "1234567890".repeat(5);
+        for num in [
+            long.clone(),
+            format!("-{}.{}e-{}", long, long, long),
+            format!("0.{}E+{}", long, long),
+        ]
+        .iter()
+        {
+            let json = format!("[{},{}]", num, num);
+            let mut nums = Vec::new();
+            let ret = parse(
+                json.as_bytes(),
+                0,
+                |start: usize, end: usize, info: usize| -> i64 {
+                    if info & NUMBER == NUMBER {
+                        nums.push(json[start..end].to_string());
+                    }
+                    1
+                },
+            );
+            assert_eq!(ret, json.len() as i64);
+            assert_eq!(nums, [num.clone(), num.clone()]);
+        }
+    }
+
+    #[test]
+    fn short_inputs() {
+        // every short input reaches the close, colon, and escape handling at
+        // the smallest indexes, and must never panic
+        let chars = b"{}[]:,\"\\1 ";
+        let mut inputs: Vec<Vec<u8>> = vec![vec![]];
+        for _ in 0..4 {
+            let mut next = Vec::new();
+            for input in &inputs {
+                for &ch in chars.iter() {
+                    let mut input = input.clone();
+                    input.push(ch);
+                    next.push(input);
+                }
+            }
+            inputs.extend(next.into_iter().filter(|input| input.len() <= 4));
+            inputs.sort();
+            inputs.dedup();
+        }
+        for json in &inputs {
+            for &opts in [0, UNCHECKED, JSON5 | EMPTY_SLOTS].iter() {
+                for &r in [1, -1, 0].iter() {
+                    let ret = parse(json, opts, |start: usize, end: usize, _: usize| -> i64 {
+                        assert!(start <= end && end <= json.len());
+                        r
+                    });
+                    assert!(ret.unsigned_abs() as usize <= json.len());
+                }
+            }
+        }
+        assert_eq!(parse(b"}", 0, |_, _, _| 1), 0);
+        assert_eq!(parse(b"]", 0, |_, _, _| 1), 0);
+        assert_eq!(parse(b"[]", 0, |_, _, _| 1), 2);
+        assert_eq!(parse(b"{}", UNCHECKED, |_, _, _| -1), 2);
+        assert_eq!(parse(b"[}", 0, |_, _, _| -1), -1);
+        // unchecked only counts the brackets
+        assert_eq!(parse(b"[}", UNCHECKED, |_, _, _| -1), 2);
+    }
+
+    #[test]
+    fn json5_line_separators() {
+        let each = |_: usize, _: usize, _: usize| -> i64 { 1 };
+        let skip = |_: usize, _: usize, _: usize| -> i64 { -1 };
+        let ls: &[u8] = b"\xE2\x80\xA8";
+        let ps: &[u8] = b"\xE2\x80\xA9";
+        let json = [
+            ls, b"{", ps, b"\"a\"", ls, b":", ps, b"[", ls, b"1", ps, b",", ls, b"2", ps, b"]", ls,
+            b",", ps, b"\"b\"", b":", b"true", ls, b"}", ps,
+        ]
+        .concat();
+        assert_eq!(parse(&json, 0, each), 0);
+        assert_eq!(parse(&json, JSON5, each), json.len() as i64);
+        assert_eq!(parse(&json, JSON5, skip), json.len() as i64);
+        let mut out = String::new();
+        parse(&json, JSON5, |start: usize, end: usize, _: usize| -> i64 {
+            out.push_str(&frag(&json, start, end));
+            1
+        });
+        assert_eq!(out, r#"{"a":[1,2],"b":true}"#);
+        // other three byte characters are not whitespace
+        let json = b"[1\xE2\x80\xAA]";
+        assert_eq!(parse(json, JSON5, each), -2);
+        assert_eq!(parse(b"[1\xE2\x80]", JSON5, each), -2);
+        assert_eq!(parse(b"[1,\xE2\x80\xA8", JSON5, each), -6);
+        // still allowed inside of strings
+        assert_eq!(parse(b"\"\xE2\x80\xA8\"", 0, each), 5);
+    }
+
+    #[test]
+    fn simples() {
+        assert_eq!(parse_simple(br#" 10 "#), NUMBER | START | END);
+        assert_eq!(parse_simple(br#" 10.0 "#), NUMBER | DOT | START | END);
+        assert_eq!(
+            parse_simple(br#" -0.0 "#),
+            NUMBER | SIGN | DOT | START | END
+        );
+        assert_eq!(
+            parse_simple(br#" -1230.1230e10 "#),
+            NUMBER | SIGN | DOT | E | START | END
+        );
+        assert_eq!(
+            parse_simple(br#" -1230.1230e-10 "#),
+            NUMBER | SIGN | DOT | E | START | END
+        );
+        assert_eq!(
+            parse_simple(br#" 1230.1230e+10 "#),
+            NUMBER | DOT | E | START | END
+        );
+        assert_eq!(
+            parse_simple(br#" -1230e10 "#),
+            NUMBER | SIGN | E | START | END
+        );
+        assert_eq!(parse_simple(br#" 1230e10 "#), NUMBER | E | START | END);
+        assert_eq!(parse_simple(br#" "" "#), STRING | START | END);
+        assert_eq!(parse_simple(br#" "hello" "#), STRING | START | END);
+        assert_eq!(
+            parse_simple(br#" "hell\no" "#),
+            STRING | ESCAPED | START | END
+        );
+        assert_eq!(
+            parse_simple(br#" "hell\no" "#),
+            STRING | ESCAPED | START | END
+        );
+        assert_eq!(parse_simple(br#" true "#), TRUE | START | END);
+        assert_eq!(parse_simple(br#" false "#), FALSE | START | END);
+        assert_eq!(parse_simple(br#" null "#), NULL | START | END);
+    }
+
+    fn parse_simple(json: &[u8]) -> usize {
+        parse_simple_opts(json, 0)
+    }
+
+    fn parse_simple_opts(json: &[u8], opts: usize) -> usize {
+        let mut oinfo: usize = 0;
+        let ret = parse(json, opts, |_: usize, _: usize, info: usize| -> i64 {
+            oinfo = info;
+            -1
+        });
         assert_eq!(ret as usize, json.len());
         oinfo
     }
@@ -1441,6 +4255,140 @@ mod tests {
         }
     }
 
+    // Rebuild the document from the ranges of its values, keys, and
+    // brackets, with the commas and colons inferred from the structure. The
+    // rebuilt document must produce the same elements as the original, and
+    // every element must cover exactly its own text.
+    fn round_trip(json: &[u8]) {
+        let tokens = parse_to_vec(json, 0).unwrap();
+        let mut out = Vec::new();
+        let mut first = true;
+        for t in &tokens {
+            let text = &json[t.start..t.end];
+            if t.info & (COMMA | COLON) != 0 {
+                assert_eq!(text, if t.info & COMMA == COMMA { b"," } else { b":" });
+                continue;
+            }
+            if t.info & CLOSE == CLOSE {
+                assert_eq!(
+                    text,
+                    if t.info & OBJECT == OBJECT {
+                        b"}"
+                    } else {
+                        b"]"
+                    }
+                );
+                out.push(text[0]);
+                first = false;
+                continue;
+            }
+            if t.info & OPEN == OPEN {
+                assert_eq!(
+                    text,
+                    if t.info & OBJECT == OBJECT {
+                        b"{"
+                    } else {
+                        b"["
+                    }
+                );
+            } else if t.info & STRING == STRING {
+                assert!(text.len() >= 2 && text[0] == b'"' && text[text.len() - 1] == b'"');
+            } else if t.info & NUMBER == NUMBER {
+                assert!(number_f64(json, t.start, t.end).is_some());
+            }
+            if !first && out.last() != Some(&b':') {
+                // not the first element of a container or the value of a key
+                out.push(b',');
+            }
+            out.extend_from_slice(text);
+            if t.info & KEY == KEY {
+                out.push(b':');
+            }
+            first = t.info & OPEN == OPEN;
+        }
+        let texts = |json: &[u8], tokens: &[Token]| -> Vec<(Vec<u8>, usize)> {
+            tokens
+                .iter()
+                .map(|t| (json[t.start..t.end].to_vec(), t.info))
+                .collect()
+        };
+        let again = parse_to_vec(&out, 0).unwrap();
+        assert_eq!(texts(&out, &again), texts(json, &tokens));
+    }
+
+    #[test]
+    fn round_trip_files() {
+        for file in fs::read_dir("testfiles").unwrap() {
+            let json = fs::read(file.unwrap().path()).unwrap();
+            round_trip(&json);
+        }
+        round_trip(b" [ ] ");
+        round_trip(b" { } ");
+        round_trip(b" 1 ");
+        round_trip(br#"[[],{},[{}],{"a":[]}]"#);
+    }
+
+    // generate random valid json, with random whitespace
+    fn random_json(seed: &mut u64, depth: usize, out: &mut Vec<u8>) {
+        fn next(seed: &mut u64) -> u64 {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 7;
+            *seed ^= *seed << 17;
+            *seed
+        }
+        fn ws(seed: &mut u64, out: &mut Vec<u8>) {
+            for _ in 0..next(seed) % 3 {
+                out.push(b" \t\r\n"[(next(seed) % 4) as usize]);
+            }
+        }
+        const SCALARS: [&str; 10] = [
+            "0",
+            "-1",
+            "12.5e-3",
+            "1E+2",
+            "true",
+            "false",
+            "null",
+            r#""""#,
+            r#""a\"b\\""#,
+            r#""\u00e9\n""#,
+        ];
+        ws(seed, out);
+        let kind = if depth > 4 { 2 } else { next(seed) % 3 };
+        if kind == 2 {
+            let scalar = SCALARS[(next(seed) % SCALARS.len() as u64) as usize];
+            out.extend_from_slice(scalar.as_bytes());
+        } else {
+            out.push(if kind == 0 { b'{' } else { b'[' });
+            for i in 0..next(seed) % 4 {
+                if i > 0 {
+                    ws(seed, out);
+                    out.push(b',');
+                }
+                if kind == 0 {
+                    ws(seed, out);
+                    out.extend_from_slice(format!("\"k{}\"", next(seed) % 10).as_bytes());
+                    ws(seed, out);
+                    out.push(b':');
+                }
+                random_json(seed, depth + 1, out);
+            }
+            ws(seed, out);
+            out.push(if kind == 0 { b'}' } else { b']' });
+        }
+        ws(seed, out);
+    }
+
+    #[test]
+    fn round_trip_random() {
+        let mut seed = 0x2545F4914F6CDD1D;
+        for _ in 0..1000 {
+            let mut json = Vec::new();
+            random_json(&mut seed, 0, &mut json);
+            round_trip(&json);
+        }
+    }
+
     #[test]
     #[ignore]
     fn bench() {
@@ -1478,6 +4426,60 @@ mod tests {
         );
     }
 
+    // the throughput of a function over a json, in GB/sec, as the best of a
+    // few rounds of at least 32 MB each
+    fn throughput<F: FnMut(&[u8])>(json: &[u8], mut f: F) -> f64 {
+        let mut best: f64 = 0.0;
+        for _ in 0..5 {
+            let mut total = 0;
+            let start = std::time::Instant::now();
+            while total < 32 * 1024 * 1024 {
+                f(json);
+                total += json.len();
+            }
+            let rate = total as f64 / start.elapsed().as_secs_f64() / 1024.0 / 1024.0 / 1024.0;
+            best = best.max(rate);
+        }
+        best
+    }
+
+    #[test]
+    #[ignore]
+    fn modes_bench() {
+        let dir = std::env::var("PJSON_BENCH_DIR").unwrap_or_else(|_| "testfiles".into());
+        let mut paths: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        for path in paths {
+            let json = std::fs::read(&path).unwrap();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let checked = throughput(&json, |json| {
+                assert!(parse(json, 0, |_, _, _| 1) > 0);
+            });
+            let unchecked = throughput(&json, |json| {
+                assert!(parse(json, UNCHECKED, |_, _, _| -1) > 0);
+            });
+            let valid = throughput(&json, |json| {
+                assert!(valid_within_depth(json, 0, usize::MAX));
+            });
+            let tree = throughput(&json, |json| {
+                assert!(build(json, 0, &mut tree::tests::Builder).is_ok());
+            });
+            println!(
+                "running benchmark: {}: checked: {:.2} GB/sec",
+                name, checked
+            );
+            println!(
+                "running benchmark: {}: unchecked: {:.2} GB/sec",
+                name, unchecked
+            );
+            println!("running benchmark: {}: valid: {:.2} GB/sec", name, valid);
+            println!("running benchmark: {}: tree: {:.2} GB/sec", name, tree);
+        }
+    }
+
     const EXAMPLE: &[u8] = br#"
 	    {
 	      "name": {"first": "Tom", "last": "Anderson"},
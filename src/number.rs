@@ -0,0 +1,481 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+
+/// Convert a Number element to an f64. The 'start' and 'end' params are the
+/// range of a Number element, as passed to the iter function of parse.
+///
+/// Numbers that are too large for an f64 are returned as infinity.
+/// Returns None when the range is not a Number.
+pub fn number_f64(json: &[u8], start: usize, end: usize) -> Option<f64> {
+    std::str::from_utf8(&json[start..end]).ok()?.parse().ok()
+}
+
+/// Returns true if the Number element can be converted to an f64 without
+/// losing precision. That is, converting the f64 back to its shortest decimal
+/// form yields the same numeric value as the original Number.
+///
+/// Integers beyond 2^53 that are not exactly representable, and decimals that
+/// need more than 17 significant digits, are reported as not exact. So are
+/// numbers that overflow to infinity or underflow to zero. Note that a decimal
+/// like 0.1 is reported as exact because it round trips through f64, even
+/// though its binary value is an approximation.
+pub fn number_is_exact_f64(json: &[u8], start: usize, end: usize) -> bool {
+    let f = match number_f64(json, start, end) {
+        Some(f) if f.is_finite() => f,
+        _ => return false,
+    };
+    let (neg, digits, exp) = match decompose(&json[start..end]) {
+        Some(parts) => parts,
+        None => return false,
+    };
+    if digits.is_empty() {
+        // zero
+        return true;
+    }
+    let formatted = format!("{:e}", f);
+    match decompose(formatted.as_bytes()) {
+        Some((fneg, fdigits, fexp)) => fneg == neg && fdigits == digits && fexp == exp,
+        None => false,
+    }
+}
+
+/// Convert a Number element to an i64. The 'start' and 'end' params are the
+/// range of a Number element, as passed to the iter function of parse.
+///
+/// Returns None when the Number has a fraction or an exponent, or when it does
+/// not fit in an i64. Overflow is detected as the digits are read, so a very
+/// long Number is rejected without reading all of its digits.
+pub fn number_i64(json: &[u8], start: usize, end: usize) -> Option<i64> {
+    let num = &json[start..end];
+    let neg = num.first() == Some(&b'-');
+    let digits = if neg { &num[1..] } else { num };
+    if digits.is_empty() {
+        return None;
+    }
+    let mut n: i64 = 0;
+    for &ch in digits {
+        if !ch.is_ascii_digit() {
+            return None;
+        }
+        let d = (ch - b'0') as i64;
+        // accumulate negative numbers as negative, to allow for i64::MIN
+        n = n.checked_mul(10)?;
+        n = if neg {
+            n.checked_sub(d)?
+        } else {
+            n.checked_add(d)?
+        };
+    }
+    Some(n)
+}
+
+/// Convert a Number element to a u64. The 'start' and 'end' params are the
+/// range of a Number element, as passed to the iter function of parse.
+///
+/// Returns None when the Number is negative, has a fraction or an exponent, or
+/// does not fit in a u64. Negative zero is zero. Overflow is detected as the
+/// digits are read, so a very long Number is rejected without reading all of
+/// its digits.
+pub fn number_u64(json: &[u8], start: usize, end: usize) -> Option<u64> {
+    let num = &json[start..end];
+    let neg = num.first() == Some(&b'-');
+    let digits = if neg { &num[1..] } else { num };
+    if digits.is_empty() {
+        return None;
+    }
+    let mut n: u64 = 0;
+    for &ch in digits {
+        if !ch.is_ascii_digit() {
+            return None;
+        }
+        n = n.checked_mul(10)?.checked_add((ch - b'0') as u64)?;
+    }
+    if neg && n != 0 {
+        return None;
+    }
+    Some(n)
+}
+
+/// Convert a Number element to a canonical string, such that numbers that are
+/// equal by value have the same canonical string, like "1e2", "100", and
+/// "100.0". The 'start', 'end', and 'info' params are those of a Number
+/// element, as passed to the iter function of parse.
+///
+/// The canonical string uses the format of the ECMAScript Number toString
+/// function, which is also used by canonicalize, such as "100", "0.5", and
+/// "1e+21". Unlike converting to an f64, the value is never rounded, so
+/// numbers beyond the precision of an f64 keep all of their digits and
+/// numbers beyond its range keep their exponent. A Number with an exponent
+/// that does not fit in an i64 is returned as is.
+pub fn number_canonical(json: &[u8], start: usize, end: usize, info: usize) -> String {
+    let num = &json[start..end];
+    if info & (DOT | E) == 0 && num.len() <= 21 {
+        // integers are already canonical, except for negative zero
+        if num == b"-0" {
+            return "0".to_string();
+        }
+        return String::from_utf8_lossy(num).into_owned();
+    }
+    let (neg, digits, exp) = match decompose(num) {
+        Some(parts) => parts,
+        None => return String::from_utf8_lossy(num).into_owned(),
+    };
+    if digits.is_empty() {
+        return "0".to_string();
+    }
+    let n = match exp.checked_add(digits.len() as i64) {
+        Some(n) => n,
+        None => return String::from_utf8_lossy(num).into_owned(),
+    };
+    let mut out = Vec::new();
+    if neg {
+        out.push(b'-');
+    }
+    write_es_digits(&digits, n, &mut out);
+    String::from_utf8(out).unwrap()
+}
+
+// Write a finite f64 in the format of the ECMAScript Number.prototype.toString
+// function, as required by RFC 8785. The shortest round trip digits are used.
+pub(crate) fn write_es_number(f: f64, out: &mut Vec<u8>) {
+    if f == 0.0 {
+        out.push(b'0');
+        return;
+    }
+    if f < 0.0 {
+        out.push(b'-');
+    }
+    let formatted = format!("{:e}", f.abs());
+    let (mantissa, exp) = formatted.split_once('e').unwrap();
+    let digits: Vec<u8> = mantissa.bytes().filter(|&ch| ch != b'.').collect();
+    write_es_digits(&digits, exp.parse::<i64>().unwrap() + 1, out);
+}
+
+/// Parse an Array of Numbers, such as a GeoJSON coordinate, directly into a
+/// Vec of f64. The 'start' and 'end' params are the range of the complete
+/// Array, such as from parse_value, with optional whitespace around it.
+///
+/// The Numbers are validated and converted inline, without calling back for
+/// each element, which is much faster than parse for large numeric Arrays.
+/// Numbers are appended to 'out', and those that are too large for an f64 are
+/// infinity, like number_f64. Any other value, including a nested Array, is an
+/// error at its offset. On error, 'out' is truncated back to its original
+/// length.
+pub fn parse_number_array(
+    json: &[u8],
+    start: usize,
+    end: usize,
+    out: &mut Vec<f64>,
+) -> Result<(), ParseError> {
+    let len = out.len();
+    let ret = number_array(&json[..end], start, out);
+    if let Err(i) = ret {
+        out.truncate(len);
+        let kind = if i >= end {
+            ErrorKind::UnexpectedEof
+        } else {
+            ErrorKind::Syntax
+        };
+        return Err(ParseError::new(kind, i));
+    }
+    Ok(())
+}
+
+// number_array does the work of parse_number_array, with the json cut at the
+// end of the range. The error is the offset where the scan failed.
+fn number_array(json: &[u8], start: usize, out: &mut Vec<f64>) -> Result<(), usize> {
+    let mut i = skipws(json, start, 0);
+    if i == json.len() || json[i] != b'[' {
+        return Err(i);
+    }
+    i = skipws(json, i + 1, 0);
+    if i < json.len() && json[i] == b']' {
+        i += 1;
+    } else {
+        loop {
+            if i == json.len() || (json[i] != b'-' && !isnum(json[i])) {
+                return Err(i);
+            }
+            let (end, _, ok, _) = vnumber(json, i + 1, 0);
+            if !ok {
+                return Err(end);
+            }
+            out.push(number_f64(json, i, end).ok_or(i)?);
+            i = skipws(json, end, 0);
+            if i == json.len() {
+                return Err(i);
+            }
+            if json[i] == b']' {
+                i += 1;
+                break;
+            }
+            if json[i] != b',' {
+                return Err(i);
+            }
+            i = skipws(json, i + 1, 0);
+        }
+    }
+    i = skipws(json, i, 0);
+    if i < json.len() {
+        return Err(i);
+    }
+    Ok(())
+}
+
+// Write significant digits in the format of the ECMAScript
+// Number.prototype.toString function, where 'n' is the position of the
+// decimal point relative to the first digit.
+fn write_es_digits(digits: &[u8], n: i64, out: &mut Vec<u8>) {
+    let k = digits.len() as i64;
+    if k <= n && n <= 21 {
+        out.extend_from_slice(digits);
+        out.resize(out.len() + (n - k) as usize, b'0');
+    } else if 0 < n && n <= 21 {
+        out.extend_from_slice(&digits[..n as usize]);
+        out.push(b'.');
+        out.extend_from_slice(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        out.extend_from_slice(b"0.");
+        out.resize(out.len() + (-n) as usize, b'0');
+        out.extend_from_slice(digits);
+    } else {
+        out.push(digits[0]);
+        if k > 1 {
+            out.push(b'.');
+            out.extend_from_slice(&digits[1..]);
+        }
+        out.push(b'e');
+        if n > 0 {
+            out.push(b'+');
+        }
+        out.extend_from_slice((n - 1).to_string().as_bytes());
+    }
+}
+
+// Break a number into its sign, significant digits (without leading or
+// trailing zeros), and the decimal exponent that applies to the last digit.
+// Zero has no significant digits.
+fn decompose(num: &[u8]) -> Option<(bool, Vec<u8>, i64)> {
+    let mut i = 0;
+    let neg = num.first() == Some(&b'-');
+    if neg {
+        i += 1;
+    }
+    let mut digits = Vec::new();
+    let mut exp: i64 = 0;
+    let mut frac = false;
+    while i < num.len() {
+        match num[i] {
+            b'0'..=b'9' => {
+                if !digits.is_empty() || num[i] != b'0' {
+                    digits.push(num[i]);
+                }
+                if frac {
+                    exp -= 1;
+                }
+            }
+            b'.' => frac = true,
+            b'e' | b'E' => break,
+            _ => return None,
+        }
+        i += 1;
+    }
+    if i < num.len() {
+        let e: i64 = std::str::from_utf8(&num[i + 1..])
+            .ok()?
+            .trim_start_matches('+')
+            .parse()
+            .ok()?;
+        exp = exp.checked_add(e)?;
+    }
+    while digits.last() == Some(&b'0') {
+        digits.pop();
+        exp += 1;
+    }
+    Some((neg, digits, exp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f64_of(num: &str) -> Option<f64> {
+        number_f64(num.as_bytes(), 0, num.len())
+    }
+
+    fn exact(num: &str) -> bool {
+        number_is_exact_f64(num.as_bytes(), 0, num.len())
+    }
+
+    #[test]
+    fn number_array() {
+        let json = br#"{"coordinates": [ [-122.4194, 37.7749], [1e2,-0,3] ]}"#;
+        let mut out = Vec::new();
+        let (token, _) = parse_value(&json[18..]).unwrap();
+        let (start, end) = (18 + token.start, 18 + token.end);
+        parse_number_array(json, start, end, &mut out).unwrap();
+        parse_number_array(json, 40, 50, &mut out).unwrap();
+        assert_eq!(out, [-122.4194, 37.7749, 100.0, -0.0, 3.0]);
+        let err = parse_number_array(json, 16, 52, &mut out).unwrap_err();
+        assert_eq!((err.offset, out.len()), (18, 5));
+        out.clear();
+        for json in [&b"[]"[..], b" [ ] ", b"[1]", b"\t[1 , 2]\n"] {
+            parse_number_array(json, 0, json.len(), &mut out).unwrap();
+        }
+        assert_eq!(out, [1.0, 1.0, 2.0]);
+        let err = |json: &[u8]| {
+            let mut out = vec![9.0];
+            let err = parse_number_array(json, 0, json.len(), &mut out).unwrap_err();
+            assert_eq!(out, [9.0]);
+            (err.kind, err.offset)
+        };
+        assert_eq!(err(b"[1,[2]]"), (ErrorKind::Syntax, 3));
+        assert_eq!(err(b"[1,\"2\"]"), (ErrorKind::Syntax, 3));
+        assert_eq!(err(b"[1,]"), (ErrorKind::Syntax, 3));
+        assert_eq!(err(b"[1 2]"), (ErrorKind::Syntax, 3));
+        assert_eq!(err(b"[01]"), (ErrorKind::Syntax, 2));
+        assert_eq!(err(b"[1.]"), (ErrorKind::Syntax, 3));
+        assert_eq!(err(b"[1] x"), (ErrorKind::Syntax, 4));
+        assert_eq!(err(b"{}"), (ErrorKind::Syntax, 0));
+        assert_eq!(err(b"[1,2"), (ErrorKind::UnexpectedEof, 4));
+        assert_eq!(err(b"[1,-"), (ErrorKind::UnexpectedEof, 4));
+        assert_eq!(err(b""), (ErrorKind::UnexpectedEof, 0));
+    }
+
+    #[test]
+    fn to_f64() {
+        assert_eq!(f64_of("0"), Some(0.0));
+        assert_eq!(f64_of("-12.5e2"), Some(-1250.0));
+        assert_eq!(f64_of("1E+2"), Some(100.0));
+        assert_eq!(f64_of("1e999"), Some(f64::INFINITY));
+        assert_eq!(f64_of("true"), None);
+        let json = br#"{"a":[1.5]}"#;
+        assert_eq!(number_f64(json, 6, 9), Some(1.5));
+    }
+
+    #[test]
+    fn exactness() {
+        for num in [
+            "0",
+            "-0",
+            "0.0e10",
+            "1",
+            "-1",
+            "100",
+            "1e2",
+            "1.5",
+            "0.1",
+            "123.456",
+            "1E-300",
+            "9007199254740992",
+            "-9007199254740992",
+            "18014398509481984",
+            "1e20",
+            "0.30000000000000004",
+            "1.7976931348623157e308",
+        ] {
+            assert!(exact(num), "{}", num);
+        }
+        for num in [
+            "9007199254740993",
+            "-9007199254740993",
+            "12345678901234567890",
+            "0.123456789012345678",
+            "0.30000000000000001",
+            "1e400",
+            "-1e400",
+            "1e-400",
+            "2.5e-324",
+        ] {
+            assert!(!exact(num), "{}", num);
+        }
+    }
+
+    #[test]
+    fn integers() {
+        let i64_of = |num: &str| number_i64(num.as_bytes(), 0, num.len());
+        let u64_of = |num: &str| number_u64(num.as_bytes(), 0, num.len());
+        assert_eq!(i64_of("0"), Some(0));
+        assert_eq!(i64_of("-0"), Some(0));
+        assert_eq!(i64_of("-42"), Some(-42));
+        assert_eq!(i64_of("9223372036854775807"), Some(i64::MAX));
+        assert_eq!(i64_of("-9223372036854775808"), Some(i64::MIN));
+        assert_eq!(i64_of("9223372036854775808"), None);
+        assert_eq!(i64_of("-9223372036854775809"), None);
+        assert_eq!(i64_of("1.0"), None);
+        assert_eq!(i64_of("1e2"), None);
+        assert_eq!(i64_of("-"), None);
+        assert_eq!(u64_of("18446744073709551615"), Some(u64::MAX));
+        assert_eq!(u64_of("18446744073709551616"), None);
+        assert_eq!(u64_of("-0"), Some(0));
+        assert_eq!(u64_of("-1"), None);
+        assert_eq!(u64_of("12.5"), None);
+        let json = br#"{"a":[123]}"#;
+        assert_eq!(number_i64(json, 6, 9), Some(123));
+        assert_eq!(number_u64(json, 6, 9), Some(123));
+    }
+
+    #[test]
+    fn long_integers() {
+        let mut json = vec![b'['];
+        json.resize(10_001, b'9');
+        json.push(b']');
+        let mut n = 0;
+        let ret = crate::parse(&json, 0, |start: usize, end: usize, info: usize| -> i64 {
+            if info & crate::NUMBER == crate::NUMBER {
+                assert_eq!(number_i64(&json, start, end), None);
+                assert_eq!(number_u64(&json, start, end), None);
+                n += 1;
+            }
+            1
+        });
+        assert_eq!(ret, json.len() as i64);
+        assert_eq!(n, 1);
+        let zeros = "0".repeat(10_000);
+        assert_eq!(number_i64(zeros.as_bytes(), 0, zeros.len()), Some(0));
+    }
+
+    #[test]
+    fn canonical() {
+        let canon = |num: &str| {
+            let json = num.as_bytes();
+            let mut out = String::new();
+            crate::parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+                out = number_canonical(json, start, end, info);
+                1
+            });
+            out
+        };
+        for nums in [
+            &["100", "1e2", "100.0", "1E+2", "0.1e3", "10000e-2"][..],
+            &["0", "-0", "0.0", "0e10", "-0.0E-5"],
+            &["-1.5", "-15e-1", "-0.15E1", "-1.50"],
+            &["1e+21", "1e21", "1000000000000000000000", "10e20"],
+            &["1e-7", "0.0000001", "100e-9"],
+            &["0.000001", "1e-6"],
+            &["123456789012345678901", "1.23456789012345678901e20"],
+        ]
+        .iter()
+        {
+            for num in nums.iter() {
+                assert_eq!(canon(num), canon(nums[0]), "{} {}", num, nums[0]);
+            }
+        }
+        assert_eq!(canon("100.0"), "100");
+        assert_eq!(canon("1e21"), "1e+21");
+        assert_eq!(canon("1e-7"), "1e-7");
+        assert_eq!(canon("0.000001"), "0.000001");
+        assert_eq!(canon("-12.50e-1"), "-1.25");
+        // no rounding beyond the precision or range of an f64
+        assert_eq!(
+            canon("12345678901234567890123"),
+            "1.2345678901234567890123e+22"
+        );
+        assert_ne!(canon("9007199254740993"), canon("9007199254740992"));
+        assert_eq!(canon("1e400"), "1e+400");
+        assert_eq!(canon("1e99999999999999999999"), "1e99999999999999999999");
+    }
+}
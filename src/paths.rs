@@ -0,0 +1,239 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+
+struct Frame {
+    // the length of the path of the container
+    len: usize,
+    // the next index, for arrays
+    index: Option<usize>,
+}
+
+/// Parse JSON and call 'iter' once for every scalar value with the path of the
+/// value from the root, such as "friends.0.first". This is the core of
+/// flattening a document into key paths.
+///
+/// The path is made of the decoded Object keys and the Array indexes, joined
+/// by '.'. A scalar root has an empty path. Keys are not escaped, so a key
+/// that contains a '.' is indistinguishable from two keys. The 'start', 'end',
+/// and 'info' params are the same as those of parse, and so is the return
+/// value of 'iter', except that returning -1 works like returning 1.
+pub fn parse_paths<F>(json: &[u8], opts: usize, iter: F) -> i64
+where
+    F: FnMut(&str, usize, usize, usize) -> i64,
+{
+    let mut iter = iter;
+    let mut path = String::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let opts = opts & !EVENT_OPTS;
+    parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        if info & (COMMA | COLON) != 0 {
+            return 1;
+        }
+        if info & CLOSE == CLOSE {
+            stack.pop();
+            return 1;
+        }
+        if let Some(frame) = stack.last_mut() {
+            // the path of a member is set by its key
+            if info & KEY == KEY || frame.index.is_some() {
+                path.truncate(frame.len);
+                if frame.len > 0 {
+                    path.push('.');
+                }
+            }
+            if info & KEY == KEY {
+                path.push_str(&unescape(json, start, end));
+                return 1;
+            }
+            if let Some(index) = frame.index.as_mut() {
+                path.push_str(&index.to_string());
+                *index += 1;
+            }
+        }
+        if info & OPEN == OPEN {
+            stack.push(Frame {
+                len: path.len(),
+                index: if info & ARRAY == ARRAY { Some(0) } else { None },
+            });
+            return 1;
+        }
+        match iter(&path, start, end, info) {
+            0 => 0,
+            _ => 1,
+        }
+    })
+}
+
+/// Find the first error in JSON and return the path of where it is, along
+/// with its offset. Returns None when the document is valid.
+///
+/// The path is made in the same way as for parse_paths. It is the path of the
+/// value that was being parsed when the error was found, such as
+/// "friends.2.nets" for an error in the value of that member, or "friends.3"
+/// for an error in the fourth element of the "friends" Array. An error that is
+/// not inside of a value, such as a missing key or a missing comma, has the
+/// path of the container that it is in.
+///
+/// ```
+/// let json = br#"{"friends": [1, 2, {"nets": [x]}]}"#;
+/// assert_eq!(pjson::error_path(json, 0), Some(("friends.2.nets.0".into(), 29)));
+/// ```
+pub fn error_path(json: &[u8], opts: usize) -> Option<(String, usize)> {
+    let mut path = String::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    // true when the path is of a value that has not been parsed yet, and
+    // false when it is of a value that is complete
+    let mut in_value = true;
+    let opts = opts & !EVENT_OPTS;
+    let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        if info & CLOSE == CLOSE {
+            let frame = stack.pop().unwrap();
+            path.truncate(frame.len);
+            in_value = false;
+            return 1;
+        }
+        if info & OPEN == OPEN {
+            let index = if info & ARRAY == ARRAY { Some(0) } else { None };
+            stack.push(Frame {
+                len: path.len(),
+                index,
+            });
+            in_value = false;
+        }
+        let frame = match stack.last_mut() {
+            Some(frame) => frame,
+            None => return 1,
+        };
+        if info & KEY == KEY {
+            push_component(&mut path, frame.len, &unescape(json, start, end));
+            in_value = true;
+        } else if info & COMMA == COMMA || info & (OPEN | ARRAY) == OPEN | ARRAY {
+            if let Some(index) = frame.index.as_mut() {
+                if info & COMMA == COMMA {
+                    *index += 1;
+                }
+                push_component(&mut path, frame.len, &index.to_string());
+                in_value = true;
+            } else {
+                path.truncate(frame.len);
+                in_value = false;
+            }
+        } else if info & COLON != COLON && info & OPEN != OPEN {
+            in_value = false;
+        }
+        1
+    });
+    if ret > 0 {
+        return None;
+    }
+    if !in_value {
+        if let Some(frame) = stack.last() {
+            path.truncate(frame.len);
+        }
+    }
+    Some((path, (-ret) as usize))
+}
+
+// push_component replaces the path after 'len' with a component
+fn push_component(path: &mut String, len: usize, component: &str) {
+    path.truncate(len);
+    if len > 0 {
+        path.push('.');
+    }
+    path.push_str(component);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flatten(json: &[u8]) -> Vec<String> {
+        let mut out = Vec::new();
+        let ret = parse_paths(
+            json,
+            0,
+            |path: &str, start: usize, end: usize, _: usize| -> i64 {
+                out.push(format!(
+                    "{} = {}",
+                    path,
+                    std::str::from_utf8(&json[start..end]).unwrap()
+                ));
+                1
+            },
+        );
+        assert_eq!(ret, json.len() as i64);
+        out
+    }
+
+    #[test]
+    fn paths() {
+        let json = br#"{
+          "name": {"first": "Tom", "last": "Anderson"},
+          "age": 37,
+          "children": ["Sara", "Alex"],
+          "friends": [{"first": "Dale", "nets": ["ig", "fb"]}, {}, []],
+          "e\u0073c": [[1, 2], [3]],
+          "": true
+        }"#;
+        assert_eq!(
+            flatten(json),
+            [
+                r#"name.first = "Tom""#,
+                r#"name.last = "Anderson""#,
+                "age = 37",
+                r#"children.0 = "Sara""#,
+                r#"children.1 = "Alex""#,
+                r#"friends.0.first = "Dale""#,
+                r#"friends.0.nets.0 = "ig""#,
+                r#"friends.0.nets.1 = "fb""#,
+                "esc.0.0 = 1",
+                "esc.0.1 = 2",
+                "esc.1.0 = 3",
+                " = true",
+            ]
+        );
+        assert_eq!(flatten(b" 1 "), [" = 1"]);
+        assert_eq!(flatten(b"[1,[2]]"), ["0 = 1", "1.0 = 2"]);
+    }
+
+    #[test]
+    fn stop() {
+        let json = br#"{"a":[1,2,3],"b":4}"#;
+        let mut paths = Vec::new();
+        let ret = parse_paths(json, 0, |path: &str, _, _, _| -> i64 {
+            paths.push(path.to_string());
+            if path == "a.1" {
+                0
+            } else {
+                -1
+            }
+        });
+        assert!(ret > 0 && (ret as usize) < json.len());
+        assert_eq!(paths, ["a.0", "a.1"]);
+        assert!(parse_paths(br#"{"a":[1,}"#, 0, |_, _, _, _| 1) <= 0);
+    }
+
+    #[test]
+    fn errors() {
+        let path = |json: &[u8]| error_path(json, 0);
+        let at = |path: &str, offset: usize| Some((path.to_string(), offset));
+        assert_eq!(path(br#"{"a":[1,{"b":true}]}"#), None);
+        assert_eq!(path(b" 1 "), None);
+        let json = br#"{"friends": [1, 2, {"nets": x}]}"#;
+        assert_eq!(path(json), at("friends.2.nets", 28));
+        assert_eq!(path(br#"{"a":[1,2,"#), at("a.2", 10));
+        assert_eq!(path(br#"{"a":[1 2]}"#), at("a", 8));
+        assert_eq!(path(br#"{"a":[[1],]}"#), at("a.1", 10));
+        assert_eq!(path(br#"{"a":{"b":1,}}"#), at("a", 12));
+        assert_eq!(path(br#"{"a":{"b" 1}}"#), at("a.b", 10));
+        assert_eq!(path(br#"{"a":{"b":[]}"#), at("", 13));
+        assert_eq!(path(br#"{"a":1} x"#), at("", 8));
+        assert_eq!(path(br#"[1,"#), at("1", 3));
+        assert_eq!(path(br#"{"e\u0073c":[tru]}"#), at("esc.0", 14));
+        assert_eq!(path(b"x"), at("", 0));
+        assert_eq!(path(b""), at("", 0));
+    }
+}
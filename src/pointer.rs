@@ -0,0 +1,293 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::borrow::Cow;
+
+// split a JSON Pointer into its decoded reference tokens
+fn tokens(pointer: &str) -> Option<Vec<Cow<'_, str>>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    if !pointer.starts_with('/') {
+        return None;
+    }
+    let mut toks = Vec::new();
+    for tok in pointer[1..].split('/') {
+        if !tok.contains('~') {
+            toks.push(Cow::Borrowed(tok));
+            continue;
+        }
+        let mut s = String::with_capacity(tok.len());
+        let mut chars = tok.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '~' {
+                s.push(ch);
+                continue;
+            }
+            match chars.next() {
+                Some('0') => s.push('~'),
+                Some('1') => s.push('/'),
+                _ => return None,
+            }
+        }
+        toks.push(Cow::Owned(s));
+    }
+    Some(toks)
+}
+
+// returns true if the reference token is the array index
+fn index_matches(tok: &str, index: usize) -> bool {
+    let b = tok.as_bytes();
+    if b.is_empty() || !b.iter().all(|ch| ch.is_ascii_digit()) || (b.len() > 1 && b[0] == b'0') {
+        return false;
+    }
+    tok.parse() == Ok(index)
+}
+
+struct Frame {
+    array: bool,
+    index: usize,
+    // the last key of the object matched the reference token
+    key: bool,
+}
+
+// A child of a container, as seen by the matcher of walk.
+pub(crate) enum Child<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+// Walk the json along a path of 'len' steps, returning the ranges and info
+// bits of the values at the end of the path. The info is that of the first
+// element of the value, without the OPEN bit. The 'step' function returns
+// true when a child matches the step at a depth. Containers that are not on
+// the path are skipped. When not 'all', the walk stops at the first match.
+// When 'exact', each step matches at most one child that is worth looking
+// into, so the walk also stops as soon as the path cannot be matched anymore.
+pub(crate) fn walk<M>(
+    json: &[u8],
+    len: usize,
+    step: M,
+    all: bool,
+    exact: bool,
+) -> Vec<(usize, usize, usize)>
+where
+    M: Fn(usize, Child) -> bool,
+{
+    let mut results: Vec<(usize, usize, usize)> = Vec::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut skipping = false;
+    let mut matched = false;
+    let ret = parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+        if info & (COMMA | COLON) != 0 {
+            return 1;
+        }
+        if info & CLOSE == CLOSE {
+            if skipping {
+                // the close of a skipped container
+                skipping = false;
+                if matched {
+                    matched = false;
+                    results.last_mut().unwrap().1 = end;
+                    if !all {
+                        return 0;
+                    }
+                }
+                return 1;
+            }
+            stack.pop();
+            // the target can only be inside of the container that was just
+            // closed, so there is nothing more to find
+            return if all || !exact { 1 } else { 0 };
+        }
+        let depth = stack.len();
+        if info & KEY == KEY {
+            let frame = stack.last_mut().unwrap();
+            frame.key = step(depth - 1, Child::Key(&unescape(json, start, end)));
+            return 1;
+        }
+        let hit = match stack.last_mut() {
+            None => true,
+            Some(frame) if frame.array => {
+                frame.index += 1;
+                step(depth - 1, Child::Index(frame.index - 1))
+            }
+            Some(frame) => frame.key,
+        };
+        if info & OPEN == OPEN {
+            if hit && depth == len {
+                results.push((start, end, info & !OPEN));
+                matched = true;
+                skipping = true;
+                return -1;
+            }
+            if hit {
+                stack.push(Frame {
+                    array: info & ARRAY == ARRAY,
+                    index: 0,
+                    key: false,
+                });
+                return 1;
+            }
+            skipping = true;
+            return -1;
+        }
+        if hit && depth == len {
+            results.push((start, end, info));
+            if !all {
+                return 0;
+            }
+        }
+        1
+    });
+    if ret <= 0 {
+        results.clear();
+    }
+    results
+}
+
+/// Get the value that a JSON Pointer (RFC 6901) refers to, such as
+/// "/friends/0/first". Returns the byte range of the value, such that
+/// json[start..end] is the complete value, or None if the pointer does not
+/// refer to a value or is malformed, or the json is invalid.
+///
+/// Parsing stops as soon as the value is found, so the json after the value
+/// is not validated, and containers that are not on the path are skipped
+/// over quickly. When an Object has duplicate keys the first one is used.
+pub fn get_pointer(json: &[u8], pointer: &str) -> Option<(usize, usize)> {
+    let (start, end, _) = get_pointer_info(json, pointer)?;
+    Some((start, end))
+}
+
+/// Same as get_pointer but also returns the info bits of the value, such as
+/// STRING or OBJECT, so that its type is known without looking at it again.
+/// The info bits are those of the value's first element, as passed to the
+/// iter function of parse, but without OPEN.
+pub fn get_pointer_info(json: &[u8], pointer: &str) -> Option<(usize, usize, usize)> {
+    walk_pointer(json, pointer, false).pop()
+}
+
+/// Same as get_pointer but returns every value that the pointer refers to,
+/// in the order that they appear in the json. There can be more than one
+/// only when Objects on the path have duplicate keys. The whole json is
+/// validated, and an empty list is returned if it is invalid.
+pub fn get_pointer_all(json: &[u8], pointer: &str) -> Vec<(usize, usize)> {
+    walk_pointer(json, pointer, true)
+        .into_iter()
+        .map(|(start, end, _)| (start, end))
+        .collect()
+}
+
+fn walk_pointer(json: &[u8], pointer: &str, all: bool) -> Vec<(usize, usize, usize)> {
+    let toks = match tokens(pointer) {
+        Some(toks) => toks,
+        None => return Vec::new(),
+    };
+    let step = |depth: usize, child: Child| match child {
+        Child::Key(key) => key == toks[depth],
+        Child::Index(index) => index_matches(&toks[depth], index),
+    };
+    walk(json, toks.len(), step, all, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &[u8] = br#"{
+      "name": {"first": "Tom", "last": "Anderson"},
+      "children": ["Sara", "Alex", "Jack"],
+      "a/b": 1, "m~n": 2, "": 3, "e\u0073c": 4,
+      "friends": [{"first": "Dale", "nets": ["ig", "fb"]}, {"first": "Roger"}]
+    }"#;
+
+    fn get(json: &[u8], pointer: &str) -> Option<String> {
+        let (start, end) = get_pointer(json, pointer)?;
+        Some(String::from_utf8(json[start..end].to_vec()).unwrap())
+    }
+
+    #[test]
+    fn pointers() {
+        assert_eq!(
+            get(JSON, ""),
+            Some(String::from_utf8(JSON.to_vec()).unwrap())
+        );
+        assert_eq!(get(JSON, "/name/last").as_deref(), Some(r#""Anderson""#));
+        assert_eq!(
+            get(JSON, "/children").as_deref(),
+            Some(r#"["Sara", "Alex", "Jack"]"#)
+        );
+        assert_eq!(get(JSON, "/children/2").as_deref(), Some(r#""Jack""#));
+        assert_eq!(get(JSON, "/a~1b").as_deref(), Some("1"));
+        assert_eq!(get(JSON, "/m~0n").as_deref(), Some("2"));
+        assert_eq!(get(JSON, "/").as_deref(), Some("3"));
+        assert_eq!(get(JSON, "/esc").as_deref(), Some("4"));
+        assert_eq!(get(JSON, "/friends/0/nets/1").as_deref(), Some(r#""fb""#));
+        assert_eq!(
+            get(JSON, "/friends/1").as_deref(),
+            Some(r#"{"first": "Roger"}"#)
+        );
+        for pointer in [
+            "/children/3",
+            "/children/01",
+            "/children/-",
+            "/name/first/x",
+            "/nope",
+            "name",
+            "/m~2n",
+        ]
+        .iter()
+        {
+            assert_eq!(get(JSON, pointer), None, "{}", pointer);
+        }
+        assert_eq!(get(br#"{"a":1,}"#, "/b"), None);
+    }
+
+    #[test]
+    fn early_stop() {
+        // the json after the match is never looked at
+        let json = br#"{"a":{"b":[1,2]},"c":"#;
+        assert_eq!(get(json, "/a/b/1").as_deref(), Some("2"));
+        assert_eq!(get(json, "/a").as_deref(), Some(r#"{"b":[1,2]}"#));
+        assert_eq!(get(json, "/a/c"), None);
+        assert!(get_pointer_all(json, "/a").is_empty());
+        let json = br#"[{"id":1},{"id":2},{"id":3},{"id":4}]"#;
+        assert_eq!(get(json, "/1/id").as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn all() {
+        let json = br#"{"a":{"b":1,"b":[2]},"a":{"b":3},"b":4}"#;
+        let all: Vec<_> = get_pointer_all(json, "/a/b")
+            .into_iter()
+            .map(|(start, end)| &json[start..end])
+            .collect();
+        assert_eq!(all, [&b"1"[..], b"[2]", b"3"]);
+        assert_eq!(get(json, "/a/b").as_deref(), Some("1"));
+        assert_eq!(get_pointer_all(json, "").len(), 1);
+        assert!(get_pointer_all(json, "/c").is_empty());
+    }
+
+    #[test]
+    fn info() {
+        let info = |pointer: &str| get_pointer_info(JSON, pointer).map(|(_, _, info)| info);
+        assert_eq!(info("/name/first"), Some(VALUE | STRING));
+        assert_eq!(info("/e\u{73}c"), Some(VALUE | NUMBER));
+        assert_eq!(info("/children"), Some(VALUE | ARRAY));
+        assert_eq!(info("/friends/0"), Some(VALUE | OBJECT));
+        assert_eq!(info(""), Some(START | OBJECT));
+        assert_eq!(info("/missing"), None);
+        let (start, end, _) = get_pointer_info(JSON, "/name").unwrap();
+        assert_eq!(Some((start, end)), get_pointer(JSON, "/name"));
+        assert_eq!(
+            get_pointer_info(br#" -1.5 "#, ""),
+            Some((1, 5, START | END | NUMBER | SIGN | DOT))
+        );
+        assert_eq!(
+            get_pointer_info(br#"{"a":"\n"}"#, "/a"),
+            Some((5, 9, VALUE | STRING | ESCAPED))
+        );
+    }
+}
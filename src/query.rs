@@ -0,0 +1,303 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::borrow::Cow;
+
+enum Sel {
+    Name(String),
+    Index(usize),
+    Any,
+}
+
+struct Step {
+    descend: bool,
+    sel: Sel,
+}
+
+impl Step {
+    fn matches(&self, key: Option<&str>, index: Option<usize>) -> bool {
+        match &self.sel {
+            Sel::Name(name) => key == Some(name.as_str()),
+            Sel::Index(n) => index == Some(*n),
+            Sel::Any => true,
+        }
+    }
+}
+
+// compile a path into steps, returning None if the path is not supported
+fn compile(path: &str) -> Option<Vec<Step>> {
+    let path = path.as_bytes();
+    if path.is_empty() || path[0] != b'$' {
+        return None;
+    }
+    let mut steps = Vec::new();
+    let mut i = 1;
+    while i < path.len() {
+        let mut descend = false;
+        if path[i] == b'.' {
+            i += 1;
+            if i < path.len() && path[i] == b'.' {
+                descend = true;
+                i += 1;
+            }
+            if i < path.len() && path[i] == b'[' {
+                if !descend {
+                    return None;
+                }
+            } else {
+                let mark = i;
+                while i < path.len() && path[i] != b'.' && path[i] != b'[' {
+                    i += 1;
+                }
+                let name = std::str::from_utf8(&path[mark..i]).ok()?;
+                let sel = match name {
+                    "" => return None,
+                    "*" => Sel::Any,
+                    _ => Sel::Name(name.to_string()),
+                };
+                steps.push(Step { descend, sel });
+                continue;
+            }
+        }
+        if path[i] != b'[' {
+            return None;
+        }
+        let close = i + path[i..].iter().position(|&ch| ch == b']')?;
+        let inner = &path[i + 1..close];
+        let sel = if inner == b"*" {
+            Sel::Any
+        } else if inner.len() >= 2 && inner[0] == b'\'' && inner[inner.len() - 1] == b'\'' {
+            let name = &inner[1..inner.len() - 1];
+            if name.contains(&b'\'') {
+                return None;
+            }
+            Sel::Name(std::str::from_utf8(name).ok()?.to_string())
+        } else if !inner.is_empty() && inner.iter().all(|ch| ch.is_ascii_digit()) {
+            Sel::Index(std::str::from_utf8(inner).ok()?.parse().ok()?)
+        } else {
+            return None;
+        };
+        steps.push(Step { descend, sel });
+        i = close + 1;
+    }
+    Some(steps)
+}
+
+struct Frame<'a> {
+    // the steps that have been matched so far by the paths that are still
+    // alive in this container
+    states: Vec<usize>,
+    array: bool,
+    index: usize,
+    key: Option<Cow<'a, str>>,
+    // position in the results of this container, when it matched the path
+    result: Option<usize>,
+}
+
+/// Query JSON with a small subset of JSONPath, returning the byte ranges of
+/// the matching values in the order that they appear in the json.
+///
+/// The path must start with '$' and may be followed by any number of:
+///
+/// - `.name` or `['name']` for the member of an Object,
+/// - `.*` or `[*]` for every member of an Object or element of an Array,
+/// - `[n]` for the element of an Array at index n,
+/// - `..name`, `..*`, or `..[n]` for a recursive descent that matches at any
+///   depth.
+///
+/// Names are compared to the decoded Object keys, and may not contain escape
+/// sequences or quotes. Filters, slices, unions, negative indexes, and
+/// scripts are not supported. An unsupported path or invalid json returns an
+/// empty list. Branches that cannot match the path are skipped over quickly.
+pub fn query(json: &[u8], path: &str) -> Vec<(usize, usize)> {
+    find(json, path, false)
+}
+
+/// Same as query but only returns the first matching value. Parsing stops as
+/// soon as the value is found, so the json after the value is not validated.
+pub fn query_first(json: &[u8], path: &str) -> Option<(usize, usize)> {
+    find(json, path, true).pop()
+}
+
+fn find(json: &[u8], path: &str, first: bool) -> Vec<(usize, usize)> {
+    let steps = match compile(path) {
+        Some(steps) => steps,
+        None => return Vec::new(),
+    };
+    let mut results: Vec<(usize, usize)> = Vec::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let ret = parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+        if info & (COMMA | COLON) != 0 {
+            return 1;
+        }
+        if info & KEY == KEY {
+            stack.last_mut().unwrap().key = Some(unescape(json, start, end));
+            return 1;
+        }
+        if info & CLOSE == CLOSE {
+            let frame = stack.pop().unwrap();
+            if let Some(i) = frame.result {
+                results[i].1 = end;
+                if first {
+                    return 0;
+                }
+            }
+            return 1;
+        }
+        let mut states = Vec::new();
+        match stack.last_mut() {
+            None => states.push(0),
+            Some(parent) => {
+                let key = parent.key.take();
+                let index = if parent.array {
+                    parent.index += 1;
+                    Some(parent.index - 1)
+                } else {
+                    None
+                };
+                for &s in &parent.states {
+                    let step = &steps[s];
+                    if step.descend && !states.contains(&s) {
+                        states.push(s);
+                    }
+                    if step.matches(key.as_deref(), index) && !states.contains(&(s + 1)) {
+                        states.push(s + 1);
+                    }
+                }
+            }
+        }
+        let matched = states.contains(&steps.len());
+        if info & OPEN == OPEN {
+            let result = if matched {
+                results.push((start, end));
+                Some(results.len() - 1)
+            } else {
+                None
+            };
+            states.retain(|&s| s < steps.len());
+            // the first match needs only its end, so its children are skipped
+            let live = !(states.is_empty() || first && matched);
+            stack.push(Frame {
+                states,
+                array: info & ARRAY == ARRAY,
+                index: 0,
+                key: None,
+                result,
+            });
+            return if live { 1 } else { -1 };
+        }
+        if matched {
+            results.push((start, end));
+            if first {
+                return 0;
+            }
+        }
+        1
+    });
+    if ret <= 0 {
+        results.clear();
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &[u8] = br#"{
+      "name": {"first": "Tom", "last": "Anderson"},
+      "age": 37,
+      "children": ["Sara", "Alex", "Jack"],
+      "fav.movie": "Deer Hunter",
+      "friends": [
+        {"first": "Dale", "last": "Murphy", "nets": ["ig", "fb"]},
+        {"first": "Roger", "last": "Craig", "nets": ["fb", "tw"]},
+        {"first": "Jane", "last": "Murphy", "nets": ["ig", "tw"]}
+      ]
+    }"#;
+
+    fn q(json: &[u8], path: &str) -> Vec<String> {
+        query(json, path)
+            .into_iter()
+            .map(|(start, end)| String::from_utf8(json[start..end].to_vec()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn children() {
+        assert_eq!(q(JSON, "$.age"), ["37"]);
+        assert_eq!(q(JSON, "$.name.last"), [r#""Anderson""#]);
+        assert_eq!(q(JSON, "$['fav.movie']"), [r#""Deer Hunter""#]);
+        assert_eq!(q(JSON, "$.children[1]"), [r#""Alex""#]);
+        assert_eq!(q(JSON, "$.children[3]"), Vec::<String>::new());
+        assert_eq!(
+            q(JSON, "$.friends[*].first"),
+            [r#""Dale""#, r#""Roger""#, r#""Jane""#]
+        );
+        assert_eq!(q(JSON, "$.friends[2].nets[0]"), [r#""ig""#]);
+        assert_eq!(q(JSON, "$.name.*"), [r#""Tom""#, r#""Anderson""#]);
+        assert_eq!(q(br#"[1,[2,3]]"#, "$[1]"), ["[2,3]"]);
+        assert_eq!(q(br#" 1 "#, "$"), ["1"]);
+        assert_eq!(q(br#"{"a":{}}"#, "$"), [r#"{"a":{}}"#]);
+    }
+
+    #[test]
+    fn descend() {
+        assert_eq!(
+            q(JSON, "$..first"),
+            [r#""Tom""#, r#""Dale""#, r#""Roger""#, r#""Jane""#]
+        );
+        assert_eq!(
+            q(JSON, "$.friends..nets[1]"),
+            [r#""fb""#, r#""tw""#, r#""tw""#]
+        );
+        assert_eq!(q(JSON, "$..[0]").len(), 5);
+        let json = br#"{"a":{"a":{"b":1}},"c":[{"a":2}]}"#;
+        assert_eq!(q(json, "$..a"), [r#"{"a":{"b":1}}"#, r#"{"b":1}"#, "2"]);
+        assert_eq!(q(json, "$..a.b"), ["1"]);
+        assert_eq!(q(json, "$..*").len(), 6);
+    }
+
+    #[test]
+    fn unsupported() {
+        for path in [
+            "",
+            "a",
+            "$.",
+            "$[",
+            "$[-1]",
+            "$[0:2]",
+            "$[?(@.a)]",
+            "$[0,1]",
+            "$.[0]",
+            "$...a",
+        ]
+        .iter()
+        {
+            assert!(compile(path).is_none(), "{}", path);
+            assert!(query(JSON, path).is_empty());
+        }
+        assert!(query(br#"{"a":1,}"#, "$.a").is_empty());
+    }
+
+    #[test]
+    fn first() {
+        let first = |json: &[u8], path: &str| {
+            let (start, end) = query_first(json, path)?;
+            Some(String::from_utf8(json[start..end].to_vec()).unwrap())
+        };
+        assert_eq!(
+            first(JSON, "$.friends[*].first").as_deref(),
+            Some(r#""Dale""#)
+        );
+        assert_eq!(first(JSON, "$..nets").as_deref(), Some(r#"["ig", "fb"]"#));
+        assert_eq!(first(JSON, "$.nope"), None);
+        // the json after the match is never looked at
+        let json = br#"{"a":[{"b":1},{"b":2}],"c":"#;
+        assert_eq!(first(json, "$..b").as_deref(), Some("1"));
+        assert_eq!(first(json, "$.a").as_deref(), Some(r#"[{"b":1},{"b":2}]"#));
+        assert!(query(json, "$..b").is_empty());
+    }
+}
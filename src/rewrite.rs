@@ -0,0 +1,260 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+/// Parse JSON and write it to 'w', allowing for the Keys and scalar Values
+/// to be replaced along the way.
+///
+/// The transform function is called for every Key, String, Number, True,
+/// False, and Null element with the element's info bits and bytes. Returning
+/// Some replaces the element with the provided bytes, which must be valid JSON
+/// for that position. Returning None keeps the original bytes.
+///
+/// The structural characters (brackets, commas, and colons) are written by
+/// the rewriter and all insignificant whitespace is removed, so the output is
+/// a minified form of the input. An empty element, such as from the
+/// EMPTY_SLOTS option, is passed to the transform function as a null.
+///
+/// The return value has the same meaning as for parse. When the json is
+/// invalid, the output written up until the error is left in 'w'. Any error
+/// from writing stops the parsing and is returned.
+pub fn rewrite<W, F>(json: &[u8], opts: usize, w: &mut W, transform: F) -> io::Result<i64>
+where
+    W: Write,
+    F: FnMut(usize, &[u8]) -> Option<Cow<'_, [u8]>>,
+{
+    let mut transform = transform;
+    let mut err = None;
+    let opts = opts & !EVENT_OPTS;
+    let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        let bytes = if start == end && info & NULL == NULL {
+            &b"null"[..]
+        } else {
+            &json[start..end]
+        };
+        let res = if info & (OPEN | CLOSE | COMMA | COLON) != 0 {
+            w.write_all(bytes)
+        } else {
+            match transform(info, bytes) {
+                Some(bytes) => w.write_all(&bytes),
+                None => w.write_all(bytes),
+            }
+        };
+        match res {
+            Ok(()) => 1,
+            Err(e) => {
+                err = Some(e);
+                0
+            }
+        }
+    });
+    match err {
+        Some(e) => Err(e),
+        None => Ok(ret),
+    }
+}
+
+/// The layout of the output of normalize_to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Style {
+    /// no whitespace at all
+    Minified,
+    /// every member and element on its own line, indented by two spaces for
+    /// each level, with a space after each colon
+    Pretty,
+}
+
+/// Validate JSON and write it to 'w' in a normalized layout, such as for a
+/// gateway that only lets through valid json. The insignificant whitespace is
+/// replaced by the whitespace of the style, while the elements themselves are
+/// written as they are. An empty element, such as from the EMPTY_SLOTS option,
+/// is written as a null.
+///
+/// The output is held in memory until the whole document is found to be
+/// valid, so nothing is written for invalid json, which is an io::Error of the
+/// InvalidData kind that wraps a ParseError. Any error from writing is
+/// returned as it is.
+pub fn normalize_to<W: Write>(json: &[u8], opts: usize, style: Style, w: &mut W) -> io::Result<()> {
+    let mut out = Vec::with_capacity(json.len());
+    let mut depth = 0;
+    // a newline is due before the next member or element
+    let mut newline = false;
+    let pretty = style == Style::Pretty;
+    let opts = opts & !EVENT_OPTS;
+    let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        if info & CLOSE == CLOSE {
+            depth -= 1;
+            if pretty && !newline {
+                out.push(b'\n');
+                out.resize(out.len() + depth * 2, b' ');
+            }
+            newline = false;
+        } else if info & (COMMA | COLON) == 0 && newline {
+            out.push(b'\n');
+            out.resize(out.len() + depth * 2, b' ');
+            newline = false;
+        }
+        if info & COMMA == COMMA {
+            out.push(b',');
+            newline = pretty;
+        } else if info & COLON == COLON {
+            out.extend_from_slice(if pretty { b": " } else { b":" });
+        } else if start == end && info & NULL == NULL {
+            out.extend_from_slice(b"null");
+        } else {
+            out.extend_from_slice(&json[start..end]);
+        }
+        if info & OPEN == OPEN {
+            depth += 1;
+            newline = pretty;
+        }
+        1
+    });
+    if ret <= 0 {
+        let err = ParseError::from_ret(json, ret);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+    }
+    w.write_all(&out)
+}
+
+/// Count the insignificant whitespace in JSON, which is the number of bytes
+/// that a minified form would save. This is every byte between the elements,
+/// while the whitespace inside of Strings is part of the element and is not
+/// counted. The header that is skipped by the CONFIG option and the comment
+/// that is skipped by the PRELUDE option are not counted, nor is the
+/// whitespace before them.
+pub fn whitespace_bytes(json: &[u8], opts: usize) -> Result<usize, ParseError> {
+    let (mut prev, _) = skip_header(json, 0, opts);
+    let mut count = 0;
+    let opts = opts & !EVENT_OPTS;
+    let ret = parse(json, opts, |start: usize, end: usize, _: usize| -> i64 {
+        count += start - prev;
+        prev = end;
+        1
+    });
+    if ret <= 0 {
+        return Err(ParseError::from_ret(json, ret));
+    }
+    Ok(count + json.len() - prev)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redact(json: &[u8]) -> (String, i64) {
+        let mut out = Vec::new();
+        let ret = rewrite(json, 0, &mut out, |info, _| {
+            if info & (STRING | VALUE) == STRING | VALUE {
+                return Some(Cow::Borrowed(br#""***""#));
+            }
+            None
+        })
+        .unwrap();
+        (String::from_utf8(out).unwrap(), ret)
+    }
+
+    #[test]
+    fn redacted() {
+        let json =
+            br#" { "user" : "tom" , "pass" : "secret" , "ids" : [ 1 , "two" , true , null ] } "#;
+        let (out, ret) = redact(json);
+        assert_eq!(ret, json.len() as i64);
+        assert_eq!(
+            out,
+            r#"{"user":"***","pass":"***","ids":[1,"***",true,null]}"#
+        );
+        let (out, ret) = redact(br#"["a", "b" "c"]"#);
+        assert_eq!(ret, -10);
+        assert_eq!(out, r#"["***","***""#);
+    }
+
+    #[test]
+    fn keys() {
+        let json = br#"{"a":{"b":1}}"#;
+        let mut out = Vec::new();
+        rewrite(json, 0, &mut out, |info, bytes| {
+            if info & KEY == KEY {
+                return Some(Cow::Owned(bytes.to_ascii_uppercase()));
+            }
+            None
+        })
+        .unwrap();
+        assert_eq!(out, br#"{"A":{"B":1}}"#);
+    }
+
+    #[test]
+    fn empty_slots() {
+        let mut out = Vec::new();
+        let ret = rewrite(b"[1, , 2]", EMPTY_SLOTS, &mut out, |_, _| None);
+        assert_eq!(ret.unwrap(), 8);
+        assert_eq!(out, b"[1,null,2]");
+        out.clear();
+        let ret = rewrite(b" ", EMPTY_AS_NULL, &mut out, |_, bytes| {
+            assert_eq!(bytes, b"null");
+            Some(Cow::Borrowed(b"0"))
+        });
+        assert_eq!(ret.unwrap(), 1);
+        assert_eq!(out, b"0");
+    }
+
+    #[test]
+    fn write_error() {
+        let mut out = [0; 4];
+        let mut w = &mut out[..];
+        let res = rewrite(br#"[1,2,3]"#, 0, &mut w, |_, _| None);
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn normalize() {
+        let json = br#" { "a" : [ 1 , { } , [ ] , "x y" ] , "b" : { "c" : null } } "#;
+        let normal = |json: &[u8], opts, style| {
+            let mut out = Vec::new();
+            normalize_to(json, opts, style, &mut out).map(|_| String::from_utf8(out).unwrap())
+        };
+        assert_eq!(
+            normal(json, 0, Style::Minified).unwrap(),
+            r#"{"a":[1,{},[],"x y"],"b":{"c":null}}"#
+        );
+        assert_eq!(
+            normal(json, 0, Style::Pretty).unwrap(),
+            "{\n  \"a\": [\n    1,\n    {},\n    [],\n    \"x y\"\n  ],\n  \"b\": {\n    \"c\": null\n  }\n}"
+        );
+        assert_eq!(normal(b" 1 ", 0, Style::Pretty).unwrap(), "1");
+        assert_eq!(
+            normal(b"[1,,2]", EMPTY_SLOTS, Style::Minified).unwrap(),
+            "[1,null,2]"
+        );
+        let mut out = Vec::new();
+        let err = normalize_to(br#"[1,2,]"#, 0, Style::Minified, &mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = err.into_inner().unwrap().downcast::<ParseError>().unwrap();
+        assert_eq!(*err, ParseError::new(ErrorKind::Syntax, 5));
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn whitespace() {
+        let json = br#" { "a b" : [ 1 , "c d" ] }
+"#;
+        let mut out = Vec::new();
+        rewrite(json, 0, &mut out, |_, _| None).unwrap();
+        assert_eq!(whitespace_bytes(json, 0), Ok(json.len() - out.len()));
+        assert_eq!(whitespace_bytes(json, 0), Ok(10));
+        assert_eq!(whitespace_bytes(json, PAIRS), Ok(10));
+        assert_eq!(whitespace_bytes(json, SPLIT_NUMBERS), Ok(10));
+        assert_eq!(whitespace_bytes(br#"{"a":["\t\n"]}"#, 0), Ok(0));
+        assert_eq!(whitespace_bytes(b"\t\r\n true", 0), Ok(4));
+        assert_eq!(whitespace_bytes(b"[1, , 2]", EMPTY_SLOTS), Ok(2));
+        assert_eq!(whitespace_bytes(b"#!/bin/cfg x\n {}", CONFIG), Ok(2));
+        assert_eq!(
+            whitespace_bytes(b"[1 2]", 0),
+            Err(ParseError::new(ErrorKind::Syntax, 3))
+        );
+    }
+}
@@ -0,0 +1,366 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::io::{self, Read};
+
+/// Parse a stream of concatenated JSON documents from a reader, such as
+/// newline-delimited json or json that is simply written one after another.
+///
+/// The 'iter' function is called for every element of every document with the
+/// index of the document, the bytes of the document, and the 'start', 'end',
+/// and 'info' of the element, which are relative to the bytes of the document.
+/// Its return value has the same meaning as for parse, and returning 0 stops
+/// the whole stream.
+///
+/// A document is read in full and validated before any of its elements are
+/// reported, and the reader is not read past the end of a document until all
+/// of its elements have been reported, so the pace of the reading is set by
+/// 'iter'. A document may be split across any number of reads.
+///
+/// Returns the number of documents that were reported, including one that was
+/// stopped. An invalid document is an io::Error of the InvalidData kind that
+/// wraps a ParseError, with the offset of the error from the start of the
+/// stream. The documents before it have already been reported. The CONFIG
+/// and PRELUDE options are not supported and are ignored.
+pub fn parse_stream_reader<R, F>(reader: R, opts: usize, iter: F) -> io::Result<usize>
+where
+    R: Read,
+    F: FnMut(usize, &[u8], usize, usize, usize) -> i64,
+{
+    let options = Options {
+        flags: opts,
+        ..Options::default()
+    };
+    parse_stream_reader_with(reader, &options, iter)
+}
+
+/// Parse a stream of concatenated JSON documents from a reader with Options.
+/// This works just like parse_stream_reader, except that the settings come
+/// from the options.
+///
+/// The max_total_bytes option caps the size of each document, which is also
+/// the most bytes that are buffered at once. Reading stops as soon as a
+/// document is found to be larger, which is a LimitExceeded error at the
+/// offset that is max_total_bytes past the start of the document.
+pub fn parse_stream_reader_with<R, F>(reader: R, options: &Options, iter: F) -> io::Result<usize>
+where
+    R: Read,
+    F: FnMut(usize, &[u8], usize, usize, usize) -> i64,
+{
+    let mut reader = reader;
+    let mut iter = iter;
+    let opts = options.flags & !(CONFIG | PRELUDE);
+    let max = options.max_total_bytes;
+    let mut st = ParserState::new();
+    st.set_options(options);
+    let mut buf = Vec::new();
+    // the offset of buf in the stream
+    let mut base = 0;
+    let mut count = 0;
+    let mut eof = false;
+    // a document that arrives in many small reads is only parsed once its
+    // end is found by the frame, which looks at each byte once. In between, it
+    // is parsed again whenever buf has doubled since the last time, so that an
+    // error is still found early.
+    let mut frame = Frame::default();
+    let mut scanned = 0;
+    loop {
+        // drop the whitespace before the next document
+        let start = skipws(&buf, 0, opts);
+        buf.drain(..start);
+        base += start;
+        if buf.is_empty() && eof {
+            return Ok(count);
+        }
+        let ready = frame.end(&buf) || eof || buf.len() >= scanned * 2 || buf.len() > max;
+        if !buf.is_empty() && ready {
+            let mut f = |_: usize, _: usize, _: usize| -> i64 { 1 };
+            let (end, ok, stop) = vany(&buf, 0, opts, &mut st, START, &mut f, true);
+            if ok && !stop && end > max {
+                let err = ParseError::new(ErrorKind::LimitExceeded, base + max);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+            }
+            if ok && !stop && (eof || end < buf.len() || !isnum(buf[end - 1])) {
+                // a Number at the end of buf may have more digits to come
+                let doc = &buf[..end];
+                let status = st.status(doc, opts, |s: usize, e: usize, info: usize| -> i64 {
+                    iter(count, doc, s, e, info)
+                });
+                count += 1;
+                if let ParseStatus::Stopped(_) = status {
+                    return Ok(count);
+                }
+                buf.drain(..end);
+                base += end;
+                frame = Frame::default();
+                scanned = 0;
+                continue;
+            }
+            // an error near the end of buf may be a token that is cut off,
+            // such as a JSON5 line separator
+            if !ok && (eof || end + 3 <= buf.len()) {
+                let err = ParseError::from_ret(&buf, -(end as i64));
+                let err = ParseError::new(err.kind, base + end);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+            }
+            scanned = buf.len();
+        }
+        if buf.len() > max {
+            let err = ParseError::new(ErrorKind::LimitExceeded, base + max);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+        }
+        eof = !fill(&mut reader, &mut buf, max)?;
+    }
+}
+
+/// Validate newline-delimited json, where each line is a separate document,
+/// and return the errors of up to 'max_errors' invalid lines, without stopping
+/// at the first one.
+///
+/// Each error is paired with the index of its line, starting at zero, and has
+/// the offset of the error from the start of the json. Blank lines are not
+/// documents and are skipped, but they are still counted in the line index.
+pub fn validate_lines(json: &[u8], opts: usize, max_errors: usize) -> Vec<(usize, ParseError)> {
+    let mut errors = Vec::new();
+    let mut st = ParserState::new();
+    let mut base = 0;
+    for (index, line) in json.split(|&ch| ch == b'\n').enumerate() {
+        if errors.len() == max_errors {
+            break;
+        }
+        if skipws(line, 0, opts) < line.len() {
+            let status = st.status(line, opts, |_: usize, _: usize, info: usize| -> i64 {
+                if info & OPEN == OPEN {
+                    -1
+                } else {
+                    1
+                }
+            });
+            if let ParseStatus::Error(err) = status {
+                errors.push((index, ParseError::new(err.kind, base + err.offset)));
+            }
+        }
+        base += line.len() + 1;
+    }
+    errors
+}
+
+// Frame follows the Strings and the nesting of the brackets of a document at
+// the start of a buffer that keeps growing, to find where the document ends
+// without parsing it from the start after every read.
+#[derive(Default)]
+struct Frame {
+    i: usize,
+    depth: usize,
+    string: bool,
+    escape: bool,
+    done: bool,
+}
+
+impl Frame {
+    // Scan the bytes that were added to buf since the last call, and return
+    // true once the document may be complete. A root that is not an Object,
+    // Array, or String is left to the parser right away.
+    fn end(&mut self, buf: &[u8]) -> bool {
+        while !self.done && self.i < buf.len() {
+            let ch = buf[self.i];
+            self.i += 1;
+            if self.string {
+                if self.escape {
+                    self.escape = false;
+                } else if ch == b'\\' {
+                    self.escape = true;
+                } else if ch == b'"' {
+                    self.string = false;
+                    self.done = self.depth == 0;
+                }
+            } else if ch == b'"' {
+                self.string = true;
+            } else if ch == b'{' || ch == b'[' {
+                self.depth += 1;
+            } else if ch == b'}' || ch == b']' || self.depth == 0 {
+                self.depth = self.depth.saturating_sub(1);
+                self.done = self.depth == 0;
+            }
+        }
+        self.done
+    }
+}
+
+// fill reads more bytes onto the end of buf, asking for at least as many as
+// buf already has, up to 64 KiB, but no more than one byte past max. Returns
+// false at the end of the reader.
+fn fill<R: Read>(reader: &mut R, buf: &mut Vec<u8>, max: usize) -> io::Result<bool> {
+    let len = buf.len();
+    let n = len.clamp(4096, 65536).min(max.saturating_add(1) - len);
+    buf.resize(len + n, 0);
+    loop {
+        match reader.read(&mut buf[len..]) {
+            Ok(n) => {
+                buf.truncate(len + n);
+                return Ok(n > 0);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                buf.truncate(len);
+                return Err(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a reader that returns at most n bytes per read
+    struct Trickle<'a> {
+        data: &'a [u8],
+        n: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.n.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    fn docs(data: &[u8], n: usize) -> io::Result<Vec<(usize, String)>> {
+        let mut out = Vec::new();
+        let reader = Trickle { data, n };
+        parse_stream_reader(reader, 0, |index, doc, start, end, info| -> i64 {
+            if info & (STRING | NUMBER | TRUE | FALSE | NULL) != 0 && info & KEY == 0 {
+                let el = std::str::from_utf8(&doc[start..end]).unwrap();
+                out.push((index, el.to_string()));
+            }
+            1
+        })?;
+        Ok(out)
+    }
+
+    #[test]
+    fn concatenated() {
+        let data = b" {\"a\":[1,\"two\"]}\n[true]\"s\"123 -4.5e1\n{}null[\n]";
+        let expect: Vec<(usize, String)> = [
+            (0, "1"),
+            (0, "\"two\""),
+            (1, "true"),
+            (2, "\"s\""),
+            (3, "123"),
+            (4, "-4.5e1"),
+            (6, "null"),
+        ]
+        .iter()
+        .map(|&(index, el)| (index, el.to_string()))
+        .collect();
+        for n in 1..data.len() + 1 {
+            assert_eq!(docs(data, n).unwrap(), expect);
+        }
+        assert_eq!(docs(b"", 1).unwrap(), []);
+        assert_eq!(docs(b" \n ", 1).unwrap(), []);
+    }
+
+    #[test]
+    fn large() {
+        // a large document that arrives in many small reads
+        let mut data = b"[".to_vec();
+        for i in 0..400000 {
+            data.extend_from_slice(format!("{},", i).as_bytes());
+        }
+        data.extend_from_slice(b"true] [1]");
+        for n in [1000, 65536] {
+            let mut values = 0;
+            let reader = Trickle { data: &data, n };
+            let count = parse_stream_reader(reader, 0, |_, _, _, _, info| -> i64 {
+                values += (info & VALUE == VALUE) as usize;
+                1
+            })
+            .unwrap();
+            assert_eq!((count, values), (2, 400002));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let data = b"[1] [2,] [3]";
+        for n in 1..data.len() + 1 {
+            let err = docs(data, n).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            let err = err.get_ref().unwrap().downcast_ref::<ParseError>();
+            assert_eq!(err, Some(&ParseError::new(ErrorKind::Syntax, 7)));
+        }
+        let err = docs(b"[1]\n{\"a\":", 2).unwrap_err();
+        let err = err.get_ref().unwrap().downcast_ref::<ParseError>();
+        assert_eq!(err, Some(&ParseError::new(ErrorKind::UnexpectedEof, 9)));
+    }
+
+    #[test]
+    fn stop() {
+        let data = b"[1,2] [3,4] [5,6]";
+        let mut seen = Vec::new();
+        let reader = Trickle { data, n: 2 };
+        let count = parse_stream_reader(reader, 0, |index, doc, start, end, _| -> i64 {
+            seen.push(doc[start..end].to_vec());
+            if index == 1 && &doc[start..end] == b"]" {
+                0
+            } else {
+                1
+            }
+        })
+        .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(seen.len(), 10);
+    }
+
+    #[test]
+    fn limit() {
+        let data = b"[1,2]  [3,4,5,6]\n[7]";
+        let options = Options {
+            max_total_bytes: 8,
+            ..Options::default()
+        };
+        for n in 1..data.len() + 1 {
+            let mut docs = Vec::new();
+            let reader = Trickle { data, n };
+            let err = parse_stream_reader_with(reader, &options, |index, doc, _, _, _| -> i64 {
+                docs.push((index, doc.len()));
+                1
+            })
+            .unwrap_err();
+            let err = err.get_ref().unwrap().downcast_ref::<ParseError>();
+            assert_eq!(err, Some(&ParseError::new(ErrorKind::LimitExceeded, 15)));
+            assert_eq!(docs, [(0, 5); 5]);
+        }
+        let options = Options {
+            max_total_bytes: 9,
+            ..Options::default()
+        };
+        let reader = Trickle { data, n: 3 };
+        let count = parse_stream_reader_with(reader, &options, |_, _, _, _, _| 1).unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn lines() {
+        let json = b"{\"a\":1}\n[1,]\n\n  \r\n\"x\n{\"b\":\r\ntrue false\n[{}]";
+        let errors = validate_lines(json, 0, 10);
+        assert_eq!(
+            errors,
+            [
+                (1, ParseError::new(ErrorKind::Syntax, 11)),
+                (4, ParseError::new(ErrorKind::UnexpectedEof, 20)),
+                (5, ParseError::new(ErrorKind::UnexpectedEof, 27)),
+                (6, ParseError::new(ErrorKind::Syntax, 33)),
+            ]
+        );
+        assert_eq!(validate_lines(json, 0, 2), errors[..2]);
+        assert!(validate_lines(json, 0, 0).is_empty());
+        assert!(validate_lines(b"1\n\n2\n", 0, 10).is_empty());
+        assert!(validate_lines(b"", 0, 10).is_empty());
+    }
+}
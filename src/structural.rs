@@ -0,0 +1,181 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+
+/// Find the offsets of all of the structural characters in JSON, which are
+/// '{', '}', '[', ']', ':', ',', and the opening and closing quotes of every
+/// String, in ascending order. The characters inside of Strings are skipped,
+/// including escaped quotes.
+///
+/// This is the first stage of a two-stage parser, like simdjson, where the
+/// second stage walks the offsets instead of the bytes. The json is scanned
+/// 16 bytes at a time using SIMD where available. Nothing is validated, so
+/// for invalid json the offsets are only meaningful up to the first error.
+/// An unclosed String at the end of the json has only its opening quote.
+///
+/// Panics if the json is 4 GiB or larger, as the offsets would not fit.
+///
+/// ```
+/// let json = br#"{"a": [1, "b\"]"]}"#;
+/// assert_eq!(pjson::structural_indices(json), [0, 1, 3, 4, 6, 8, 10, 15, 16, 17]);
+/// ```
+pub fn structural_indices(json: &[u8]) -> Vec<u32> {
+    assert!(json.len() <= u32::MAX as usize, "json is too large");
+    let mut out = Vec::new();
+    let mut i = 0;
+    loop {
+        i = next_structural(json, i);
+        if i == json.len() {
+            return out;
+        }
+        out.push(i as u32);
+        if json[i] == b'"' {
+            i += 1;
+            loop {
+                i = next_string_token(json, i);
+                if i >= json.len() {
+                    return out;
+                }
+                if json[i] == b'"' {
+                    break;
+                }
+                // skip the escaped character
+                i += 2;
+            }
+            out.push(i as u32);
+        }
+        i += 1;
+    }
+}
+
+// next_structural returns the index of the next structural character at or
+// after i, or the length of the json when there is none.
+fn next_structural(json: &[u8], mut i: usize) -> usize {
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    // SAFETY: the call is made safe because the bounds are
+    // checked in the following while loop condition.
+    unsafe {
+        use std::arch::x86_64::*;
+        while i + 16 <= json.len() {
+            let ptr = json.get_unchecked(i) as *const _ as *const __m128i;
+            let block = _mm_loadu_si128(ptr);
+            // '[' and ']' are '{' and '}' without the 0x20 bit
+            let folded = _mm_or_si128(block, _mm_set1_epi8(0x20));
+            let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(folded, _mm_set1_epi8(b'{' as i8)))
+                | _mm_movemask_epi8(_mm_cmpeq_epi8(folded, _mm_set1_epi8(b'}' as i8)))
+                | _mm_movemask_epi8(_mm_cmpeq_epi8(block, _mm_set1_epi8(b':' as i8)))
+                | _mm_movemask_epi8(_mm_cmpeq_epi8(block, _mm_set1_epi8(b',' as i8)))
+                | _mm_movemask_epi8(_mm_cmpeq_epi8(block, _mm_set1_epi8(b'"' as i8)));
+            if mask != 0 {
+                return i + mask.trailing_zeros() as usize;
+            }
+            i += 16;
+        }
+    }
+    while i < json.len() {
+        if CHTABLE[json[i] as usize] & CHSTRUCT == CHSTRUCT {
+            return i;
+        }
+        i += 1;
+    }
+    json.len()
+}
+
+// next_string_token returns the index of the next '"' or '\' at or after i,
+// or the length of the json when there is none.
+fn next_string_token(json: &[u8], mut i: usize) -> usize {
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    // SAFETY: the call is made safe because the bounds are
+    // checked in the following while loop condition.
+    unsafe {
+        use std::arch::x86_64::*;
+        while i + 16 <= json.len() {
+            let ptr = json.get_unchecked(i) as *const _ as *const __m128i;
+            let block = _mm_loadu_si128(ptr);
+            let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(block, _mm_set1_epi8(b'"' as i8)))
+                | _mm_movemask_epi8(_mm_cmpeq_epi8(block, _mm_set1_epi8(b'\\' as i8)));
+            if mask != 0 {
+                return i + mask.trailing_zeros() as usize;
+            }
+            i += 16;
+        }
+    }
+    while i < json.len() {
+        if json[i] == b'"' || json[i] == b'\\' {
+            return i;
+        }
+        i += 1;
+    }
+    json.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // the structural offsets of valid json, as reported by parse
+    fn parsed(json: &[u8]) -> Vec<u32> {
+        let mut out = Vec::new();
+        let ret = parse(json, 0, |start: usize, end: usize, info: usize| -> i64 {
+            if info & STRING == STRING {
+                out.push(start as u32);
+                out.push(end as u32 - 1);
+            } else if info & (OPEN | CLOSE | COMMA | COLON) != 0 {
+                out.push(start as u32);
+            }
+            1
+        });
+        assert_eq!(ret, json.len() as i64);
+        out
+    }
+
+    #[test]
+    fn indices() {
+        let json = br#" {"a\\":[1,"x\"y:,{}[]"],"long string with no tokens at all":{}} "#;
+        assert_eq!(structural_indices(json), parsed(json));
+        assert!(structural_indices(b"").is_empty());
+        assert!(structural_indices(b" 123 true ").is_empty());
+        assert_eq!(structural_indices(br#""abc"#), [0]);
+        assert_eq!(structural_indices(br#"["ab\"#), [0, 1]);
+        let mut json = b"[".to_vec();
+        for i in 0..100 {
+            json.extend_from_slice(format!("{},\"{}\\\\\"", i, "\\\"".repeat(i % 20)).as_bytes());
+            json.push(b',');
+        }
+        json.extend_from_slice(b"{}]");
+        assert_eq!(structural_indices(&json), parsed(&json));
+    }
+
+    #[test]
+    fn files() {
+        for file in fs::read_dir("testfiles").unwrap() {
+            let json = fs::read(file.unwrap().path()).unwrap();
+            assert_eq!(structural_indices(&json), parsed(&json));
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn structural_bench() {
+        let path = match std::env::var("PJSON_STRUCTURAL_BENCH_FILE") {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+        let json = fs::read(&path).unwrap();
+        let mut total = 0;
+        let start = std::time::Instant::now();
+        while total < 100 * 1024 * 1024 {
+            let indices = structural_indices(&json);
+            assert!(!indices.is_empty());
+            total += json.len();
+        }
+        println!(
+            "running benchmark: {}: {:.2} GB/sec",
+            path,
+            (total as f64 / start.elapsed().as_secs_f64() / 1024.0 / 1024.0 / 1024.0),
+        );
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+
+/// The kind of a TapeEntry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TapeKind {
+    /// the '{' of an Object
+    ObjectStart,
+    /// the '}' of an Object
+    ObjectEnd,
+    /// the '[' of an Array
+    ArrayStart,
+    /// the ']' of an Array
+    ArrayEnd,
+    /// the key of an Object member, which is followed by the member's value
+    Key,
+    /// a String value
+    String,
+    /// a Number value
+    Number,
+    /// a True value
+    True,
+    /// a False value
+    False,
+    /// a Null value
+    Null,
+}
+
+/// A single entry of the tape that is returned by to_tape.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TapeEntry {
+    /// the kind of the entry
+    pub kind: TapeKind,
+    /// the start index of the element in the json
+    pub start: usize,
+    /// the end index of the element, such that json[start..end] is the
+    /// complete element. For the start and end of a container this is the
+    /// single '{', '}', '[', or ']' character.
+    pub end: usize,
+    /// For the start of a container, the index of its end entry on the tape.
+    /// For the end of a container, the index of its start entry. For all other
+    /// entries, the index of the entry itself.
+    pub link: usize,
+}
+
+/// Parse JSON into a flat tape of its structural elements.
+///
+/// The tape has one entry for every value and Object key, in the order that
+/// they appear in the json, plus an end entry for every container. Commas and
+/// colons are not on the tape. An Object is its start entry, followed by a
+/// Key entry and a value for every member, then its end entry. An Array is its
+/// start entry, followed by its values, then its end entry. The first entry
+/// is the root value, and for a container the last entry is its end, so that
+/// the children of the container at index i are at the indexes between i and
+/// tape[i].link. Skipping over a container is done by jumping to its link.
+pub fn to_tape(json: &[u8], opts: usize) -> Result<Vec<TapeEntry>, ParseError> {
+    let mut tape: Vec<TapeEntry> = Vec::new();
+    let mut stack = Vec::new();
+    let opts = opts & !EVENT_OPTS;
+    let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        let link = tape.len();
+        let kind = if info & OPEN == OPEN {
+            stack.push(link);
+            if info & OBJECT == OBJECT {
+                TapeKind::ObjectStart
+            } else {
+                TapeKind::ArrayStart
+            }
+        } else if info & CLOSE == CLOSE {
+            let open = stack.pop().unwrap();
+            tape[open].link = link;
+            tape.push(TapeEntry {
+                kind: if info & OBJECT == OBJECT {
+                    TapeKind::ObjectEnd
+                } else {
+                    TapeKind::ArrayEnd
+                },
+                start,
+                end,
+                link: open,
+            });
+            return 1;
+        } else if info & KEY == KEY {
+            TapeKind::Key
+        } else if info & STRING == STRING {
+            TapeKind::String
+        } else if info & NUMBER == NUMBER {
+            TapeKind::Number
+        } else if info & TRUE == TRUE {
+            TapeKind::True
+        } else if info & FALSE == FALSE {
+            TapeKind::False
+        } else if info & NULL == NULL {
+            TapeKind::Null
+        } else {
+            // comma or colon
+            return 1;
+        };
+        tape.push(TapeEntry {
+            kind,
+            start,
+            end,
+            link,
+        });
+        1
+    });
+    if ret <= 0 {
+        return Err(ParseError::from_ret(json, ret));
+    }
+    Ok(tape)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // rebuild minified json from the tape
+    fn reconstruct(json: &[u8], tape: &[TapeEntry]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (i, entry) in tape.iter().enumerate() {
+            let first = i == 0
+                || matches!(
+                    tape[i - 1].kind,
+                    TapeKind::ObjectStart | TapeKind::ArrayStart | TapeKind::Key
+                );
+            let end = matches!(entry.kind, TapeKind::ObjectEnd | TapeKind::ArrayEnd);
+            if !first && !end {
+                out.push(b',');
+            }
+            out.extend_from_slice(&json[entry.start..entry.end]);
+            if entry.kind == TapeKind::Key {
+                out.push(b':');
+            }
+        }
+        out
+    }
+
+    fn minify(json: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        parse(json, 0, |start: usize, end: usize, _: usize| -> i64 {
+            out.extend_from_slice(&json[start..end]);
+            1
+        });
+        out
+    }
+
+    #[test]
+    fn layout() {
+        let json = br#" {"a": [1, "b", {}], "c": null} "#;
+        let tape = to_tape(json, 0).unwrap();
+        let expect = [
+            (TapeKind::ObjectStart, 1, 2, 10),
+            (TapeKind::Key, 2, 5, 1),
+            (TapeKind::ArrayStart, 7, 8, 7),
+            (TapeKind::Number, 8, 9, 3),
+            (TapeKind::String, 11, 14, 4),
+            (TapeKind::ObjectStart, 16, 17, 6),
+            (TapeKind::ObjectEnd, 17, 18, 5),
+            (TapeKind::ArrayEnd, 18, 19, 2),
+            (TapeKind::Key, 21, 24, 8),
+            (TapeKind::Null, 26, 30, 9),
+            (TapeKind::ObjectEnd, 30, 31, 0),
+        ];
+        let expect: Vec<TapeEntry> = expect
+            .iter()
+            .map(|&(kind, start, end, link)| TapeEntry {
+                kind,
+                start,
+                end,
+                link,
+            })
+            .collect();
+        assert_eq!(tape, expect);
+        assert_eq!(
+            to_tape(b" true ", 0).unwrap(),
+            [TapeEntry {
+                kind: TapeKind::True,
+                start: 1,
+                end: 5,
+                link: 0
+            }]
+        );
+        assert_eq!(
+            to_tape(b"[1,", 0),
+            Err(ParseError::new(ErrorKind::UnexpectedEof, 3))
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let jsons: [&[u8]; 6] = [
+            br#"{"a":[1,"b",{}],"c":null}"#,
+            b"[[],[[]],{},[{}]]",
+            b"false",
+            br#" { "x" : { "y" : [ true , false , -1.5e3 ] } } "#,
+            br#"[{"a":1,"b":2},{"c":[3,4]}]"#,
+            br#""a\"b""#,
+        ];
+        for json in jsons.iter() {
+            let tape = to_tape(json, 0).unwrap();
+            assert_eq!(reconstruct(json, &tape), minify(json));
+            // every container is linked both ways
+            for (i, entry) in tape.iter().enumerate() {
+                assert_eq!(tape[entry.link].link, i);
+            }
+        }
+    }
+}
@@ -0,0 +1,568 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::ops::Range;
+
+/// A single element of a JSON document, as passed to the iter function of
+/// parse.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Token {
+    /// the start index of the element
+    pub start: usize,
+    /// the end index of the element, such that json[start..end] is the
+    /// complete element
+    pub end: usize,
+    /// the info bits for the element
+    pub info: usize,
+}
+
+/// Parse JSON and append every element to the provided Vec.
+///
+/// The caller owns the Vec, which allows for reusing and presizing its memory
+/// between calls. Tokens are only appended and existing items are left as is.
+/// The tokens refer to the json by their indexes and do not borrow it, so
+/// they remain valid for as long as the same json is around.
+///
+/// On error, the Vec is truncated back to its original length.
+pub fn parse_into(json: &[u8], opts: usize, tokens: &mut Vec<Token>) -> Result<(), ParseError> {
+    let len = tokens.len();
+    let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        tokens.push(Token { start, end, info });
+        1
+    });
+    if ret <= 0 {
+        tokens.truncate(len);
+        return Err(ParseError::from_ret(json, ret));
+    }
+    Ok(())
+}
+
+/// Parse JSON into a Vec of every element.
+pub fn parse_to_vec(json: &[u8], opts: usize) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    parse_into(json, opts, &mut tokens)?;
+    Ok(tokens)
+}
+
+/// Parse JSON and return the first element for which 'pred' returns true,
+/// stopping the parsing right there.
+///
+/// Returns None when no element matches, or when the document is invalid
+/// before a match is found. Like parse_find, whatever follows the match is not
+/// validated.
+pub fn parse_until<F>(json: &[u8], opts: usize, pred: F) -> Option<Token>
+where
+    F: FnMut(&Token) -> bool,
+{
+    let mut pred = pred;
+    let mut found = None;
+    parse_find(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        let token = Token { start, end, info };
+        if pred(&token) {
+            found = Some(token);
+            0
+        } else {
+            1
+        }
+    })?;
+    found
+}
+
+/// Parse exactly one value from the front of the json, which may be a scalar
+/// or a container, and return its token along with the unconsumed tail.
+///
+/// Leading whitespace is skipped. The bytes that follow the value are not
+/// required to be whitespace, which is useful for framing values of unknown
+/// length in a stream. The token covers the whole value, with the info bits
+/// of its type plus START and END. The children of a container are validated
+/// but not reported. Note that a Number at the very end of the json is
+/// considered complete, even if more digits could follow in a stream.
+pub fn parse_value(json: &[u8]) -> Result<(Token, &[u8]), ParseError> {
+    let mut st = ParserState::new();
+    let mut token = Token::default();
+    let mut f = |start: usize, end: usize, info: usize| -> i64 {
+        if info & START == START {
+            token.start = start;
+        }
+        token.end = end;
+        token.info = (info & !(OPEN | CLOSE)) | START | END;
+        -1
+    };
+    let (i, ok, _) = vany(json, 0, 0, &mut st, START, &mut f, false);
+    if !ok {
+        return Err(ParseError::from_ret(json, -(i as i64)));
+    }
+    Ok((token, &json[i..]))
+}
+
+/// Skip over 'n' values of an Array, starting at 'start', and return the offset
+/// just after the last one, such as for paging through a large Array. The
+/// offset can be passed back in as 'start' to skip some more.
+///
+/// The 'start' may be the offset of a value, or of the whitespace or ',' just
+/// before one, such as the offset right after the '['. Each value is validated,
+/// without reporting any of its elements. When the Array ends before 'n' values
+/// were skipped, the offset of its ']' is returned instead.
+///
+/// ```
+/// let json = br#"[{"a":1},[2,3],"4",5]"#;
+/// let i = pjson::skip_values(json, 1, 2, 0).unwrap();
+/// assert_eq!(i, 14);
+/// assert_eq!(pjson::skip_values(json, i, 1, 0).unwrap(), 18);
+/// assert_eq!(pjson::skip_values(json, i, 10, 0).unwrap(), 20);
+/// ```
+pub fn skip_values(json: &[u8], start: usize, n: usize, opts: usize) -> Result<usize, ParseError> {
+    let mut st = ParserState::new();
+    let mut f = |_: usize, _: usize, _: usize| -> i64 { 1 };
+    let mut i = start.min(json.len());
+    for k in 0..n {
+        i = skipws(json, i, opts);
+        if i < json.len() && json[i] == b',' {
+            i = skipws(json, i + 1, opts);
+        } else if k > 0 && i < json.len() && json[i] != b']' {
+            return Err(ParseError::new(ErrorKind::Syntax, i));
+        }
+        if i < json.len() && json[i] == b']' {
+            return Ok(i);
+        }
+        let (i_, ok, _) = vany(json, i, opts, &mut st, VALUE, &mut f, true);
+        if !ok {
+            return Err(ParseError::from_ret(json, -(i_ as i64)));
+        }
+        i = i_;
+    }
+    Ok(i)
+}
+
+/// Find the '}' or ']' that closes the Object or Array that opens at 'open',
+/// such as for jumping to the matching bracket in an editor, or for taking out
+/// a container when its start is already known.
+///
+/// The container is validated, unless the UNCHECKED option is used, where only
+/// Strings and the nesting of the brackets are followed. It is an error when
+/// json[open] is not a '{' or '[', or when the container is not closed.
+///
+/// ```
+/// let json = br#"{"a":[1,{"b":"]"}],"c":[]}"#;
+/// assert_eq!(pjson::find_close(json, 5, 0).unwrap(), 17);
+/// assert_eq!(pjson::find_close(json, 0, 0).unwrap(), json.len() - 1);
+/// ```
+pub fn find_close(json: &[u8], open: usize, opts: usize) -> Result<usize, ParseError> {
+    if open >= json.len() {
+        return Err(ParseError::new(ErrorKind::UnexpectedEof, json.len()));
+    }
+    if json[open] != b'{' && json[open] != b'[' {
+        return Err(ParseError::new(ErrorKind::Syntax, open));
+    }
+    let (end, ok) = if opts & UNCHECKED == UNCHECKED {
+        squash(json, open + 1, usize::MAX)
+    } else {
+        squash_checked(json, open + 1, json[open], opts, &mut ParserState::new())
+    };
+    if !ok {
+        return Err(ParseError::from_ret(json, -(end as i64)));
+    }
+    Ok(end - 1)
+}
+
+/// Validate an edited JSON document by parsing again only the innermost
+/// container around the edit, such as for keeping the tokens of a document
+/// that is open in an editor up to date on every keystroke.
+///
+/// The 'old_tokens' are those of the document before the edit, as returned by
+/// parse_to_vec with no options. The 'edit' is the range of the old document
+/// that was replaced, and 'new_len_delta' is the length of its replacement
+/// minus the length of the range. The 'json' is the document after the edit.
+///
+/// The tokens of the container are replaced by those of its new children, and
+/// the tokens after it are shifted by 'new_len_delta'. When the edit is not
+/// inside of a container, or when it changes the bracket that closes it, the
+/// whole document is parsed again. The result is the same as that of calling
+/// parse_to_vec on the new document.
+pub fn revalidate(
+    json: &[u8],
+    old_tokens: &[Token],
+    edit: Range<usize>,
+    new_len_delta: isize,
+) -> Result<Vec<Token>, ParseError> {
+    let mut stack = Vec::new();
+    let mut span = None;
+    for (i, token) in old_tokens.iter().enumerate() {
+        if token.info & OPEN == OPEN {
+            stack.push(i);
+        } else if token.info & CLOSE == CLOSE {
+            let a = match stack.pop() {
+                Some(a) => a,
+                None => break,
+            };
+            if old_tokens[a].start < edit.start && edit.end <= token.start {
+                span = Some((a, i));
+                break;
+            }
+        }
+    }
+    let (a, b) = match span {
+        Some(span) => span,
+        None => return parse_to_vec(json, 0),
+    };
+    let shift = |offset: usize| (offset as isize + new_len_delta) as usize;
+    let open = old_tokens[a].start;
+    match find_close(json, open, 0) {
+        Ok(close) if close == shift(old_tokens[b].start) => {
+            let mut tokens = Vec::with_capacity(old_tokens.len());
+            tokens.extend_from_slice(&old_tokens[..=a]);
+            // the container was already validated by find_close, but keep
+            // any error relative to the whole json anyway
+            let inner = parse_to_vec(&json[open..=close], 0)
+                .map_err(|err| ParseError::new(err.kind, err.offset + open))?;
+            for token in &inner[1..inner.len() - 1] {
+                tokens.push(Token {
+                    start: token.start + open,
+                    end: token.end + open,
+                    info: token.info,
+                });
+            }
+            for token in &old_tokens[b..] {
+                tokens.push(Token {
+                    start: shift(token.start),
+                    end: shift(token.end),
+                    info: token.info,
+                });
+            }
+            Ok(tokens)
+        }
+        _ => parse_to_vec(json, 0),
+    }
+}
+
+/// Validate a JSON document and return the range of its root value, without
+/// the whitespace around it, such that json[start..end] is the complete value.
+/// This is for storing exactly the bytes of a document's value. A header that
+/// is skipped by the CONFIG option is also not part of the range.
+pub fn trimmed_value(json: &[u8], opts: usize) -> Result<(usize, usize), ParseError> {
+    let mut range = (0, 0);
+    let status = parse_status(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        if info & START == START {
+            range.0 = start;
+        }
+        if info & END == END {
+            range.1 = end;
+        }
+        1
+    });
+    match status {
+        ParseStatus::Error(err) => Err(err),
+        _ => Ok(range),
+    }
+}
+
+/// The kind of the root value of a JSON document, as returned by root_kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// an Object, of which only the '{' was parsed
+    ObjectOpen,
+    /// an Array, of which only the '[' was parsed
+    ArrayOpen,
+    /// a String
+    String,
+    /// a Number
+    Number,
+    /// a True
+    True,
+    /// a False
+    False,
+    /// a Null
+    Null,
+}
+
+/// Classify the root value of a JSON document by parsing only its first
+/// element, such as for routing a document by its type.
+///
+/// Only the opening character of an Object or Array is parsed, so the rest of
+/// the container is not validated. A scalar root is validated, but whatever
+/// follows it is not. Use parse_status, or valid_within_depth with a max_depth
+/// of usize::MAX, for when the whole document must be valid.
+pub fn root_kind(json: &[u8], opts: usize) -> Result<Kind, ParseError> {
+    let mut kind = Kind::Null;
+    let opts = opts & !EVENT_OPTS;
+    let status = parse_status(json, opts, |_: usize, _: usize, info: usize| -> i64 {
+        kind = if info & OBJECT == OBJECT {
+            Kind::ObjectOpen
+        } else if info & ARRAY == ARRAY {
+            Kind::ArrayOpen
+        } else if info & STRING == STRING {
+            Kind::String
+        } else if info & NUMBER == NUMBER {
+            Kind::Number
+        } else if info & TRUE == TRUE {
+            Kind::True
+        } else if info & FALSE == FALSE {
+            Kind::False
+        } else {
+            Kind::Null
+        };
+        0
+    });
+    match status {
+        ParseStatus::Error(err) => Err(err),
+        _ => Ok(kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let json = br#"{"a":[1,true]}"#;
+        let tokens = parse_to_vec(json, 0).unwrap();
+        let expect = [
+            (0, 1, START | OPEN | OBJECT),
+            (1, 4, KEY | STRING),
+            (4, 5, COLON),
+            (5, 6, VALUE | OPEN | ARRAY),
+            (6, 7, VALUE | NUMBER),
+            (7, 8, COMMA),
+            (8, 12, VALUE | TRUE),
+            (12, 13, VALUE | CLOSE | ARRAY),
+            (13, 14, END | CLOSE | OBJECT),
+        ];
+        let expect: Vec<Token> = expect
+            .iter()
+            .map(|&(start, end, info)| Token { start, end, info })
+            .collect();
+        assert_eq!(tokens, expect);
+    }
+
+    #[test]
+    fn close() {
+        let json = br#" [1, {"a": ["}"]}, [[]], "x]"] "#;
+        for opts in [0, UNCHECKED] {
+            assert_eq!(find_close(json, 1, opts), Ok(29));
+            assert_eq!(find_close(json, 5, opts), Ok(16));
+            assert_eq!(find_close(json, 11, opts), Ok(15));
+            assert_eq!(find_close(json, 19, opts), Ok(22));
+            assert_eq!(find_close(json, 20, opts), Ok(21));
+        }
+        let err = |kind, offset| Err(ParseError::new(kind, offset));
+        assert_eq!(find_close(json, 2, 0), err(ErrorKind::Syntax, 2));
+        assert_eq!(find_close(json, 99, 0), err(ErrorKind::UnexpectedEof, 31));
+        assert_eq!(find_close(b"[1,{]", 0, 0), err(ErrorKind::Syntax, 4));
+        assert_eq!(
+            find_close(b"[1,[2]", 0, 0),
+            err(ErrorKind::UnexpectedEof, 6)
+        );
+        assert_eq!(
+            find_close(b"[1,[2]", 0, UNCHECKED),
+            err(ErrorKind::UnexpectedEof, 6)
+        );
+        // only the container itself is parsed
+        assert_eq!(find_close(b"[1] x", 0, 0), Ok(2));
+    }
+
+    #[test]
+    fn revalidating() {
+        let old = br#"{"a": [1, {"b": 2}, 3], "c": "x"}"#.to_vec();
+        let tokens = parse_to_vec(&old, 0).unwrap();
+        let edit = |range: Range<usize>, with: &[u8]| {
+            let mut json = old.clone();
+            json.splice(range.clone(), with.iter().copied());
+            let delta = with.len() as isize - range.len() as isize;
+            let res = revalidate(&json, &tokens, range, delta);
+            assert_eq!(res, parse_to_vec(&json, 0));
+            res
+        };
+        // inside of the inner Object
+        assert!(edit(16..17, b"true").is_ok());
+        // inside of the Array, next to the inner Object
+        assert!(edit(7..8, b"10").is_ok());
+        assert!(edit(8..9, b"").is_err());
+        assert!(edit(20..21, b"4, 5").is_ok());
+        // inside of the root Object only
+        assert!(edit(29..32, b"[]").is_ok());
+        // closing the Array early
+        assert!(edit(9..9, b"]").is_err());
+        // the root bracket itself
+        assert!(edit(0..1, b"[").is_err());
+        assert!(edit(0..1, b" {").is_ok());
+        assert_eq!(
+            edit(16..17, b"2 3"),
+            Err(ParseError::new(ErrorKind::Syntax, 18))
+        );
+    }
+
+    #[test]
+    fn skipping() {
+        let json = b"[ 1 , {\"a\":[]} ,\"x\", [[]] ]";
+        let mut ends = Vec::new();
+        let mut i = 1;
+        loop {
+            i = skip_values(json, i, 1, 0).unwrap();
+            if json[i] == b']' {
+                break;
+            }
+            ends.push(i);
+        }
+        assert_eq!(ends, [3, 14, 19, 25]);
+        assert_eq!(skip_values(json, 1, 4, 0), Ok(25));
+        assert_eq!(skip_values(json, 1, 5, 0), Ok(26));
+        assert_eq!(skip_values(json, 5, 0, 0), Ok(5));
+        assert_eq!(skip_values(b"[]", 1, 3, 0), Ok(1));
+        let err = |kind, offset| Err(ParseError::new(kind, offset));
+        assert_eq!(skip_values(b"[1 2]", 1, 2, 0), err(ErrorKind::Syntax, 3));
+        assert_eq!(
+            skip_values(b"[{\"a\"}]", 1, 1, 0),
+            err(ErrorKind::Syntax, 5)
+        );
+        assert_eq!(
+            skip_values(b"[1,[2", 1, 2, 0),
+            err(ErrorKind::UnexpectedEof, 5)
+        );
+    }
+
+    #[test]
+    fn until() {
+        let json = br#"{"a":[1,"x"],"b":"y"}"#;
+        let token = parse_until(json, 0, |token| token.info & STRING == STRING);
+        assert_eq!(
+            token,
+            Some(Token {
+                start: 1,
+                end: 4,
+                info: KEY | STRING
+            })
+        );
+        let token = parse_until(json, 0, |token| {
+            token.info & (VALUE | STRING) == VALUE | STRING
+        });
+        assert_eq!(token.map(|t| &json[t.start..t.end]), Some(&br#""x""#[..]));
+        assert_eq!(
+            parse_until(json, 0, |token| token.info & NULL == NULL),
+            None
+        );
+        let mut seen = 0;
+        let token = parse_until(b"[1,2,3]", 0, |token| {
+            seen += 1;
+            token.info & NUMBER == NUMBER && token.start == 3
+        });
+        assert_eq!((token.map(|t| t.start), seen), (Some(3), 4));
+        // invalid before the match, and after it
+        assert_eq!(
+            parse_until(b"[1,,true]", 0, |t| t.info & TRUE == TRUE),
+            None
+        );
+        assert!(parse_until(b"[true,,]", 0, |t| t.info & TRUE == TRUE).is_some());
+    }
+
+    #[test]
+    fn into() {
+        let mut tokens = Vec::with_capacity(64);
+        parse_into(b"[1,2]", 0, &mut tokens).unwrap();
+        assert_eq!(tokens.len(), 5);
+        parse_into(b" 3 ", 0, &mut tokens).unwrap();
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(
+            tokens[5],
+            Token {
+                start: 1,
+                end: 2,
+                info: NUMBER | START | END
+            }
+        );
+        assert_eq!(
+            parse_into(b"[1,2,", 0, &mut tokens),
+            Err(ParseError::new(ErrorKind::UnexpectedEof, 5))
+        );
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(tokens.capacity(), 64);
+        tokens.clear();
+        parse_into(b"[]", 0, &mut tokens).unwrap();
+        assert_eq!(tokens.len(), 2);
+    }
+
+    #[test]
+    fn value() {
+        let value = |json: &'static [u8]| {
+            let (token, tail) = parse_value(json)?;
+            let frag = std::str::from_utf8(&json[token.start..token.end]).unwrap();
+            Ok((frag, token.info, std::str::from_utf8(tail).unwrap()))
+        };
+        assert_eq!(
+            value(br#" {"a":[1,2]} {"b":3}"#),
+            Ok((r#"{"a":[1,2]}"#, OBJECT | START | END, r#" {"b":3}"#))
+        );
+        assert_eq!(value(b"[1]tail"), Ok(("[1]", ARRAY | START | END, "tail")));
+        assert_eq!(
+            value(br#""a\"b"x"#),
+            Ok((r#""a\"b""#, STRING | ESCAPED | START | END, "x"))
+        );
+        assert_eq!(value(b"12 34"), Ok(("12", NUMBER | START | END, " 34")));
+        assert_eq!(
+            value(b"-1.5,"),
+            Ok(("-1.5", NUMBER | SIGN | DOT | START | END, ","))
+        );
+        assert_eq!(value(b"null"), Ok(("null", NULL | START | END, "")));
+        assert_eq!(
+            value(b"  "),
+            Err(ParseError::new(ErrorKind::UnexpectedEof, 2))
+        );
+        assert_eq!(value(b"[1,}"), Err(ParseError::new(ErrorKind::Syntax, 3)));
+        assert_eq!(value(b"]"), Err(ParseError::new(ErrorKind::Syntax, 0)));
+        // values one after another
+        let mut json = &br#"1 "two" [3] {"four":4}"#[..];
+        let mut count = 0;
+        while !json.is_empty() {
+            let (_, tail) = parse_value(json).unwrap();
+            json = tail;
+            count += 1;
+        }
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn root() {
+        assert_eq!(root_kind(br#" {"a":1}"#, 0), Ok(Kind::ObjectOpen));
+        assert_eq!(root_kind(b"[1,2]", 0), Ok(Kind::ArrayOpen));
+        assert_eq!(root_kind(br#""s""#, 0), Ok(Kind::String));
+        assert_eq!(root_kind(b"-1.5e3", 0), Ok(Kind::Number));
+        assert_eq!(root_kind(b"true", 0), Ok(Kind::True));
+        assert_eq!(root_kind(b"false", 0), Ok(Kind::False));
+        assert_eq!(root_kind(b"\nnull\n", 0), Ok(Kind::Null));
+        assert_eq!(root_kind(b"#!/bin/cfg\n[]", CONFIG), Ok(Kind::ArrayOpen));
+        // the rest of the document is not parsed
+        assert_eq!(root_kind(b"[1,}", 0), Ok(Kind::ArrayOpen));
+        assert_eq!(root_kind(b"1 2", 0), Ok(Kind::Number));
+        assert_eq!(
+            root_kind(b"  ", 0),
+            Err(ParseError::new(ErrorKind::UnexpectedEof, 2))
+        );
+        assert_eq!(
+            root_kind(b"nulx", 0),
+            Err(ParseError::new(ErrorKind::Syntax, 1))
+        );
+        assert_eq!(
+            root_kind(b"}", 0),
+            Err(ParseError::new(ErrorKind::Syntax, 0))
+        );
+    }
+
+    #[test]
+    fn trimmed() {
+        assert_eq!(trimmed_value(b" \n{\"a\": [1, 2]}\t ", 0), Ok((2, 15)));
+        assert_eq!(trimmed_value(b"[]", 0), Ok((0, 2)));
+        assert_eq!(trimmed_value(b"  \"s p\"  ", 0), Ok((2, 7)));
+        assert_eq!(trimmed_value(b"-1.5\n", 0), Ok((0, 4)));
+        assert_eq!(trimmed_value(b"#!/bin/cfg\n null ", CONFIG), Ok((12, 16)));
+        assert_eq!(
+            trimmed_value(b" [1] x", 0),
+            Err(ParseError::new(ErrorKind::Syntax, 5))
+        );
+        assert_eq!(
+            trimmed_value(b" ", 0),
+            Err(ParseError::new(ErrorKind::UnexpectedEof, 1))
+        );
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+use std::borrow::Cow;
+
+/// A builder for turning the elements of a JSON document into a tree of
+/// values, used by the build function. The build function keeps track of the
+/// containers that are open, so a builder only needs to create values and add
+/// them to containers.
+pub trait TreeBuilder {
+    /// A complete value
+    type Value;
+    /// An Object that is being built
+    type Object;
+    /// An Array that is being built
+    type Array;
+
+    /// Start a new Object.
+    fn begin_object(&mut self) -> Self::Object;
+    /// Add a member to an Object. The key is decoded.
+    fn key(&mut self, object: &mut Self::Object, key: Cow<'_, str>, value: Self::Value);
+    /// Finish an Object, turning it into a value.
+    fn end_object(&mut self, object: Self::Object) -> Self::Value;
+    /// Start a new Array.
+    fn begin_array(&mut self) -> Self::Array;
+    /// Add an element to an Array.
+    fn element(&mut self, array: &mut Self::Array, value: Self::Value);
+    /// Finish an Array, turning it into a value.
+    fn end_array(&mut self, array: Self::Array) -> Self::Value;
+    /// Create a String value. The string is decoded.
+    fn string(&mut self, s: Cow<'_, str>) -> Self::Value;
+    /// Create a Number value from the text of the Number, such as "-1.5e3".
+    /// The info param has the SIGN, DOT, and E bits of the Number.
+    fn number(&mut self, num: &str, info: usize) -> Self::Value;
+    /// Create a True or False value.
+    fn bool(&mut self, b: bool) -> Self::Value;
+    /// Create a Null value.
+    fn null(&mut self) -> Self::Value;
+}
+
+enum Frame<'a, O, A> {
+    Object(O, Option<Cow<'a, str>>),
+    Array(A),
+}
+
+/// Parse JSON into a tree of values, using the provided builder.
+pub fn build<V, B>(json: &[u8], opts: usize, builder: &mut B) -> Result<V, ParseError>
+where
+    B: TreeBuilder<Value = V>,
+{
+    let mut stack: Vec<Frame<B::Object, B::Array>> = Vec::new();
+    let mut root = None;
+    let opts = opts & !EVENT_OPTS;
+    let ret = parse(json, opts, |start: usize, end: usize, info: usize| -> i64 {
+        let value = if info & OPEN == OPEN {
+            if info & OBJECT == OBJECT {
+                stack.push(Frame::Object(builder.begin_object(), None));
+            } else {
+                stack.push(Frame::Array(builder.begin_array()));
+            }
+            return 1;
+        } else if info & KEY == KEY {
+            if let Some(Frame::Object(_, key)) = stack.last_mut() {
+                *key = Some(unescape(json, start, end));
+            }
+            return 1;
+        } else if info & CLOSE == CLOSE {
+            match stack.pop() {
+                Some(Frame::Object(object, _)) => builder.end_object(object),
+                Some(Frame::Array(array)) => builder.end_array(array),
+                None => return 0,
+            }
+        } else if info & STRING == STRING {
+            builder.string(unescape(json, start, end))
+        } else if info & NUMBER == NUMBER {
+            let num = std::str::from_utf8(&json[start..end]).unwrap();
+            builder.number(num, info & (SIGN | DOT | E))
+        } else if info & (TRUE | FALSE) != 0 {
+            builder.bool(info & TRUE == TRUE)
+        } else if info & NULL == NULL {
+            builder.null()
+        } else {
+            // comma or colon
+            return 1;
+        };
+        match stack.last_mut() {
+            Some(Frame::Object(object, key)) => {
+                let key = key.take().unwrap_or_default();
+                builder.key(object, key, value);
+            }
+            Some(Frame::Array(array)) => builder.element(array, value),
+            None => root = Some(value),
+        }
+        1
+    });
+    if ret <= 0 {
+        return Err(ParseError::from_ret(json, ret));
+    }
+    Ok(root.unwrap())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // a tree of plain values, which is also used to benchmark build
+    #[derive(Debug, PartialEq)]
+    pub(crate) enum Value {
+        Null,
+        Bool(bool),
+        Number(f64),
+        String(String),
+        Array(Vec<Value>),
+        Object(Vec<(String, Value)>),
+    }
+
+    pub(crate) struct Builder;
+
+    impl TreeBuilder for Builder {
+        type Value = Value;
+        type Object = Vec<(String, Value)>;
+        type Array = Vec<Value>;
+        fn begin_object(&mut self) -> Self::Object {
+            Vec::new()
+        }
+        fn key(&mut self, object: &mut Self::Object, key: Cow<'_, str>, value: Value) {
+            object.push((key.into_owned(), value));
+        }
+        fn end_object(&mut self, object: Self::Object) -> Value {
+            Value::Object(object)
+        }
+        fn begin_array(&mut self) -> Self::Array {
+            Vec::new()
+        }
+        fn element(&mut self, array: &mut Self::Array, value: Value) {
+            array.push(value);
+        }
+        fn end_array(&mut self, array: Self::Array) -> Value {
+            Value::Array(array)
+        }
+        fn string(&mut self, s: Cow<'_, str>) -> Value {
+            Value::String(s.into_owned())
+        }
+        fn number(&mut self, num: &str, _: usize) -> Value {
+            Value::Number(num.parse().unwrap())
+        }
+        fn bool(&mut self, b: bool) -> Value {
+            Value::Bool(b)
+        }
+        fn null(&mut self) -> Value {
+            Value::Null
+        }
+    }
+
+    #[test]
+    fn values() {
+        let json = br#" { "a\n" : [ 1 , -2.5e1 , "x\"y" , true , false , null ] , "b" : { } } "#;
+        let value = build(json, 0, &mut Builder).unwrap();
+        assert_eq!(
+            value,
+            Value::Object(vec![
+                (
+                    "a\n".to_string(),
+                    Value::Array(vec![
+                        Value::Number(1.0),
+                        Value::Number(-25.0),
+                        Value::String("x\"y".to_string()),
+                        Value::Bool(true),
+                        Value::Bool(false),
+                        Value::Null,
+                    ])
+                ),
+                ("b".to_string(), Value::Object(vec![])),
+            ])
+        );
+        assert_eq!(build(b" 12 ", 0, &mut Builder), Ok(Value::Number(12.0)));
+        assert_eq!(
+            build(b"[[]]", 0, &mut Builder),
+            Ok(Value::Array(vec![Value::Array(vec![])]))
+        );
+        assert_eq!(
+            build(b"[1,", 0, &mut Builder),
+            Err(ParseError::new(ErrorKind::UnexpectedEof, 3))
+        );
+    }
+
+    // A builder that only counts the values, showing that the value types
+    // can be anything.
+    struct Counter;
+
+    impl TreeBuilder for Counter {
+        type Value = usize;
+        type Object = usize;
+        type Array = usize;
+        fn begin_object(&mut self) -> usize {
+            0
+        }
+        fn key(&mut self, object: &mut usize, _: Cow<'_, str>, value: usize) {
+            *object += value;
+        }
+        fn end_object(&mut self, object: usize) -> usize {
+            object + 1
+        }
+        fn begin_array(&mut self) -> usize {
+            0
+        }
+        fn element(&mut self, array: &mut usize, value: usize) {
+            *array += value;
+        }
+        fn end_array(&mut self, array: usize) -> usize {
+            array + 1
+        }
+        fn string(&mut self, _: Cow<'_, str>) -> usize {
+            1
+        }
+        fn number(&mut self, _: &str, _: usize) -> usize {
+            1
+        }
+        fn bool(&mut self, _: bool) -> usize {
+            1
+        }
+        fn null(&mut self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn counter() {
+        let json = br#"{"a":[1,2,{"b":null}],"c":"d"}"#;
+        assert_eq!(build(json, 0, &mut Counter), Ok(7));
+        // the options that only add elements have no effect
+        for opts in [PAIRS, SPLIT_NUMBERS, ESCAPES, DOC_EVENTS, RECORDS] {
+            assert_eq!(build(json, opts, &mut Counter), Ok(7));
+        }
+    }
+}
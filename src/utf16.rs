@@ -0,0 +1,93 @@
+// Copyright 2021 Joshua J Baker. All rights reserved.
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file.
+
+use crate::*;
+
+/// Transcode UTF-16 json, such as from a Windows data source, into UTF-8 so
+/// that it can be fed to parse and the other functions of this crate.
+///
+/// The units are in native order, so UTF-16LE bytes should first be combined
+/// with u16::from_le_bytes. A leading byte order mark is kept as U+FEFF, which
+/// the CONFIG option skips over. An unpaired surrogate is a Syntax error at
+/// the index of the unit.
+///
+/// The offsets reported by the parser are then byte offsets into the UTF-8
+/// output. Use utf8_to_utf16_offset to turn them back into offsets of units.
+pub fn transcode_utf16_to_utf8(units: &[u16]) -> Result<Vec<u8>, ParseError> {
+    let mut out = Vec::with_capacity(units.len());
+    let mut offset = 0;
+    for ch in std::char::decode_utf16(units.iter().copied()) {
+        match ch {
+            Ok(ch) => {
+                let mut buf = [0; 4];
+                out.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                offset += ch.len_utf16();
+            }
+            Err(_) => return Err(ParseError::new(ErrorKind::Syntax, offset)),
+        }
+    }
+    Ok(out)
+}
+
+/// Convert a byte offset into UTF-8 json, as made by transcode_utf16_to_utf8,
+/// into the offset of the same position in the original UTF-16 units. An
+/// offset past the end of the json is clamped to the end.
+pub fn utf8_to_utf16_offset(json: &[u8], offset: usize) -> usize {
+    let offset = offset.min(json.len());
+    let mut units = 0;
+    for &ch in &json[..offset] {
+        // every character has one leading byte, and those of four bytes are
+        // a surrogate pair
+        if ch & 0xC0 != 0x80 {
+            units += if ch >= 0xF0 { 2 } else { 1 };
+        }
+    }
+    units
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcode() {
+        let src = "{\"a\":\"h\u{e9}llo \u{1F600}\",\"b\":[1,2]}";
+        let units: Vec<u16> = src.encode_utf16().collect();
+        let json = transcode_utf16_to_utf8(&units).unwrap();
+        assert_eq!(json, src.as_bytes());
+        assert_eq!(parse(&json, 0, |_, _, _| 1), json.len() as i64);
+        // map the offsets of elements back to units
+        let tokens = parse_to_vec(&json, 0).unwrap();
+        let b = tokens
+            .iter()
+            .find(|t| &json[t.start..t.end] == b"\"b\"")
+            .unwrap();
+        let start = utf8_to_utf16_offset(&json, b.start);
+        assert_eq!(start, 16);
+        assert_eq!(
+            String::from_utf16(&units[start..start + 3]).unwrap(),
+            "\"b\""
+        );
+        assert_eq!(utf8_to_utf16_offset(&json, json.len()), units.len());
+        assert_eq!(utf8_to_utf16_offset(&json, 999), units.len());
+    }
+
+    #[test]
+    fn bom_and_surrogates() {
+        let units: Vec<u16> = "\u{feff}[1]".encode_utf16().collect();
+        let json = transcode_utf16_to_utf8(&units).unwrap();
+        assert_eq!(parse(&json, CONFIG, |_, _, _| 1), json.len() as i64);
+        assert!(parse(&json, 0, |_, _, _| 1) <= 0);
+        let units = [b'"' as u16, 0xD800, b'"' as u16];
+        assert_eq!(
+            transcode_utf16_to_utf8(&units),
+            Err(ParseError::new(ErrorKind::Syntax, 1))
+        );
+        let units = [b'[' as u16, 0xD83D, 0xDE00, 0xDC00];
+        assert_eq!(
+            transcode_utf16_to_utf8(&units),
+            Err(ParseError::new(ErrorKind::Syntax, 3))
+        );
+    }
+}